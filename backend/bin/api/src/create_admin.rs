@@ -0,0 +1,69 @@
+//! `api create-admin`: bootstrap the first admin account.
+//!
+//! ```text
+//! api create-admin --citizen-id ID --first-name NAME --last-name NAME --phone PHONE [--email EMAIL]
+//! ```
+//!
+//! The password is taken from `ADMIN_PASSWORD`, or read from the first line of
+//! stdin so it stays out of shell history.
+use auth_service::domain::NewAdminInput;
+use sqlx::PgPool;
+use std::{env, io::BufRead};
+
+const USAGE: &str = "usage: api create-admin --citizen-id ID --first-name NAME --last-name NAME --phone PHONE [--email EMAIL]";
+
+pub async fn run(pool: PgPool, args: &[String]) -> anyhow::Result<()> {
+    let input = parse_args(args)?;
+    let user_id = auth_service::bootstrap_admin(pool, input).await?;
+    tracing::info!(%user_id, "admin account created");
+    println!("{user_id}");
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> anyhow::Result<NewAdminInput> {
+    let mut citizen_id = None;
+    let mut first_name = None;
+    let mut last_name = None;
+    let mut phone = None;
+    let mut email = None;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let slot = match flag.as_str() {
+            "--citizen-id" => &mut citizen_id,
+            "--first-name" => &mut first_name,
+            "--last-name" => &mut last_name,
+            "--phone" => &mut phone,
+            "--email" => &mut email,
+            other => anyhow::bail!("unknown argument `{other}`\n{USAGE}"),
+        };
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("missing value for `{flag}`\n{USAGE}"))?;
+        *slot = Some(value.clone());
+    }
+    let required = |value: Option<String>, flag: &str| {
+        value.ok_or_else(|| anyhow::anyhow!("missing `{flag}`\n{USAGE}"))
+    };
+    Ok(NewAdminInput {
+        citizen_id: required(citizen_id, "--citizen-id")?,
+        first_name: required(first_name, "--first-name")?,
+        last_name: required(last_name, "--last-name")?,
+        phone: required(phone, "--phone")?,
+        email,
+        password: read_password()?,
+    })
+}
+
+fn read_password() -> anyhow::Result<String> {
+    if let Ok(password) = env::var("ADMIN_PASSWORD") {
+        return Ok(password);
+    }
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let password = line.trim_end_matches(['\r', '\n']).to_owned();
+    anyhow::ensure!(
+        !password.is_empty(),
+        "set ADMIN_PASSWORD or pass the password on stdin"
+    );
+    Ok(password)
+}
@@ -1,10 +1,12 @@
 use axum::Router;
 use common::config::AppConfig;
 use db::{connect, migrate};
-use std::env;
+use std::{env, net::SocketAddr};
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing_subscriber::{EnvFilter, fmt};
 
+mod create_admin;
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     fmt()
@@ -23,14 +25,23 @@ async fn main() -> anyhow::Result<()> {
         tracing::warn!("SKIP_MIGRATIONS set; skipping database migrations");
     }
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("create-admin") {
+        return create_admin::run(pool, &args[1..]).await;
+    }
+
     // Service routers
     let appt = appointment_service::router(pool.clone());
     let auth = auth_service::router(pool.clone());
+    let admin = auth_service::admin_router(pool.clone());
     let diag = diagnosis_service::router(pool.clone());
     let rx = prescription_service::router(pool.clone());
     let order = order_service::router(pool.clone());
     let ship = shipping_service::router(pool.clone());
 
+    // Background jobs
+    appointment_service::spawn_jobs(pool.clone(), &cfg);
+
     // OpenAPI/Swagger
     let openapi = openapi::router::<openapi::ApiDoc>();
 
@@ -38,18 +49,20 @@ async fn main() -> anyhow::Result<()> {
         .nest(
             "/api",
             appt.merge(auth)
+                .merge(admin)
                 .merge(diag)
                 .merge(rx)
                 .merge(order)
                 .merge(ship),
         )
+        .merge(auth_service::well_known_router())
         .nest("/docs", openapi)
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive());
 
     tracing::info!("listening on {}", cfg.bind_addr);
     axum_server::bind(cfg.bind_addr.parse()?)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await?;
     Ok(())
 }
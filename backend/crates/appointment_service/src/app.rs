@@ -1,32 +1,1128 @@
 use crate::domain::*;
-use common::error::AppResult;
+use axum::http::StatusCode;
+use common::{
+    config::BookingRules,
+    error::{AppError, AppResult},
+    notify::{Channel, Notification},
+};
 use db::PgTx;
+use std::cmp::Ordering;
+use time::{
+    Date, Duration, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset, macros::format_description,
+};
+use uuid::Uuid;
 
 pub trait AppointmentRepo: Send + Sync {
     #[expect(async_fn_in_trait)]
     async fn create(&self, tx: &mut PgTx<'_>, cmd: NewAppointment) -> AppResult<Appointment>;
+    /// Lock the timeslot row until `tx` ends.
+    #[expect(async_fn_in_trait)]
+    async fn lock_timeslot(
+        &self,
+        tx: &mut PgTx<'_>,
+        timeslot_id: i32,
+    ) -> AppResult<Option<LockedTimeslot>>;
+    /// Why the timeslot is not held on `date` (clinic holiday, doctor leave,
+    /// cancelled occurrence or a one-off timeslot on another date), if so.
+    #[expect(async_fn_in_trait)]
+    async fn schedule_block(
+        &self,
+        tx: &mut PgTx<'_>,
+        timeslot_id: i32,
+        date: Date,
+    ) -> AppResult<Option<String>>;
+    /// Places taken in the timeslot on `date`: appointments other than
+    /// REJECTED and CANCELED ones, plus live waitlist holds.
+    #[expect(async_fn_in_trait)]
+    async fn booked_count(&self, tx: &mut PgTx<'_>, timeslot_id: i32, date: Date)
+    -> AppResult<i64>;
+    /// Upcoming PENDING appointments of the patient from `from` on. Locks the
+    /// patient row first so concurrent bookings by one patient serialize.
+    #[expect(async_fn_in_trait)]
+    async fn pending_count(
+        &self,
+        tx: &mut PgTx<'_>,
+        patient_id: Uuid,
+        from: Date,
+    ) -> AppResult<i64>;
+    /// Lock the appointment row until `tx` ends.
+    #[expect(async_fn_in_trait)]
+    async fn lock_appointment(&self, tx: &mut PgTx<'_>, id: i32) -> AppResult<Option<Appointment>>;
+    /// The reschedule policy of the doctor holding the timeslot.
+    #[expect(async_fn_in_trait)]
+    async fn keeps_accepted_on_reschedule(
+        &self,
+        tx: &mut PgTx<'_>,
+        timeslot_id: i32,
+    ) -> AppResult<bool>;
+    #[expect(async_fn_in_trait)]
+    async fn move_appointment(
+        &self,
+        tx: &mut PgTx<'_>,
+        cmd: MoveAppointment,
+    ) -> AppResult<Appointment>;
     #[expect(async_fn_in_trait)]
     async fn by_id(&self, id: i32) -> AppResult<Option<Appointment>>;
+    /// Set the status and append the change to the transition log.
+    #[expect(async_fn_in_trait)]
+    async fn set_status(
+        &self,
+        tx: &mut PgTx<'_>,
+        from: AppointmentStatus,
+        cmd: StatusChange,
+    ) -> AppResult<Appointment>;
+    /// Free places across the doctor's timeslots held on `date` that start at
+    /// `earliest` or later, or `None` when there is no such timeslot.
+    #[expect(async_fn_in_trait)]
+    async fn free_places(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+        date: Date,
+        earliest: PrimitiveDateTime,
+    ) -> AppResult<Option<i64>>;
+    #[expect(async_fn_in_trait)]
+    async fn join_waitlist(
+        &self,
+        tx: &mut PgTx<'_>,
+        patient_id: Uuid,
+        doctor_id: Uuid,
+        date: Date,
+    ) -> AppResult<WaitlistEntry>;
+    /// Lock the waitlist entry until `tx` ends.
+    #[expect(async_fn_in_trait)]
+    async fn lock_waitlist_entry(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+    ) -> AppResult<Option<WaitlistEntry>>;
+    /// Move the entry out of WAITING/OFFERED, releasing any held place.
+    #[expect(async_fn_in_trait)]
+    async fn close_waitlist_entry(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+        status: WaitlistStatus,
+    ) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
+    async fn attach_waitlist_appointment(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+        appointment_id: i32,
+    ) -> AppResult<()>;
+    /// Hold a place in the timeslot for the longest-waiting patient of the
+    /// doctor on `date`, if anyone is waiting.
+    #[expect(async_fn_in_trait)]
+    async fn offer_next(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+        timeslot_id: i32,
+        date: Date,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<Option<WaitlistOffer>>;
+    /// The patient's contact details and the slot of a waitlist offer.
+    #[expect(async_fn_in_trait)]
+    async fn offer_contact(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+    ) -> AppResult<Option<OfferContact>>;
+    /// Queue a message about the offer on the entry, to be sent after commit.
+    #[expect(async_fn_in_trait)]
+    async fn queue_offer_notice(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+        notification: &Notification,
+    ) -> AppResult<()>;
+    /// Expire lapsed offers and entries still waiting for a date before
+    /// `today`; returns the lapsed offers. Entries locked elsewhere are
+    /// skipped until the next sweep.
     #[expect(async_fn_in_trait)]
-    async fn accept(&self, tx: &mut PgTx<'_>, id: i32) -> AppResult<()>;
+    async fn expire_waitlist(
+        &self,
+        tx: &mut PgTx<'_>,
+        today: Date,
+    ) -> AppResult<Vec<WaitlistOffer>>;
+    /// The intake questionnaire of the doctor's department, in order.
+    #[expect(async_fn_in_trait)]
+    async fn intake_questions(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+    ) -> AppResult<Vec<IntakeQuestion>>;
+    #[expect(async_fn_in_trait)]
+    async fn save_intake(
+        &self,
+        tx: &mut PgTx<'_>,
+        appointment_id: i32,
+        intake: &VisitIntake,
+    ) -> AppResult<()>;
+    /// Give the appointment the next number in its doctor's queue for the
+    /// appointment date.
+    #[expect(async_fn_in_trait)]
+    async fn take_queue_number(&self, tx: &mut PgTx<'_>, appointment_id: i32) -> AppResult<i32>;
+    /// Lock the doctor's queue for `date` until `tx` ends; `false` when no
+    /// one has checked in that day.
+    #[expect(async_fn_in_trait)]
+    async fn lock_queue(&self, tx: &mut PgTx<'_>, doctor_id: Uuid, date: Date) -> AppResult<bool>;
+    /// The checked-in appointment the doctor has called in on `date`.
+    #[expect(async_fn_in_trait)]
+    async fn in_consultation(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+        date: Date,
+    ) -> AppResult<Option<i32>>;
+    /// Call in the waiting appointment with the lowest queue number.
+    #[expect(async_fn_in_trait)]
+    async fn call_next(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+        date: Date,
+    ) -> AppResult<Option<i32>>;
 }
 
+const MAX_CHIEF_COMPLAINT_CHARS: usize = 200;
+const MAX_NOTES_CHARS: usize = 2000;
+const MAX_ANSWER_CHARS: usize = 1000;
+
 #[derive(Clone)]
 pub struct AppointmentService<R: AppointmentRepo> {
     pub repo: R,
+    rules: BookingRules,
+    clinic_offset: UtcOffset,
 }
 
 impl<R: AppointmentRepo> AppointmentService<R> {
-    pub fn new(repo: R) -> Self {
-        Self { repo }
+    pub fn new(repo: R, rules: BookingRules, clinic_utc_offset_minutes: i32) -> Self {
+        let clinic_offset =
+            UtcOffset::from_whole_seconds(clinic_utc_offset_minutes * 60).unwrap_or(UtcOffset::UTC);
+        Self {
+            repo,
+            rules,
+            clinic_offset,
+        }
+    }
+
+    /// Today in clinic local time.
+    pub fn today(&self) -> Date {
+        OffsetDateTime::now_utc()
+            .to_offset(self.clinic_offset)
+            .date()
     }
 
+    /// Book a place in the timeslot. Concurrent bookings of the same
+    /// timeslot serialize on its row lock, so capacity cannot be exceeded.
+    /// The visit reason is checked against the questionnaire of the doctor's
+    /// department and stored with the appointment.
     pub async fn book(&self, tx: &mut PgTx<'_>, cmd: NewAppointment) -> AppResult<Appointment> {
-        // DB uniqueness & exclusion constraints already enforce conflicts.
-        self.repo.create(tx, cmd).await
+        let slot = self.lock_slot(tx, cmd.timeslot_id).await?;
+        self.check_place(tx, &slot, cmd.timeslot_id, cmd.date)
+            .await?;
+        let pending = self
+            .repo
+            .pending_count(tx, cmd.patient_id, self.today())
+            .await?;
+        if pending >= self.rules.max_pending_per_patient {
+            return Err(BookingError::TooManyPending {
+                max_pending: self.rules.max_pending_per_patient,
+            }
+            .into());
+        }
+        let questions = self.repo.intake_questions(tx, slot.doctor_id).await?;
+        let intake = check_intake(&questions, &cmd.intake)?;
+        let appt = self.repo.create(tx, cmd).await?;
+        self.repo
+            .save_intake(tx, appt.appointment_id, &intake)
+            .await?;
+        Ok(appt)
+    }
+
+    /// Cancel the patient's own appointment. ACCEPTED appointments cannot be
+    /// cancelled within the cancellation cutoff, and checked-in visits only
+    /// by clinic staff.
+    pub async fn cancel(
+        &self,
+        tx: &mut PgTx<'_>,
+        appointment_id: i32,
+        patient_id: Uuid,
+    ) -> AppResult<Appointment> {
+        let current = self
+            .repo
+            .lock_appointment(tx, appointment_id)
+            .await?
+            .filter(|appt| appt.patient_id == patient_id)
+            .ok_or(AppError::NotFound)?;
+        match current.status {
+            AppointmentStatus::ACCEPTED => {
+                let slot = self.lock_slot(tx, current.timeslot_id).await?;
+                self.check_cutoff(current.date, slot.start_time)?;
+            }
+            AppointmentStatus::CHECKED_IN => {
+                return Err(AppError::Conflict(
+                    "a checked-in visit can only be cancelled by the clinic".into(),
+                ));
+            }
+            _ => {}
+        }
+        self.change_status(
+            tx,
+            StatusChange {
+                appointment_id,
+                to: AppointmentStatus::CANCELED,
+                actor_id: patient_id,
+                note: None,
+            },
+        )
+        .await
+    }
+
+    /// Move a PENDING or ACCEPTED appointment to another slot of the same
+    /// doctor. An ACCEPTED appointment stays ACCEPTED only if the doctor's
+    /// policy says so; otherwise it needs accepting again.
+    pub async fn reschedule(
+        &self,
+        tx: &mut PgTx<'_>,
+        cmd: RescheduleAppointment,
+    ) -> AppResult<Appointment> {
+        let current = self
+            .repo
+            .lock_appointment(tx, cmd.appointment_id)
+            .await?
+            .filter(|appt| appt.patient_id == cmd.patient_id)
+            .ok_or(AppError::NotFound)?;
+        if !current.status.is_open() {
+            return Err(AppError::Conflict(
+                "only pending or accepted appointments can be rescheduled".into(),
+            ));
+        }
+        if current.timeslot_id == cmd.timeslot_id && current.date == cmd.date {
+            return Err(AppError::BadRequest(
+                "the appointment is already in this slot".into(),
+            ));
+        }
+        // Lock both timeslots in id order so that patients moving in opposite
+        // directions between the same two timeslots cannot deadlock.
+        let (from, to) = match current.timeslot_id.cmp(&cmd.timeslot_id) {
+            Ordering::Equal => {
+                let slot = self.lock_slot(tx, cmd.timeslot_id).await?;
+                (slot, slot)
+            }
+            Ordering::Less => {
+                let from = self.lock_slot(tx, current.timeslot_id).await?;
+                (from, self.lock_slot(tx, cmd.timeslot_id).await?)
+            }
+            Ordering::Greater => {
+                let to = self.lock_slot(tx, cmd.timeslot_id).await?;
+                (self.lock_slot(tx, current.timeslot_id).await?, to)
+            }
+        };
+        if current.status == AppointmentStatus::ACCEPTED {
+            self.check_cutoff(current.date, from.start_time)?;
+        }
+        if to.doctor_id != from.doctor_id {
+            return Err(BookingError::OtherDoctor.into());
+        }
+        self.check_place(tx, &to, cmd.timeslot_id, cmd.date).await?;
+        let keep_accepted = matches!(current.status, AppointmentStatus::ACCEPTED)
+            && self
+                .repo
+                .keeps_accepted_on_reschedule(tx, cmd.timeslot_id)
+                .await?;
+        let status = if keep_accepted {
+            AppointmentStatus::ACCEPTED
+        } else {
+            AppointmentStatus::PENDING
+        };
+        let moved = self
+            .repo
+            .move_appointment(
+                tx,
+                MoveAppointment {
+                    appointment_id: cmd.appointment_id,
+                    timeslot_id: cmd.timeslot_id,
+                    date: cmd.date,
+                    status,
+                    moved_by: cmd.patient_id,
+                },
+            )
+            .await?;
+        self.offer_freed_place(tx, current.timeslot_id, current.date)
+            .await?;
+        Ok(moved)
+    }
+
+    async fn lock_slot(&self, tx: &mut PgTx<'_>, timeslot_id: i32) -> AppResult<LockedTimeslot> {
+        self.repo
+            .lock_timeslot(tx, timeslot_id)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    /// Check that the locked timeslot can be booked on `date`: inside the
+    /// booking window, held that day and not full.
+    async fn check_place(
+        &self,
+        tx: &mut PgTx<'_>,
+        slot: &LockedTimeslot,
+        timeslot_id: i32,
+        date: Date,
+    ) -> AppResult<()> {
+        self.check_window(date, slot.start_time)?;
+        if let Some(reason) = self.repo.schedule_block(tx, timeslot_id, date).await? {
+            return Err(BookingError::Unavailable(reason).into());
+        }
+        let booked = self.repo.booked_count(tx, timeslot_id, date).await?;
+        if booked >= i64::from(slot.capacity) {
+            return Err(BookingError::TimeslotFull.into());
+        }
+        Ok(())
+    }
+
+    /// The instant a slot time on `date` falls on, in clinic local time.
+    pub fn slot_start(&self, date: Date, start_time: Time) -> OffsetDateTime {
+        PrimitiveDateTime::new(date, start_time).assume_offset(self.clinic_offset)
+    }
+
+    /// The earliest slot start, in clinic local time, and the last date
+    /// that can be booked right now.
+    pub fn booking_window(&self) -> (PrimitiveDateTime, Date) {
+        let earliest = OffsetDateTime::now_utc().to_offset(self.clinic_offset)
+            + Duration::minutes(self.rules.min_lead_minutes);
+        (
+            PrimitiveDateTime::new(earliest.date(), earliest.time()),
+            self.today() + Duration::days(self.rules.max_horizon_days),
+        )
+    }
+
+    fn check_window(&self, date: Date, start_time: Time) -> Result<(), BookingError> {
+        check_window_at(
+            &self.rules,
+            self.clinic_offset,
+            date,
+            start_time,
+            OffsetDateTime::now_utc(),
+        )
+    }
+
+    fn check_cutoff(&self, date: Date, start_time: Time) -> Result<(), BookingError> {
+        check_cutoff_at(
+            &self.rules,
+            self.clinic_offset,
+            date,
+            start_time,
+            OffsetDateTime::now_utc(),
+        )
+    }
+
+    /// Move the appointment along its state machine, rejecting illegal
+    /// moves with `409`. Check-in happens on the appointment date and takes
+    /// a queue number; a visit cannot be completed or marked as a no-show
+    /// before that date.
+    pub async fn change_status(
+        &self,
+        tx: &mut PgTx<'_>,
+        cmd: StatusChange,
+    ) -> AppResult<Appointment> {
+        let current = self
+            .repo
+            .lock_appointment(tx, cmd.appointment_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        current.status.transition_to(cmd.to)?;
+        let today = self.today();
+        match cmd.to {
+            AppointmentStatus::CHECKED_IN if current.date != today => {
+                return Err(AppError::Conflict(
+                    "patients can only check in on the appointment date".into(),
+                ));
+            }
+            AppointmentStatus::COMPLETED | AppointmentStatus::NO_SHOW if current.date > today => {
+                return Err(AppError::Conflict(format!(
+                    "an appointment cannot be marked {} before its date",
+                    cmd.to.as_str()
+                )));
+            }
+            _ => {}
+        }
+        let frees_place = matches!(
+            cmd.to,
+            AppointmentStatus::REJECTED | AppointmentStatus::CANCELED
+        );
+        let appt = self.repo.set_status(tx, current.status, cmd).await?;
+        if appt.status == AppointmentStatus::CHECKED_IN {
+            self.repo.take_queue_number(tx, appt.appointment_id).await?;
+        }
+        if frees_place {
+            self.offer_freed_place(tx, appt.timeslot_id, appt.date)
+                .await?;
+        }
+        Ok(appt)
+    }
+
+    /// Complete the visit in progress, if any, and call in the patient with
+    /// the lowest queue number still waiting today. Returns the called
+    /// appointment, or `None` when no one is waiting.
+    pub async fn call_next(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+        actor_id: Uuid,
+    ) -> AppResult<Option<i32>> {
+        let today = self.today();
+        if !self.repo.lock_queue(tx, doctor_id, today).await? {
+            return Ok(None);
+        }
+        if let Some(current) = self.repo.in_consultation(tx, doctor_id, today).await? {
+            self.change_status(
+                tx,
+                StatusChange {
+                    appointment_id: current,
+                    to: AppointmentStatus::COMPLETED,
+                    actor_id,
+                    note: None,
+                },
+            )
+            .await?;
+        }
+        self.repo.call_next(tx, doctor_id, today).await
+    }
+
+    /// Queue the patient for the doctor on `date`. Only allowed when every
+    /// place the doctor offers that day is taken.
+    pub async fn join_waitlist(
+        &self,
+        tx: &mut PgTx<'_>,
+        patient_id: Uuid,
+        doctor_id: Uuid,
+        date: Date,
+    ) -> AppResult<WaitlistEntry> {
+        let today = self.today();
+        if date < today {
+            return Err(BookingError::InPast.into());
+        }
+        if date > today + Duration::days(self.rules.max_horizon_days) {
+            return Err(BookingError::TooFar {
+                max_horizon_days: self.rules.max_horizon_days,
+            }
+            .into());
+        }
+        // Slots already started or inside the lead time cannot be booked, so
+        // their places do not count as free.
+        let (earliest, _) = self.booking_window();
+        match self.repo.free_places(tx, doctor_id, date, earliest).await? {
+            None => {
+                return Err(BookingError::Unavailable(
+                    "the doctor has no timeslots left to book on this date".into(),
+                )
+                .into());
+            }
+            Some(free) if free > 0 => return Err(BookingError::NotFull.into()),
+            Some(_) => {}
+        }
+        self.repo
+            .join_waitlist(tx, patient_id, doctor_id, date)
+            .await
+    }
+
+    /// Turn the place held for the patient into a PENDING appointment,
+    /// subject to the usual booking rules and the intake questionnaire.
+    pub async fn confirm_offer(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+        patient_id: Uuid,
+        intake: VisitIntakeReq,
+    ) -> AppResult<Appointment> {
+        let entry = self
+            .repo
+            .lock_waitlist_entry(tx, entry_id)
+            .await?
+            .filter(|entry| entry.patient_id == patient_id)
+            .ok_or(AppError::NotFound)?;
+        let (timeslot_id, expires_at) = match entry {
+            WaitlistEntry {
+                status: WaitlistStatus::Offered,
+                offered_timeslot_id: Some(timeslot_id),
+                offer_expires_at: Some(expires_at),
+                ..
+            } => (timeslot_id, expires_at),
+            WaitlistEntry {
+                status: WaitlistStatus::Expired,
+                offered_timeslot_id: Some(_),
+                ..
+            } => return Err(BookingError::OfferExpired.into()),
+            _ => return Err(BookingError::NoOpenOffer.into()),
+        };
+        if expires_at <= OffsetDateTime::now_utc() {
+            return Err(BookingError::OfferExpired.into());
+        }
+        // Release the hold first so the booking below can take its place.
+        self.repo
+            .close_waitlist_entry(tx, entry_id, WaitlistStatus::Confirmed)
+            .await?;
+        let appt = self
+            .book(
+                tx,
+                NewAppointment {
+                    patient_id,
+                    timeslot_id,
+                    date: entry.date,
+                    intake,
+                },
+            )
+            .await?;
+        self.repo
+            .attach_waitlist_appointment(tx, entry_id, appt.appointment_id)
+            .await?;
+        Ok(appt)
+    }
+
+    /// Take the patient off the waitlist, passing a held place on.
+    pub async fn leave_waitlist(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+        patient_id: Uuid,
+    ) -> AppResult<()> {
+        let entry = self
+            .repo
+            .lock_waitlist_entry(tx, entry_id)
+            .await?
+            .filter(|entry| entry.patient_id == patient_id)
+            .ok_or(AppError::NotFound)?;
+        if !matches!(
+            entry.status,
+            WaitlistStatus::Waiting | WaitlistStatus::Offered
+        ) {
+            return Err(AppError::Conflict(format!(
+                "the waitlist entry is already {}",
+                entry.status.as_str()
+            )));
+        }
+        self.repo
+            .close_waitlist_entry(tx, entry_id, WaitlistStatus::Left)
+            .await?;
+        if let (WaitlistStatus::Offered, Some(timeslot_id)) =
+            (entry.status, entry.offered_timeslot_id)
+        {
+            self.offer_freed_place(tx, timeslot_id, entry.date).await?;
+        }
+        Ok(())
+    }
+
+    /// Expire lapsed offers and pass each held place on to the next patient
+    /// waiting. Returns the new offers.
+    pub async fn expire_waitlist_offers(&self, tx: &mut PgTx<'_>) -> AppResult<Vec<WaitlistOffer>> {
+        let lapsed = self.repo.expire_waitlist(tx, self.today()).await?;
+        let mut offers = Vec::new();
+        for offer in lapsed {
+            if let Some(next) = self
+                .offer_freed_place(tx, offer.timeslot_id, offer.date)
+                .await?
+            {
+                offers.push(next);
+            }
+        }
+        Ok(offers)
+    }
+
+    /// Offer a place that just came free in the timeslot to the next
+    /// waitlisted patient. Nothing is offered when the place is not actually
+    /// free or the slot is too close to be booked. The hold never outlasts
+    /// the minimum booking lead time.
+    async fn offer_freed_place(
+        &self,
+        tx: &mut PgTx<'_>,
+        timeslot_id: i32,
+        date: Date,
+    ) -> AppResult<Option<WaitlistOffer>> {
+        let Some(slot) = self.repo.lock_timeslot(tx, timeslot_id).await? else {
+            return Ok(None);
+        };
+        if self.check_window(date, slot.start_time).is_err()
+            || self
+                .repo
+                .schedule_block(tx, timeslot_id, date)
+                .await?
+                .is_some()
+            || self.repo.booked_count(tx, timeslot_id, date).await? >= i64::from(slot.capacity)
+        {
+            return Ok(None);
+        }
+        let hold_until =
+            OffsetDateTime::now_utc() + Duration::minutes(self.rules.waitlist_hold_minutes);
+        let book_by =
+            self.slot_start(date, slot.start_time) - Duration::minutes(self.rules.min_lead_minutes);
+        let offer = self
+            .repo
+            .offer_next(
+                tx,
+                slot.doctor_id,
+                timeslot_id,
+                date,
+                hold_until.min(book_by),
+            )
+            .await?;
+        if let Some(offer) = &offer {
+            self.queue_offer_notice(tx, offer).await?;
+        }
+        Ok(offer)
+    }
+
+    /// Queue a message telling the patient which place is held for them and
+    /// until when, by email when they have one and by SMS otherwise. It is
+    /// sent once `tx` commits; the hold stands whether or not it arrives.
+    async fn queue_offer_notice(&self, tx: &mut PgTx<'_>, offer: &WaitlistOffer) -> AppResult<()> {
+        let Some(contact) = self.repo.offer_contact(tx, offer.entry_id).await? else {
+            return Ok(());
+        };
+        let (channel, to) = match contact.email {
+            Some(email) => (Channel::Email, email),
+            None => (Channel::Sms, contact.phone),
+        };
+        let date_fmt = format_description!("[year]-[month]-[day]");
+        let time_fmt = format_description!("[hour]:[minute]");
+        let deadline_fmt = format_description!("[year]-[month]-[day] [hour]:[minute]");
+        let expires_at = offer.expires_at.to_offset(self.clinic_offset);
+        let notification = Notification {
+            channel,
+            to,
+            subject: "A place is held for you".into(),
+            body: format!(
+                "A place with {} on {} at {} ({}) has come free and is held for you until {}. \
+                 Confirm it from your waitlist before then or it goes to the next patient.",
+                contact.doctor_name,
+                offer.date.format(&date_fmt).expect("valid date format"),
+                contact
+                    .start_time
+                    .format(&time_fmt)
+                    .expect("valid time format"),
+                contact.place_name,
+                expires_at
+                    .format(&deadline_fmt)
+                    .expect("valid deadline format"),
+            ),
+        };
+        self.repo
+            .queue_offer_notice(tx, offer.entry_id, &notification)
+            .await
+    }
+}
+
+/// Whether a slot starting at `start_time` on `date`, clinic local time, can
+/// be booked at `now`: it has not started, is at least the minimum lead time
+/// away and falls within the booking horizon.
+fn check_window_at(
+    rules: &BookingRules,
+    clinic_offset: UtcOffset,
+    date: Date,
+    start_time: Time,
+    now: OffsetDateTime,
+) -> Result<(), BookingError> {
+    let lead = PrimitiveDateTime::new(date, start_time).assume_offset(clinic_offset) - now;
+    if lead <= Duration::ZERO {
+        return Err(BookingError::InPast);
+    }
+    if lead < Duration::minutes(rules.min_lead_minutes) {
+        return Err(BookingError::TooSoon {
+            min_lead_minutes: rules.min_lead_minutes,
+        });
+    }
+    let today = now.to_offset(clinic_offset).date();
+    if date > today + Duration::days(rules.max_horizon_days) {
+        return Err(BookingError::TooFar {
+            max_horizon_days: rules.max_horizon_days,
+        });
+    }
+    Ok(())
+}
+
+/// Whether an ACCEPTED visit at `start_time` on `date` can still be cancelled
+/// or moved at `now`: at least the cancellation cutoff must remain.
+fn check_cutoff_at(
+    rules: &BookingRules,
+    clinic_offset: UtcOffset,
+    date: Date,
+    start_time: Time,
+    now: OffsetDateTime,
+) -> Result<(), BookingError> {
+    let lead = PrimitiveDateTime::new(date, start_time).assume_offset(clinic_offset) - now;
+    if lead < Duration::hours(rules.cancellation_cutoff_hours) {
+        return Err(BookingError::CancellationCutoff {
+            cutoff_hours: rules.cancellation_cutoff_hours,
+        });
+    }
+    Ok(())
+}
+
+/// Trim the visit reason and match the answers to `questions`: every answer
+/// must belong to a question, required questions must be answered, and
+/// yes/no and choice answers are stored in their canonical spelling. Answers
+/// come back in questionnaire order with the question wording attached.
+fn check_intake(questions: &[IntakeQuestion], req: &VisitIntakeReq) -> AppResult<VisitIntake> {
+    fn optional(field: &str, value: Option<&str>, max_chars: usize) -> AppResult<Option<String>> {
+        let value = value.map(str::trim).filter(|v| !v.is_empty());
+        if value.is_some_and(|v| v.chars().count() > max_chars) {
+            return Err(AppError::BadRequest(format!(
+                "{field} must be at most {max_chars} characters"
+            )));
+        }
+        Ok(value.map(str::to_owned))
+    }
+    let chief_complaint = optional(
+        "chief_complaint",
+        req.chief_complaint.as_deref(),
+        MAX_CHIEF_COMPLAINT_CHARS,
+    )?;
+    let notes = optional("notes", req.notes.as_deref(), MAX_NOTES_CHARS)?;
+
+    for (i, answer) in req.answers.iter().enumerate() {
+        if !questions
+            .iter()
+            .any(|q| q.question_id == answer.question_id)
+        {
+            return Err(AppError::BadRequest(format!(
+                "question {} is not part of the doctor's questionnaire",
+                answer.question_id
+            )));
+        }
+        if req.answers[..i]
+            .iter()
+            .any(|other| other.question_id == answer.question_id)
+        {
+            return Err(AppError::BadRequest(format!(
+                "question {} is answered more than once",
+                answer.question_id
+            )));
+        }
+    }
+
+    let mut answers = Vec::new();
+    for question in questions {
+        let given = req
+            .answers
+            .iter()
+            .find(|a| a.question_id == question.question_id)
+            .map(|a| a.answer.trim())
+            .filter(|a| !a.is_empty());
+        let Some(given) = given else {
+            if question.required {
+                return Err(AppError::BadRequest(format!(
+                    "question {} ({}) must be answered",
+                    question.question_id, question.prompt
+                )));
+            }
+            continue;
+        };
+        let answer = match question.kind {
+            IntakeQuestionKind::Text => {
+                if given.chars().count() > MAX_ANSWER_CHARS {
+                    return Err(AppError::BadRequest(format!(
+                        "the answer to question {} must be at most {MAX_ANSWER_CHARS} characters",
+                        question.question_id
+                    )));
+                }
+                given.to_owned()
+            }
+            IntakeQuestionKind::YesNo => match given.to_ascii_lowercase().as_str() {
+                "yes" => "yes".to_owned(),
+                "no" => "no".to_owned(),
+                _ => {
+                    return Err(AppError::BadRequest(format!(
+                        "the answer to question {} must be yes or no",
+                        question.question_id
+                    )));
+                }
+            },
+            IntakeQuestionKind::Choice => question
+                .options
+                .iter()
+                .find(|option| option.to_lowercase() == given.to_lowercase())
+                .cloned()
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "the answer to question {} must be one of: {}",
+                        question.question_id,
+                        question.options.join(", ")
+                    ))
+                })?,
+        };
+        answers.push(IntakeAnswer {
+            question_id: Some(question.question_id),
+            prompt: question.prompt.clone(),
+            answer,
+        });
+    }
+    Ok(VisitIntake {
+        chief_complaint,
+        notes,
+        answers,
+    })
+}
+
+impl From<IllegalTransition> for AppError {
+    fn from(err: IllegalTransition) -> Self {
+        AppError::Conflict(err.to_string())
+    }
+}
+
+impl From<BookingError> for AppError {
+    fn from(err: BookingError) -> Self {
+        let status = match err {
+            BookingError::InPast | BookingError::TooSoon { .. } | BookingError::TooFar { .. } => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            _ => StatusCode::CONFLICT,
+        };
+        AppError::Rule {
+            status,
+            code: err.code(),
+            message: err.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{date, datetime, offset, time};
+
+    const CLINIC: UtcOffset = offset!(+7);
+    /// 06:30 on Oct 18 at the clinic, still Oct 17 in UTC.
+    const NOW: OffsetDateTime = datetime!(2026-10-17 23:30 UTC);
+
+    fn rules() -> BookingRules {
+        BookingRules {
+            min_lead_minutes: 60,
+            max_horizon_days: 90,
+            cancellation_cutoff_hours: 24,
+            max_pending_per_patient: 3,
+            waitlist_hold_minutes: 30,
+        }
+    }
+
+    fn window(date: Date, start_time: Time) -> Result<(), BookingError> {
+        check_window_at(&rules(), CLINIC, date, start_time, NOW)
+    }
+
+    fn cutoff(date: Date, start_time: Time) -> Result<(), BookingError> {
+        check_cutoff_at(&rules(), CLINIC, date, start_time, NOW)
+    }
+
+    #[test]
+    fn slot_exactly_the_lead_time_away_can_be_booked() {
+        assert!(window(date!(2026 - 10 - 18), time!(07:30)).is_ok());
+    }
+
+    #[test]
+    fn slot_a_minute_inside_the_lead_time_is_too_soon() {
+        assert!(matches!(
+            window(date!(2026 - 10 - 18), time!(07:29)),
+            Err(BookingError::TooSoon {
+                min_lead_minutes: 60
+            })
+        ));
+    }
+
+    #[test]
+    fn slot_that_has_started_is_in_the_past() {
+        assert!(matches!(
+            window(date!(2026 - 10 - 18), time!(06:30)),
+            Err(BookingError::InPast)
+        ));
+    }
+
+    #[test]
+    fn horizon_counts_from_the_clinic_date() {
+        // 90 days after Oct 18, the clinic's today.
+        assert!(window(date!(2027 - 01 - 16), time!(09:00)).is_ok());
+        assert!(matches!(
+            window(date!(2027 - 01 - 17), time!(09:00)),
+            Err(BookingError::TooFar {
+                max_horizon_days: 90
+            })
+        ));
+    }
+
+    #[test]
+    fn cancelling_exactly_at_the_cutoff_is_allowed() {
+        assert!(cutoff(date!(2026 - 10 - 19), time!(06:30)).is_ok());
+    }
+
+    #[test]
+    fn cancelling_a_minute_past_the_cutoff_is_refused() {
+        assert!(matches!(
+            cutoff(date!(2026 - 10 - 19), time!(06:29)),
+            Err(BookingError::CancellationCutoff { cutoff_hours: 24 })
+        ));
+    }
+
+    fn question(question_id: i32, kind: IntakeQuestionKind, required: bool) -> IntakeQuestion {
+        IntakeQuestion {
+            question_id,
+            prompt: format!("Question {question_id}?"),
+            kind,
+            options: match kind {
+                IntakeQuestionKind::Choice => vec!["Mild".into(), "Severe".into()],
+                _ => Vec::new(),
+            },
+            required,
+        }
+    }
+
+    /// Q1 required free text, Q2 optional yes/no, Q3 optional choice.
+    fn questionnaire() -> Vec<IntakeQuestion> {
+        vec![
+            question(1, IntakeQuestionKind::Text, true),
+            question(2, IntakeQuestionKind::YesNo, false),
+            question(3, IntakeQuestionKind::Choice, false),
+        ]
+    }
+
+    fn intake(answers: &[(i32, &str)]) -> VisitIntakeReq {
+        VisitIntakeReq {
+            chief_complaint: None,
+            notes: None,
+            answers: answers
+                .iter()
+                .map(|&(question_id, answer)| IntakeAnswerReq {
+                    question_id,
+                    answer: answer.into(),
+                })
+                .collect(),
+        }
+    }
+
+    fn rejected(result: AppResult<VisitIntake>) -> String {
+        match result {
+            Err(AppError::BadRequest(message)) => message,
+            Err(err) => panic!("unexpected error: {err}"),
+            Ok(_) => panic!("intake accepted"),
+        }
+    }
+
+    fn answers(intake: &VisitIntake) -> Vec<(Option<i32>, &str)> {
+        intake
+            .answers
+            .iter()
+            .map(|a| (a.question_id, a.answer.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn answers_come_back_in_questionnaire_order() {
+        let intake = check_intake(
+            &questionnaire(),
+            &intake(&[(3, "Mild"), (1, "  Since Monday  ")]),
+        )
+        .unwrap();
+        assert_eq!(
+            answers(&intake),
+            vec![(Some(1), "Since Monday"), (Some(3), "Mild")]
+        );
+        assert_eq!(intake.answers[0].prompt, "Question 1?");
+    }
+
+    #[test]
+    fn unknown_question_is_refused() {
+        let message = rejected(check_intake(
+            &questionnaire(),
+            &intake(&[(1, "x"), (9, "x")]),
+        ));
+        assert!(message.contains("question 9 is not part"), "{message}");
+    }
+
+    #[test]
+    fn question_answered_twice_is_refused() {
+        let message = rejected(check_intake(
+            &questionnaire(),
+            &intake(&[(1, "x"), (2, "yes"), (2, "no")]),
+        ));
+        assert!(message.contains("more than once"), "{message}");
+    }
+
+    #[test]
+    fn missing_required_answer_is_refused() {
+        let message = rejected(check_intake(&questionnaire(), &intake(&[(2, "yes")])));
+        assert!(
+            message.contains("question 1 (Question 1?) must be answered"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn blank_answer_counts_as_missing() {
+        let message = rejected(check_intake(&questionnaire(), &intake(&[(1, "   ")])));
+        assert!(message.contains("must be answered"), "{message}");
+        let intake = check_intake(&questionnaire(), &intake(&[(1, "x"), (2, "  ")])).unwrap();
+        assert_eq!(answers(&intake), vec![(Some(1), "x")]);
+    }
+
+    #[test]
+    fn yes_no_and_choice_answers_are_stored_canonically() {
+        let intake = check_intake(
+            &questionnaire(),
+            &intake(&[(1, "x"), (2, "YES"), (3, "severe")]),
+        )
+        .unwrap();
+        assert_eq!(
+            answers(&intake),
+            vec![(Some(1), "x"), (Some(2), "yes"), (Some(3), "Severe")]
+        );
+    }
+
+    #[test]
+    fn answers_outside_the_allowed_values_are_refused() {
+        let message = rejected(check_intake(
+            &questionnaire(),
+            &intake(&[(1, "x"), (2, "maybe")]),
+        ));
+        assert!(message.contains("must be yes or no"), "{message}");
+        let message = rejected(check_intake(
+            &questionnaire(),
+            &intake(&[(1, "x"), (3, "Moderate")]),
+        ));
+        assert!(message.contains("one of: Mild, Severe"), "{message}");
+    }
+
+    #[test]
+    fn text_fields_are_capped() {
+        let long_answer = "a".repeat(MAX_ANSWER_CHARS + 1);
+        let message = rejected(check_intake(
+            &questionnaire(),
+            &intake(&[(1, &long_answer)]),
+        ));
+        assert!(message.contains("at most 1000 characters"), "{message}");
+
+        let at_cap = "a".repeat(MAX_ANSWER_CHARS);
+        assert!(check_intake(&questionnaire(), &intake(&[(1, &at_cap)])).is_ok());
+
+        let mut req = intake(&[(1, "x")]);
+        req.chief_complaint = Some("c".repeat(MAX_CHIEF_COMPLAINT_CHARS + 1));
+        let message = rejected(check_intake(&questionnaire(), &req));
+        assert!(
+            message.contains("chief_complaint must be at most"),
+            "{message}"
+        );
+
+        let mut req = intake(&[(1, "x")]);
+        req.notes = Some("n".repeat(MAX_NOTES_CHARS + 1));
+        let message = rejected(check_intake(&questionnaire(), &req));
+        assert!(message.contains("notes must be at most"), "{message}");
     }
 
-    pub async fn accept(&self, tx: &mut PgTx<'_>, id: i32) -> AppResult<()> {
-        self.repo.accept(tx, id).await
+    #[test]
+    fn blank_reason_and_notes_are_dropped() {
+        let mut req = intake(&[(1, "x")]);
+        req.chief_complaint = Some("  Cough ".into());
+        req.notes = Some("   ".into());
+        let intake = check_intake(&questionnaire(), &req).unwrap();
+        assert_eq!(intake.chief_complaint.as_deref(), Some("Cough"));
+        assert_eq!(intake.notes, None);
     }
 }
@@ -3,13 +3,121 @@ use time::{Date, Time};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
 #[sqlx(type_name = "appointment_status", rename_all = "SCREAMING_SNAKE_CASE")]
+#[allow(non_camel_case_types)]
 pub enum AppointmentStatus {
     PENDING,
     ACCEPTED,
     REJECTED,
     CANCELED,
+    CHECKED_IN,
+    COMPLETED,
+    NO_SHOW,
+}
+
+/// The lifecycle of an appointment:
+///
+/// ```text
+/// PENDING ──> ACCEPTED ──> CHECKED_IN ──> COMPLETED
+///    │           ├────────────┼──> NO_SHOW
+///    ├───────────┴────────────┼──> REJECTED
+///    └───────────┴────────────┴──> CANCELED
+/// ```
+///
+/// REJECTED, CANCELED, COMPLETED and NO_SHOW are final. A checked-in patient
+/// who leaves before being seen is a NO_SHOW, and one sent away is CANCELED;
+/// only clinic staff make those moves. Rescheduling moves an ACCEPTED
+/// appointment back to PENDING unless the doctor keeps acceptance.
+impl AppointmentStatus {
+    pub fn can_transition_to(self, next: Self) -> bool {
+        use AppointmentStatus::*;
+        matches!(
+            (self, next),
+            (PENDING, ACCEPTED | REJECTED | CANCELED)
+                | (ACCEPTED, CHECKED_IN | NO_SHOW | REJECTED | CANCELED)
+                | (CHECKED_IN, COMPLETED | NO_SHOW | CANCELED)
+        )
+    }
+
+    pub fn transition_to(self, next: Self) -> Result<Self, IllegalTransition> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(IllegalTransition {
+                from: self,
+                to: next,
+            })
+        }
+    }
+
+    /// PENDING or ACCEPTED: the visit is still ahead and can be moved.
+    pub fn is_open(self) -> bool {
+        matches!(self, Self::PENDING | Self::ACCEPTED)
+    }
+
+    pub fn is_final(self) -> bool {
+        matches!(
+            self,
+            Self::REJECTED | Self::CANCELED | Self::COMPLETED | Self::NO_SHOW
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PENDING => "PENDING",
+            Self::ACCEPTED => "ACCEPTED",
+            Self::REJECTED => "REJECTED",
+            Self::CANCELED => "CANCELED",
+            Self::CHECKED_IN => "CHECKED_IN",
+            Self::COMPLETED => "COMPLETED",
+            Self::NO_SHOW => "NO_SHOW",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IllegalTransition {
+    pub from: AppointmentStatus,
+    pub to: AppointmentStatus,
+}
+
+impl std::fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot move an appointment from {} to {}",
+            self.from.as_str(),
+            self.to.as_str()
+        )
+    }
+}
+
+/// A status change requested by `actor_id`.
+pub struct StatusChange {
+    pub appointment_id: i32,
+    pub to: AppointmentStatus,
+    pub actor_id: Uuid,
+    pub note: Option<String>,
+}
+
+/// One entry of an appointment's status log. The first entry has no
+/// `from_status`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AppointmentTransition {
+    #[schema(nullable = true)]
+    pub from_status: Option<AppointmentStatus>,
+    pub to_status: AppointmentStatus,
+    /// Who made the change; absent for changes made by the system.
+    #[schema(nullable = true)]
+    pub actor_id: Option<Uuid>,
+    #[schema(nullable = true)]
+    pub actor_name: Option<String>,
+    #[schema(nullable = true)]
+    pub note: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime, example = "2025-10-10T12:34:56Z")]
+    pub created_at: time::OffsetDateTime,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -40,6 +148,127 @@ pub struct NewAppointment {
     pub patient_id: Uuid,
     pub timeslot_id: i32,
     pub date: Date,
+    pub intake: VisitIntakeReq,
+}
+
+/// A timeslot locked for booking.
+#[derive(Debug, Clone, Copy)]
+pub struct LockedTimeslot {
+    pub doctor_id: Uuid,
+    pub capacity: i32,
+    pub start_time: Time,
+}
+
+/// Why a booking, move, cancellation or waitlist request was refused. Each variant has a
+/// stable `code` that clients can branch on.
+#[derive(Debug, Clone)]
+pub enum BookingError {
+    InPast,
+    TooSoon { min_lead_minutes: i64 },
+    TooFar { max_horizon_days: i64 },
+    TooManyPending { max_pending: i64 },
+    CancellationCutoff { cutoff_hours: i64 },
+    TimeslotFull,
+    Unavailable(String),
+    AlreadyBooked,
+    OtherDoctor,
+    NotFull,
+    AlreadyWaitlisted,
+    NoOpenOffer,
+    OfferExpired,
+}
+
+impl BookingError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InPast => "BOOKING_IN_PAST",
+            Self::TooSoon { .. } => "BOOKING_TOO_SOON",
+            Self::TooFar { .. } => "BOOKING_TOO_FAR_AHEAD",
+            Self::TooManyPending { .. } => "TOO_MANY_PENDING_BOOKINGS",
+            Self::CancellationCutoff { .. } => "CANCELLATION_CUTOFF_PASSED",
+            Self::TimeslotFull => "TIMESLOT_FULL",
+            Self::Unavailable(_) => "TIMESLOT_UNAVAILABLE",
+            Self::AlreadyBooked => "ALREADY_BOOKED",
+            Self::OtherDoctor => "OTHER_DOCTOR",
+            Self::NotFull => "TIMESLOTS_AVAILABLE",
+            Self::AlreadyWaitlisted => "ALREADY_WAITLISTED",
+            Self::NoOpenOffer => "NO_OPEN_OFFER",
+            Self::OfferExpired => "OFFER_EXPIRED",
+        }
+    }
+}
+
+impl std::fmt::Display for BookingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InPast => write!(f, "the slot has already started"),
+            Self::TooSoon { min_lead_minutes } => write!(
+                f,
+                "slots must be booked at least {min_lead_minutes} minutes before they start"
+            ),
+            Self::TooFar { max_horizon_days } => write!(
+                f,
+                "slots can be booked at most {max_horizon_days} days ahead"
+            ),
+            Self::TooManyPending { max_pending } => write!(
+                f,
+                "you already have {max_pending} appointments awaiting the doctor's answer"
+            ),
+            Self::CancellationCutoff { cutoff_hours } => write!(
+                f,
+                "accepted appointments cannot be changed within {cutoff_hours} hours of their start"
+            ),
+            Self::TimeslotFull => write!(f, "timeslot is fully booked on this date"),
+            Self::Unavailable(reason) => write!(f, "{reason}"),
+            Self::AlreadyBooked => write!(
+                f,
+                "you already have an appointment in this timeslot on this date"
+            ),
+            Self::OtherDoctor => write!(
+                f,
+                "appointments can only be moved to another timeslot of the same doctor"
+            ),
+            Self::NotFull => write!(
+                f,
+                "the doctor still has free places on this date; book one of them instead"
+            ),
+            Self::AlreadyWaitlisted => write!(
+                f,
+                "you are already on the waitlist for this doctor on this date"
+            ),
+            Self::NoOpenOffer => write!(f, "no place is being held for this waitlist entry"),
+            Self::OfferExpired => write!(f, "the held place has expired"),
+        }
+    }
+}
+
+/// Body of a refused booking, move, cancellation or waitlist request.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BookingErrorBody {
+    /// `BOOKING_IN_PAST`, `BOOKING_TOO_SOON`, `BOOKING_TOO_FAR_AHEAD`,
+    /// `TOO_MANY_PENDING_BOOKINGS`, `CANCELLATION_CUTOFF_PASSED`,
+    /// `TIMESLOT_FULL`, `TIMESLOT_UNAVAILABLE`, `ALREADY_BOOKED`,
+    /// `OTHER_DOCTOR`, `TIMESLOTS_AVAILABLE`, `ALREADY_WAITLISTED`,
+    /// `NO_OPEN_OFFER` or `OFFER_EXPIRED`.
+    #[schema(example = "BOOKING_TOO_SOON")]
+    pub code: String,
+    pub message: String,
+}
+
+pub struct RescheduleAppointment {
+    pub appointment_id: i32,
+    pub patient_id: Uuid,
+    pub timeslot_id: i32,
+    pub date: Date,
+}
+
+/// Moves an appointment and records where it was before.
+pub struct MoveAppointment {
+    pub appointment_id: i32,
+    pub timeslot_id: i32,
+    pub date: Date,
+    pub status: AppointmentStatus,
+    pub moved_by: Uuid,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -56,6 +285,10 @@ pub struct AppointmentOverview {
     #[schema(example = "12:00")]
     pub end_time: String,
     pub status: AppointmentStatus,
+    /// The doctor's catalogued department.
+    #[schema(nullable = true)]
+    pub department_detail: Option<Department>,
+    pub location: Location,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -64,6 +297,36 @@ pub struct DoctorListItem {
     pub doctor_name: String,
     #[schema(nullable = true)]
     pub department: Option<String>,
+    #[schema(nullable = true)]
+    pub position: Option<String>,
+    /// Earliest place that can still be booked, on the requested date when
+    /// the search filtered by availability.
+    #[schema(nullable = true)]
+    pub next_available: Option<NextAvailableSlot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NextAvailableSlot {
+    pub timeslot_id: i32,
+    #[schema(example = "2025-09-23")]
+    pub date: String,
+    pub place_name: String,
+    #[schema(example = "09:00")]
+    pub start_time: String,
+    #[schema(example = "12:00")]
+    pub end_time: String,
+    pub available: i32,
+}
+
+/// One page of the doctor directory, in name order.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DoctorDirectoryPage {
+    pub items: Vec<DoctorListItem>,
+    /// Doctors matching the filters across all pages.
+    pub total: i64,
+    /// Pass as `cursor` to fetch the next page; `null` on the last page.
+    #[schema(nullable = true)]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -75,6 +338,30 @@ pub struct DoctorTimeslotView {
     pub start_time: String,
     #[schema(example = "12:00")]
     pub end_time: String,
+    /// Patients the timeslot takes per date.
+    pub capacity: i32,
+    /// Set for one-off timeslots offered on that date only.
+    #[schema(nullable = true, example = "2025-09-23")]
+    pub valid_on: Option<String>,
+    pub location: Location,
+}
+
+/// Free capacity of one timeslot on one date.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TimeslotAvailability {
+    #[schema(example = "2025-09-23")]
+    pub date: String,
+    pub timeslot_id: i32,
+    pub place_name: String,
+    #[schema(example = "09:00")]
+    pub start_time: String,
+    #[schema(example = "12:00")]
+    pub end_time: String,
+    pub capacity: i32,
+    /// Appointments other than REJECTED and CANCELED ones, plus places held
+    /// for waitlisted patients.
+    pub booked: i32,
+    pub available: i32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -90,20 +377,605 @@ pub struct DoctorAppointmentView {
     pub end_time: String,
     pub status: AppointmentStatus,
     pub status_code: i32,
+    /// How many times the patient has moved the appointment.
+    pub reschedule_count: i32,
+    /// The slot it was moved away from most recently.
+    #[schema(nullable = true)]
+    pub rescheduled_from: Option<PreviousSlot>,
+    #[schema(nullable = true)]
+    pub chief_complaint: Option<String>,
+    #[schema(nullable = true)]
+    pub notes: Option<String>,
+    pub intake_answers: Vec<IntakeAnswer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PreviousSlot {
+    #[schema(example = "2025-09-23")]
+    pub date: String,
+    #[schema(example = "09:00")]
+    pub start_time: String,
+    #[schema(example = "12:00")]
+    pub end_time: String,
 }
 
+/// Either `timeslot_id`, or `doctor_id` with the exact `start_time` and
+/// `end_time` of one of the doctor's timeslots.
 #[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct CreateAppointmentReq {
-    pub doctor_id: Uuid,
+    pub timeslot_id: Option<i32>,
+    pub doctor_id: Option<Uuid>,
+    #[schema(example = "2025-09-23")]
+    pub date: String,
+    #[schema(example = "09:00")]
+    pub start_time: Option<String>,
+    #[schema(example = "12:00")]
+    pub end_time: Option<String>,
+    /// Why the patient wants to see the doctor and answers to the intake
+    /// questionnaire of the doctor's department.
+    #[serde(flatten)]
+    pub intake: VisitIntakeReq,
+}
+
+/// Another slot of the same doctor: either `timeslot_id`, or the exact
+/// `start_time` and `end_time` of one of the doctor's timeslots.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct RescheduleReq {
+    pub timeslot_id: Option<i32>,
+    #[schema(example = "2025-09-30")]
     pub date: String,
+    #[schema(example = "09:00")]
+    pub start_time: Option<String>,
+    #[schema(example = "12:00")]
+    pub end_time: Option<String>,
+}
+
+/// One move of an appointment from one slot to another.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AppointmentReschedule {
+    #[schema(example = "2025-09-23")]
+    pub from_date: String,
+    #[schema(example = "09:00")]
+    pub from_start_time: String,
+    #[schema(example = "12:00")]
+    pub from_end_time: String,
+    pub from_place_name: String,
+    /// Status before the move.
+    pub from_status: AppointmentStatus,
+    #[schema(example = "2025-09-30")]
+    pub to_date: String,
+    #[schema(example = "09:00")]
+    pub to_start_time: String,
+    #[schema(example = "12:00")]
+    pub to_end_time: String,
+    pub to_place_name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime, example = "2025-10-10T12:34:56Z")]
+    pub rescheduled_at: time::OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DoctorBookingPolicy {
+    /// Keep an ACCEPTED appointment ACCEPTED when the patient reschedules
+    /// it; otherwise it goes back to PENDING.
+    pub keep_accepted_on_reschedule: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct UpdateTimeslotReq {
+    pub day_of_weeks: i32,
+    /// Name of a catalogued location; may be left out when `location_id`
+    /// is given.
+    #[serde(default)]
+    pub place_name: String,
+    /// Catalogued location; takes precedence over `place_name`.
+    pub location_id: Option<i32>,
     pub start_time: String,
     pub end_time: String,
+    /// Unchanged when omitted.
+    pub capacity: Option<i32>,
 }
 
 #[derive(Debug, Clone, Deserialize, ToSchema)]
-pub struct UpdateTimeslotReq {
+pub struct CreateTimeslotReq {
+    /// 0=Sun ... 6=Sat
     pub day_of_weeks: i32,
+    /// Name of a catalogued location; may be left out when `location_id`
+    /// is given.
+    #[serde(default)]
+    pub place_name: String,
+    /// Catalogued location; takes precedence over `place_name`.
+    pub location_id: Option<i32>,
+    #[schema(example = "09:00")]
+    pub start_time: String,
+    #[schema(example = "12:00")]
+    pub end_time: String,
+    /// Patients per date; defaults to 1.
+    pub capacity: Option<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct TimeRangeReq {
+    #[schema(example = "09:00")]
+    pub start_time: String,
+    #[schema(example = "12:00")]
+    pub end_time: String,
+}
+
+/// Creates one timeslot for every combination of `days` and `periods`.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct WeeklyTemplateReq {
+    /// 0=Sun ... 6=Sat
+    #[schema(example = json!([1, 2, 3, 4, 5]))]
+    pub days: Vec<i32>,
+    /// Name of a catalogued location; may be left out when `location_id`
+    /// is given.
+    #[serde(default)]
+    pub place_name: String,
+    /// Catalogued location; takes precedence over `place_name`.
+    pub location_id: Option<i32>,
+    pub periods: Vec<TimeRangeReq>,
+    /// Patients per date for every created timeslot; defaults to 1.
+    pub capacity: Option<i32>,
+}
+
+/// A single-date timeslot outside the weekly schedule.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CreateOneOffTimeslotReq {
+    #[schema(example = "2025-09-23")]
+    pub date: String,
+    /// Name of a catalogued location; may be left out when `location_id`
+    /// is given.
+    #[serde(default)]
     pub place_name: String,
+    /// Catalogued location; takes precedence over `place_name`.
+    pub location_id: Option<i32>,
+    #[schema(example = "09:00")]
     pub start_time: String,
+    #[schema(example = "12:00")]
     pub end_time: String,
+    /// Patients on that date; defaults to 1.
+    pub capacity: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ScheduleExceptionKind {
+    /// The doctor is away for whole days.
+    Leave,
+    /// One occurrence of a recurring timeslot is not held.
+    Cancelled,
+}
+
+impl ScheduleExceptionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Leave => "LEAVE",
+            Self::Cancelled => "CANCELLED",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "LEAVE" => Some(Self::Leave),
+            "CANCELLED" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleException {
+    pub exception_id: i32,
+    pub kind: ScheduleExceptionKind,
+    #[schema(example = "2025-09-23")]
+    pub start_date: String,
+    /// Same as `start_date` for a cancelled occurrence.
+    #[schema(example = "2025-09-26")]
+    pub end_date: String,
+    /// The cancelled timeslot.
+    #[schema(nullable = true)]
+    pub timeslot_id: Option<i32>,
+    #[schema(nullable = true)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ScheduleExceptionReq {
+    pub kind: ScheduleExceptionKind,
+    #[schema(example = "2025-09-23")]
+    pub start_date: String,
+    /// Last day of leave; defaults to `start_date`. Ignored for `CANCELLED`.
+    #[schema(example = "2025-09-26")]
+    pub end_date: Option<String>,
+    /// Required for `CANCELLED`.
+    pub timeslot_id: Option<i32>,
+    pub reason: Option<String>,
+}
+
+/// A newly added exception and the PENDING/ACCEPTED appointments that fall
+/// in it. Those appointments are left as they are for the doctor to handle.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleExceptionCreated {
+    pub exception: ScheduleException,
+    pub affected_appointments: Vec<DoctorAppointmentView>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClinicHoliday {
+    #[schema(example = "2025-12-05")]
+    pub date: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ClinicHolidayReq {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Department {
+    pub department_id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct DepartmentReq {
+    pub name: String,
+}
+
+/// A place where timeslots are held.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Location {
+    pub location_id: i32,
+    pub name: String,
+    #[schema(nullable = true)]
+    pub building: Option<String>,
+    #[schema(nullable = true)]
+    pub floor: Option<String>,
+    #[schema(nullable = true)]
+    pub room: Option<String>,
+    #[schema(nullable = true, example = 13.7563)]
+    pub latitude: Option<f64>,
+    #[schema(nullable = true, example = 100.5018)]
+    pub longitude: Option<f64>,
+}
+
+/// Latitude and longitude are given together or not at all.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct LocationReq {
+    pub name: String,
+    pub building: Option<String>,
+    pub floor: Option<String>,
+    pub room: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[sqlx(type_name = "varchar", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WaitlistStatus {
+    /// In the queue for the doctor and date.
+    Waiting,
+    /// A freed place is held for the patient until the offer expires.
+    Offered,
+    /// The patient took the held place; see `appointment_id`.
+    Confirmed,
+    /// The offer ran out, or the date passed while waiting.
+    Expired,
+    /// The patient left the waitlist.
+    Left,
+}
+
+impl WaitlistStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Waiting => "WAITING",
+            Self::Offered => "OFFERED",
+            Self::Confirmed => "CONFIRMED",
+            Self::Expired => "EXPIRED",
+            Self::Left => "LEFT",
+        }
+    }
+}
+
+pub struct WaitlistEntry {
+    pub entry_id: i32,
+    pub patient_id: Uuid,
+    pub doctor_id: Uuid,
+    pub date: Date,
+    pub status: WaitlistStatus,
+    pub offered_timeslot_id: Option<i32>,
+    pub offer_expires_at: Option<time::OffsetDateTime>,
+}
+
+/// A freed place offered to the next waitlisted patient.
+pub struct WaitlistOffer {
+    pub entry_id: i32,
+    pub doctor_id: Uuid,
+    pub timeslot_id: i32,
+    pub date: Date,
+    pub expires_at: time::OffsetDateTime,
+}
+
+/// Who to tell about a waitlist offer, and what was offered.
+pub struct OfferContact {
+    pub email: Option<String>,
+    pub phone: String,
+    pub doctor_name: String,
+    pub place_name: String,
+    pub start_time: Time,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct JoinWaitlistReq {
+    pub doctor_id: Uuid,
+    #[schema(example = "2025-09-23")]
+    pub date: String,
+}
+
+/// The place held for a waitlisted patient.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HeldPlace {
+    pub timeslot_id: i32,
+    pub place_name: String,
+    #[schema(example = "09:00")]
+    pub start_time: String,
+    #[schema(example = "12:00")]
+    pub end_time: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime, example = "2025-10-10T12:34:56Z")]
+    pub expires_at: time::OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WaitlistEntryView {
+    pub entry_id: i32,
+    pub doctor_id: Uuid,
+    pub doctor_name: String,
+    pub patient_id: Uuid,
+    pub patient_name: String,
+    #[schema(example = "2025-09-23")]
+    pub date: String,
+    pub status: WaitlistStatus,
+    /// 1 for the next patient to be offered a place; only while WAITING.
+    #[schema(nullable = true)]
+    pub position: Option<i64>,
+    /// Only while OFFERED.
+    #[schema(nullable = true)]
+    pub held_place: Option<HeldPlace>,
+    /// The appointment made from the offer, once CONFIRMED.
+    #[schema(nullable = true)]
+    pub appointment_id: Option<i32>,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime, example = "2025-10-10T12:34:56Z")]
+    pub created_at: time::OffsetDateTime,
+}
+
+/// A new calendar subscription link. The link is shown only once; creating
+/// another one revokes it.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CalendarFeedCreated {
+    #[schema(example = "https://api.example.com/api/appointments/calendar/3f9c...e1.ics")]
+    pub url: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime, example = "2025-10-10T12:34:56Z")]
+    pub created_at: time::OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CalendarFeedInfo {
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime, example = "2025-10-10T12:34:56Z")]
+    pub created_at: time::OffsetDateTime,
+    /// When a calendar app last fetched the feed.
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime, nullable = true)]
+    pub last_used_at: Option<time::OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum IntakeQuestionKind {
+    /// Free text.
+    Text,
+    /// `yes` or `no`.
+    YesNo,
+    /// One of the question's `options`.
+    Choice,
+}
+
+impl IntakeQuestionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "TEXT",
+            Self::YesNo => "YES_NO",
+            Self::Choice => "CHOICE",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "TEXT" => Some(Self::Text),
+            "YES_NO" => Some(Self::YesNo),
+            "CHOICE" => Some(Self::Choice),
+            _ => None,
+        }
+    }
+}
+
+/// One question of a department's pre-visit questionnaire.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IntakeQuestion {
+    pub question_id: i32,
+    pub prompt: String,
+    pub kind: IntakeQuestionKind,
+    /// The allowed answers of a CHOICE question; empty otherwise.
+    pub options: Vec<String>,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct IntakeQuestionReq {
+    pub prompt: String,
+    pub kind: IntakeQuestionKind,
+    #[serde(default)]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct VisitIntakeReq {
+    /// The main reason for the visit, in a few words.
+    #[schema(example = "Headache for three days")]
+    pub chief_complaint: Option<String>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub answers: Vec<IntakeAnswerReq>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct IntakeAnswerReq {
+    pub question_id: i32,
+    pub answer: String,
+}
+
+/// The reason for a visit as stored with the appointment.
+pub struct VisitIntake {
+    pub chief_complaint: Option<String>,
+    pub notes: Option<String>,
+    pub answers: Vec<IntakeAnswer>,
+}
+
+/// An answer together with the question as it was worded at booking time.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IntakeAnswer {
+    /// `null` once the question has been removed from the questionnaire.
+    #[schema(nullable = true)]
+    pub question_id: Option<i32>,
+    pub prompt: String,
+    pub answer: String,
+}
+
+/// An appointment with the reason for the visit.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AppointmentDetail {
+    #[serde(flatten)]
+    pub appointment: Appointment,
+    #[schema(nullable = true)]
+    pub chief_complaint: Option<String>,
+    #[schema(nullable = true)]
+    pub notes: Option<String>,
+    pub intake_answers: Vec<IntakeAnswer>,
+}
+
+/// One checked-in visit in a doctor's queue for the day.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QueueEntry {
+    pub appointment_id: i32,
+    pub queue_number: i32,
+    pub patient_id: Uuid,
+    pub patient_name: String,
+    /// CHECKED_IN while waiting or with the doctor, then COMPLETED; NO_SHOW
+    /// or CANCELED when the patient left or was sent away.
+    pub status: AppointmentStatus,
+    #[schema(example = "09:00")]
+    pub start_time: String,
+    #[schema(example = "09:30")]
+    pub end_time: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime, example = "2025-10-10T12:34:56Z")]
+    pub checked_in_at: time::OffsetDateTime,
+    /// When the doctor called the patient in.
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime, nullable = true)]
+    pub called_at: Option<time::OffsetDateTime>,
+    /// Minutes until the patient is likely to be called; only while waiting.
+    #[schema(nullable = true)]
+    pub estimated_wait_minutes: Option<i64>,
+}
+
+/// A doctor's queue for the day, in queue order.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VisitQueue {
+    pub doctor_id: Uuid,
+    #[schema(example = "2025-09-23")]
+    pub date: String,
+    /// The number of the patient with the doctor, if any.
+    #[schema(nullable = true)]
+    pub now_serving: Option<i32>,
+    pub waiting: i64,
+    pub entries: Vec<QueueEntry>,
+}
+
+/// A patient's place in the queue.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QueuePosition {
+    pub appointment_id: i32,
+    pub queue_number: i32,
+    #[schema(nullable = true)]
+    pub now_serving: Option<i32>,
+    /// Patients still waiting before this one; 0 once called.
+    pub ahead: i64,
+    /// Minutes until the patient is likely to be called; `null` once called.
+    #[schema(nullable = true)]
+    pub estimated_wait_minutes: Option<i64>,
+    pub status: AppointmentStatus,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppointmentStatus::{self, *};
+
+    const ALL: [AppointmentStatus; 7] = [
+        PENDING, ACCEPTED, REJECTED, CANCELED, CHECKED_IN, COMPLETED, NO_SHOW,
+    ];
+
+    fn allowed_from(from: AppointmentStatus) -> Vec<AppointmentStatus> {
+        ALL.into_iter()
+            .filter(|&to| from.can_transition_to(to))
+            .collect()
+    }
+
+    #[test]
+    fn pending_is_accepted_rejected_or_cancelled() {
+        assert_eq!(allowed_from(PENDING), vec![ACCEPTED, REJECTED, CANCELED]);
+    }
+
+    #[test]
+    fn accepted_is_checked_in_missed_rejected_or_cancelled() {
+        assert_eq!(
+            allowed_from(ACCEPTED),
+            vec![REJECTED, CANCELED, CHECKED_IN, NO_SHOW]
+        );
+    }
+
+    #[test]
+    fn checked_in_is_completed_missed_or_cancelled() {
+        assert_eq!(allowed_from(CHECKED_IN), vec![CANCELED, COMPLETED, NO_SHOW]);
+    }
+
+    #[test]
+    fn final_states_refuse_every_move() {
+        for from in [COMPLETED, NO_SHOW, REJECTED, CANCELED] {
+            assert!(from.is_final());
+            assert_eq!(allowed_from(from), vec![], "{from:?} moved");
+        }
+    }
+
+    #[test]
+    fn no_status_moves_to_itself() {
+        for status in ALL {
+            assert!(!status.can_transition_to(status), "{status:?} -> itself");
+        }
+    }
+
+    #[test]
+    fn illegal_transition_reports_both_ends() {
+        let err = COMPLETED.transition_to(PENDING).unwrap_err();
+        assert_eq!((err.from, err.to), (COMPLETED, PENDING));
+        assert_eq!(PENDING.transition_to(ACCEPTED).unwrap(), ACCEPTED);
+    }
 }
@@ -0,0 +1,311 @@
+//! iCalendar export of single appointments and per-user subscription feeds.
+//! Feeds are fetched by calendar apps, which cannot send a bearer header, so
+//! they are authorized by a secret token in the URL instead.
+use super::{
+    http::{Ctx, ensure_can_view},
+    ics::{CalendarEvent, calendar},
+};
+use crate::domain::{AppointmentStatus, CalendarFeedCreated, CalendarFeedInfo};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use common::{
+    auth::AuthUser,
+    error::{AppError, AppResult},
+    token::{generate_opaque_token, hash_token},
+};
+use time::{Date, Duration, OffsetDateTime, Time};
+use uuid::Uuid;
+
+/// How far back a subscription feed reaches.
+const FEED_HISTORY_DAYS: i64 = 30;
+const UID_DOMAIN: &str = "therapeia";
+
+#[utoipa::path(
+    get,
+    path = "/{appointment_id}/ics",
+    params(("appointment_id" = i32, Path)),
+    responses(
+        (status = 200, description = "The appointment as an iCalendar file", body = String, content_type = "text/calendar"),
+        (status = 403, description = "Not the patient, the doctor or an admin"),
+        (status = 404, description = "Appointment not found"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn appointment_ics(
+    user: AuthUser,
+    State(ctx): State<Ctx>,
+    Path(appointment_id): Path<i32>,
+) -> AppResult<Response> {
+    ensure_can_view(&ctx.pool, &user, appointment_id).await?;
+    let rows = calendar_rows(&ctx, CalendarFilter::Appointment(appointment_id)).await?;
+    let events: Vec<_> = rows
+        .into_iter()
+        .map(|row| calendar_event(&ctx, row, user.user_id))
+        .collect();
+    let body = calendar("Therapeia appointment", &events);
+    let disposition = format!("attachment; filename=\"appointment-{appointment_id}.ics\"");
+    Ok(ics_response(body, Some(disposition)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/calendar-feed",
+    responses(
+        (status = 200, description = "The current subscription link, without its secret", body = CalendarFeedInfo),
+        (status = 404, description = "No subscription link"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn get_calendar_feed(
+    AuthUser { user_id, .. }: AuthUser,
+    State(ctx): State<Ctx>,
+) -> AppResult<Json<CalendarFeedInfo>> {
+    let feed = sqlx::query_as!(
+        CalendarFeedInfo,
+        "SELECT created_at, last_used_at FROM calendar_feeds WHERE user_id = $1",
+        user_id
+    )
+    .fetch_optional(&ctx.pool)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    Ok(Json(feed))
+}
+
+#[utoipa::path(
+    post,
+    path = "/calendar-feed",
+    responses(
+        (status = 201, description = "New subscription link; any previous link stops working", body = CalendarFeedCreated),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn create_calendar_feed(
+    AuthUser { user_id, .. }: AuthUser,
+    State(ctx): State<Ctx>,
+) -> AppResult<(StatusCode, Json<CalendarFeedCreated>)> {
+    let token = generate_opaque_token();
+    let created_at = sqlx::query_scalar!(
+        r#"
+        INSERT INTO calendar_feeds (user_id, token_hash)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE
+        SET token_hash = EXCLUDED.token_hash,
+            created_at = now(),
+            last_used_at = NULL
+        RETURNING created_at
+        "#,
+        user_id,
+        hash_token(&token)
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    let path = format!("/api/appointments/calendar/{token}.ics");
+    let url = match &ctx.public_api_url {
+        Some(base) => format!("{base}{path}"),
+        None => path,
+    };
+    Ok((
+        StatusCode::CREATED,
+        Json(CalendarFeedCreated { url, created_at }),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/calendar-feed",
+    responses(
+        (status = 204, description = "Subscription link revoked"),
+        (status = 404, description = "No subscription link"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn delete_calendar_feed(
+    AuthUser { user_id, .. }: AuthUser,
+    State(ctx): State<Ctx>,
+) -> AppResult<StatusCode> {
+    let rows = sqlx::query!("DELETE FROM calendar_feeds WHERE user_id = $1", user_id)
+        .execute(&ctx.pool)
+        .await?;
+    if rows.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/calendar/{token}",
+    params(("token" = String, Path, description = "Secret from the subscription link; a trailing `.ics` is ignored")),
+    responses(
+        (status = 200, description = "The user's appointments as patient and as doctor, from 30 days ago on", body = String, content_type = "text/calendar"),
+        (status = 404, description = "Unknown or revoked link, or the account is deactivated"),
+    ),
+    tag = "appointments"
+)]
+pub(super) async fn calendar_feed(
+    State(ctx): State<Ctx>,
+    Path(token): Path<String>,
+) -> AppResult<Response> {
+    let token = token.strip_suffix(".ics").unwrap_or(&token);
+    // A deactivated account's link stops working but is kept, so it works
+    // again if the account is reactivated.
+    let user_id = sqlx::query_scalar!(
+        r#"
+        UPDATE calendar_feeds f
+        SET last_used_at = now()
+        FROM users u
+        WHERE f.token_hash = $1
+          AND u.user_id = f.user_id
+          AND u.deactivated_at IS NULL
+        RETURNING f.user_id
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(&ctx.pool)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    let from = ctx.svc.today() - Duration::days(FEED_HISTORY_DAYS);
+    let rows = calendar_rows(&ctx, CalendarFilter::User { user_id, from }).await?;
+    let events: Vec<_> = rows
+        .into_iter()
+        .map(|row| calendar_event(&ctx, row, user_id))
+        .collect();
+    Ok(ics_response(
+        calendar("Therapeia appointments", &events),
+        None,
+    ))
+}
+
+pub(super) fn routes() -> Router<Ctx> {
+    Router::new()
+        .route("/appointments/{appointment_id}/ics", get(appointment_ics))
+        .route(
+            "/appointments/calendar-feed",
+            get(get_calendar_feed)
+                .post(create_calendar_feed)
+                .delete(delete_calendar_feed),
+        )
+        .route("/appointments/calendar/{token}", get(calendar_feed))
+}
+
+fn ics_response(body: String, disposition: Option<String>) -> Response {
+    let mut response = body.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/calendar; charset=utf-8"),
+    );
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    if let Some(value) = disposition.and_then(|value| HeaderValue::from_str(&value).ok()) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+}
+
+enum CalendarFilter {
+    Appointment(i32),
+    /// Appointments of the user as patient or as doctor, dated `from` or later.
+    User {
+        user_id: Uuid,
+        from: Date,
+    },
+}
+
+async fn calendar_rows(ctx: &Ctx, filter: CalendarFilter) -> AppResult<Vec<CalendarRow>> {
+    let (appointment_id, user_id, from) = match filter {
+        CalendarFilter::Appointment(appointment_id) => (Some(appointment_id), None, None),
+        CalendarFilter::User { user_id, from } => (None, Some(user_id), Some(from)),
+    };
+    let rows = sqlx::query_as!(
+        CalendarRow,
+        r#"
+        SELECT
+            a.appointment_id,
+            a.patient_id,
+            concat_ws(' ', up.first_name, up.last_name) AS "patient_name!",
+            ts.doctor_id,
+            concat_ws(' ', ud.first_name, ud.last_name) AS "doctor_name!",
+            ts.place_name,
+            a.date,
+            ts.start_time,
+            ts.end_time,
+            a.status AS "status: AppointmentStatus",
+            (SELECT COUNT(*) FROM appointment_transitions t WHERE t.appointment_id = a.appointment_id)
+              + (SELECT COUNT(*) FROM appointment_reschedules r WHERE r.appointment_id = a.appointment_id)
+              AS "revisions!",
+            GREATEST(
+                a.created_at,
+                (SELECT MAX(t.created_at) FROM appointment_transitions t WHERE t.appointment_id = a.appointment_id),
+                (SELECT MAX(r.rescheduled_at) FROM appointment_reschedules r WHERE r.appointment_id = a.appointment_id)
+            ) AS "last_modified!"
+        FROM appointments a
+        JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+        JOIN users up ON up.user_id = a.patient_id
+        JOIN users ud ON ud.user_id = ts.doctor_id
+        WHERE ($1::int IS NULL OR a.appointment_id = $1)
+          AND ($2::uuid IS NULL OR a.patient_id = $2 OR ts.doctor_id = $2)
+          AND ($3::date IS NULL OR a.date >= $3)
+        ORDER BY a.date, ts.start_time, a.appointment_id
+        "#,
+        appointment_id,
+        user_id,
+        from
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    Ok(rows)
+}
+
+struct CalendarRow {
+    appointment_id: i32,
+    patient_id: Uuid,
+    patient_name: String,
+    doctor_id: Uuid,
+    doctor_name: String,
+    place_name: String,
+    date: Date,
+    start_time: Time,
+    end_time: Time,
+    status: AppointmentStatus,
+    revisions: i64,
+    last_modified: OffsetDateTime,
+}
+
+/// The event as seen by `viewer`: patients see who they are visiting,
+/// doctors whom they are seeing.
+fn calendar_event(ctx: &Ctx, row: CalendarRow, viewer: Uuid) -> CalendarEvent {
+    let summary = if row.patient_id == viewer {
+        format!("Appointment with {}", row.doctor_name)
+    } else if row.doctor_id == viewer {
+        format!("Appointment with {}", row.patient_name)
+    } else {
+        format!("Appointment: {} with {}", row.patient_name, row.doctor_name)
+    };
+    let description = format!(
+        "Doctor: {}\nPatient: {}\nStatus: {}",
+        row.doctor_name,
+        row.patient_name,
+        row.status.as_str()
+    );
+    CalendarEvent {
+        uid: format!("appointment-{}@{UID_DOMAIN}", row.appointment_id),
+        // The booking itself is the first revision.
+        sequence: (row.revisions - 1).max(0),
+        start: ctx.svc.slot_start(row.date, row.start_time),
+        end: ctx.svc.slot_start(row.date, row.end_time),
+        summary,
+        location: row.place_name,
+        description,
+        status: row.status,
+        last_modified: row.last_modified,
+    }
+}
@@ -0,0 +1,404 @@
+//! The department and location catalogue. Anyone may browse it; admins
+//! maintain it. Doctor profiles and timeslots keep a copy of the catalogue
+//! name, which is rewritten here when an entry is renamed.
+use super::http::Ctx;
+use crate::domain::{Department, DepartmentReq, Location, LocationReq};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, put},
+};
+use common::{
+    auth::{Admin, RequireRole, Strict},
+    error::{AppError, AppResult, violates_constraint},
+};
+use sqlx::PgPool;
+
+const DEPARTMENT_NAME_FIELDS: &[(&str, &str)] = &[("uniq_department_name", "name")];
+const LOCATION_NAME_FIELDS: &[(&str, &str)] = &[("uniq_location_name", "name")];
+const DEPARTMENT_IN_USE_CONSTRAINT: &str = "doctor_profile_department_fk";
+const LOCATION_IN_USE_CONSTRAINT: &str = "time_slots_location_fk";
+
+#[utoipa::path(
+    get,
+    path = "/departments",
+    responses((status = 200, description = "All departments by name", body = [Department])),
+    tag = "appointments"
+)]
+pub(super) async fn list_departments(State(ctx): State<Ctx>) -> AppResult<Json<Vec<Department>>> {
+    let departments = sqlx::query_as!(
+        Department,
+        "SELECT department_id, name FROM departments ORDER BY lower(name)"
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    Ok(Json(departments))
+}
+
+#[utoipa::path(
+    post,
+    path = "/departments",
+    request_body = DepartmentReq,
+    responses(
+        (status = 201, description = "Department created", body = Department),
+        (status = 400, description = "Invalid payload"),
+        (status = 409, description = "Name already in use"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn create_department(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<DepartmentReq>,
+) -> AppResult<(StatusCode, Json<Department>)> {
+    let name = required_name(&req.name)?;
+    let department = sqlx::query_as!(
+        Department,
+        "INSERT INTO departments (name) VALUES ($1) RETURNING department_id, name",
+        name
+    )
+    .fetch_one(&ctx.pool)
+    .await
+    .map_err(|e| AppError::on_unique_violation(e, DEPARTMENT_NAME_FIELDS))?;
+    Ok((StatusCode::CREATED, Json(department)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/departments/{department_id}",
+    params(("department_id" = i32, Path)),
+    request_body = DepartmentReq,
+    responses(
+        (status = 200, description = "Department renamed, including on doctor profiles", body = Department),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Department not found"),
+        (status = 409, description = "Name already in use"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn update_department(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(department_id): Path<i32>,
+    Json(req): Json<DepartmentReq>,
+) -> AppResult<Json<Department>> {
+    let name = required_name(&req.name)?;
+    let mut tx = ctx.pool.begin().await?;
+    let department = sqlx::query_as!(
+        Department,
+        r#"
+        UPDATE departments
+        SET name = $2, updated_at = now()
+        WHERE department_id = $1
+        RETURNING department_id, name
+        "#,
+        department_id,
+        name
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::on_unique_violation(e, DEPARTMENT_NAME_FIELDS))?
+    .ok_or(AppError::NotFound)?;
+    sqlx::query!(
+        "UPDATE doctor_profile SET department = $2 WHERE department_id = $1",
+        department_id,
+        name
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Json(department))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/departments/{department_id}",
+    params(("department_id" = i32, Path)),
+    responses(
+        (status = 204, description = "Department removed"),
+        (status = 404, description = "Department not found"),
+        (status = 409, description = "Doctors still belong to the department"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn delete_department(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(department_id): Path<i32>,
+) -> AppResult<StatusCode> {
+    let result = sqlx::query!(
+        "DELETE FROM departments WHERE department_id = $1",
+        department_id
+    )
+    .execute(&ctx.pool)
+    .await;
+    match result {
+        Ok(rows) if rows.rows_affected() == 0 => Err(AppError::NotFound),
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(err) if violates_constraint(&err, DEPARTMENT_IN_USE_CONSTRAINT) => Err(
+            AppError::Conflict("doctors still belong to this department".into()),
+        ),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/locations",
+    responses((status = 200, description = "All locations by name", body = [Location])),
+    tag = "appointments"
+)]
+pub(super) async fn list_locations(State(ctx): State<Ctx>) -> AppResult<Json<Vec<Location>>> {
+    let locations = sqlx::query_as!(
+        Location,
+        r#"
+        SELECT location_id, name, building, floor, room, latitude, longitude
+        FROM locations
+        ORDER BY lower(name)
+        "#
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    Ok(Json(locations))
+}
+
+#[utoipa::path(
+    post,
+    path = "/locations",
+    request_body = LocationReq,
+    responses(
+        (status = 201, description = "Location created", body = Location),
+        (status = 400, description = "Invalid payload"),
+        (status = 409, description = "Name already in use"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn create_location(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<LocationReq>,
+) -> AppResult<(StatusCode, Json<Location>)> {
+    let req = validate_location(req)?;
+    let location = sqlx::query_as!(
+        Location,
+        r#"
+        INSERT INTO locations (name, building, floor, room, latitude, longitude)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING location_id, name, building, floor, room, latitude, longitude
+        "#,
+        req.name,
+        req.building,
+        req.floor,
+        req.room,
+        req.latitude,
+        req.longitude
+    )
+    .fetch_one(&ctx.pool)
+    .await
+    .map_err(|e| AppError::on_unique_violation(e, LOCATION_NAME_FIELDS))?;
+    Ok((StatusCode::CREATED, Json(location)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/locations/{location_id}",
+    params(("location_id" = i32, Path)),
+    request_body = LocationReq,
+    responses(
+        (status = 200, description = "Location replaced; a new name also shows on its timeslots", body = Location),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Location not found"),
+        (status = 409, description = "Name already in use"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn update_location(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(location_id): Path<i32>,
+    Json(req): Json<LocationReq>,
+) -> AppResult<Json<Location>> {
+    let req = validate_location(req)?;
+    let mut tx = ctx.pool.begin().await?;
+    let location = sqlx::query_as!(
+        Location,
+        r#"
+        UPDATE locations
+        SET name = $2,
+            building = $3,
+            floor = $4,
+            room = $5,
+            latitude = $6,
+            longitude = $7,
+            updated_at = now()
+        WHERE location_id = $1
+        RETURNING location_id, name, building, floor, room, latitude, longitude
+        "#,
+        location_id,
+        req.name,
+        req.building,
+        req.floor,
+        req.room,
+        req.latitude,
+        req.longitude
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::on_unique_violation(e, LOCATION_NAME_FIELDS))?
+    .ok_or(AppError::NotFound)?;
+    sqlx::query!(
+        "UPDATE time_slots SET place_name = $2 WHERE location_id = $1",
+        location_id,
+        location.name
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Json(location))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/locations/{location_id}",
+    params(("location_id" = i32, Path)),
+    responses(
+        (status = 204, description = "Location removed"),
+        (status = 404, description = "Location not found"),
+        (status = 409, description = "Timeslots are still held there"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn delete_location(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(location_id): Path<i32>,
+) -> AppResult<StatusCode> {
+    let result = sqlx::query!("DELETE FROM locations WHERE location_id = $1", location_id)
+        .execute(&ctx.pool)
+        .await;
+    match result {
+        Ok(rows) if rows.rows_affected() == 0 => Err(AppError::NotFound),
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(err) if violates_constraint(&err, LOCATION_IN_USE_CONSTRAINT) => Err(
+            AppError::Conflict("timeslots are still held at this location".into()),
+        ),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub(super) fn routes() -> Router<Ctx> {
+    Router::new()
+        .route(
+            "/appointments/departments",
+            get(list_departments).post(create_department),
+        )
+        .route(
+            "/appointments/departments/{department_id}",
+            put(update_department).delete(delete_department),
+        )
+        .route(
+            "/appointments/locations",
+            get(list_locations).post(create_location),
+        )
+        .route(
+            "/appointments/locations/{location_id}",
+            put(update_location).delete(delete_location),
+        )
+}
+
+/// The catalogued location a timeslot is held at: `location_id` when given,
+/// otherwise the location named `place_name`, ignoring case.
+pub(super) async fn resolve_location(
+    pool: &PgPool,
+    location_id: Option<i32>,
+    place_name: &str,
+) -> AppResult<Location> {
+    if let Some(location_id) = location_id {
+        return sqlx::query_as!(
+            Location,
+            r#"
+            SELECT location_id, name, building, floor, room, latitude, longitude
+            FROM locations
+            WHERE location_id = $1
+            "#,
+            location_id
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest(format!("unknown location_id {location_id}")));
+    }
+    let place_name = place_name.trim();
+    if place_name.is_empty() {
+        return Err(AppError::BadRequest(
+            "place_name or location_id is required".into(),
+        ));
+    }
+    sqlx::query_as!(
+        Location,
+        r#"
+        SELECT location_id, name, building, floor, room, latitude, longitude
+        FROM locations
+        WHERE lower(name) = lower($1)
+        "#,
+        place_name
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "unknown location `{place_name}`; an admin must add it to the catalogue first"
+        ))
+    })
+}
+
+fn required_name(name: &str) -> AppResult<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("name must not be empty".into()));
+    }
+    Ok(name)
+}
+
+/// Trim every field, turning blank optional ones into `None`, and check the
+/// coordinates.
+fn validate_location(req: LocationReq) -> AppResult<LocationReq> {
+    fn optional(value: Option<String>) -> Option<String> {
+        value.map(|v| v.trim().to_owned()).filter(|v| !v.is_empty())
+    }
+    let name = required_name(&req.name)?.to_owned();
+    match (req.latitude, req.longitude) {
+        (None, None) => {}
+        (Some(latitude), Some(longitude)) => {
+            if !(-90.0..=90.0).contains(&latitude) {
+                return Err(AppError::BadRequest(
+                    "latitude must be between -90 and 90".into(),
+                ));
+            }
+            if !(-180.0..=180.0).contains(&longitude) {
+                return Err(AppError::BadRequest(
+                    "longitude must be between -180 and 180".into(),
+                ));
+            }
+        }
+        _ => {
+            return Err(AppError::BadRequest(
+                "latitude and longitude must be given together".into(),
+            ));
+        }
+    }
+    Ok(LocationReq {
+        name,
+        building: optional(req.building),
+        floor: optional(req.floor),
+        room: optional(req.room),
+        latitude: req.latitude,
+        longitude: req.longitude,
+    })
+}
@@ -0,0 +1,248 @@
+//! The doctor directory: name search, department and position filters,
+//! availability on a date and the next bookable place of each doctor.
+//! Pages are keyed on the last doctor returned rather than an offset, so
+//! scrolling stays stable while doctors are added.
+use super::http::{Ctx, format_date, format_time, parse_date};
+use crate::domain::{DoctorDirectoryPage, DoctorListItem, NextAvailableSlot};
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+use common::error::{AppError, AppResult};
+use time::{Date, Time};
+use uuid::Uuid;
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+#[derive(serde::Deserialize)]
+pub(super) struct DirectoryQuery {
+    q: Option<String>,
+    department: Option<String>,
+    department_id: Option<i32>,
+    position: Option<String>,
+    available_on: Option<String>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/doctor",
+    params(
+        ("q" = Option<String>, Query, description = "Words matched against the start of the doctor's first and last names"),
+        ("department" = Option<String>, Query, description = "Department name, case-insensitive"),
+        ("department_id" = Option<i32>, Query, description = "Catalogued department"),
+        ("position" = Option<String>, Query, description = "Position, case-insensitive"),
+        ("available_on" = Option<String>, Query, description = "Only doctors with a place that can be booked on this date (YYYY-MM-DD)"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("limit" = Option<i64>, Query, description = "Page size, at most 100 (default 20)")
+    ),
+    responses(
+        (status = 200, description = "Active doctors matching the filters, by name", body = DoctorDirectoryPage),
+        (status = 400, description = "Invalid date or cursor"),
+    ),
+    tag = "appointments"
+)]
+pub(super) async fn list_doctors(
+    State(ctx): State<Ctx>,
+    Query(query): Query<DirectoryQuery>,
+) -> AppResult<Json<DoctorDirectoryPage>> {
+    let name_query = query.q.as_deref().and_then(name_tsquery);
+    let department = non_blank(query.department);
+    let position = non_blank(query.position);
+    let available_on = query.available_on.as_deref().map(parse_date).transpose()?;
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(|cursor| {
+            Uuid::parse_str(cursor).map_err(|_| AppError::BadRequest("invalid cursor".into()))
+        })
+        .transpose()?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let (earliest, last_date) = ctx.svc.booking_window();
+
+    // `matches` is every doctor passing the filters; the total counts it and
+    // the page is cut from it. One row more than the page tells whether
+    // another page follows. The totals row is joined first so it comes back
+    // even when the page is empty.
+    let rows = sqlx::query_as!(
+        DirectoryRow,
+        r#"
+        WITH bookable AS NOT MATERIALIZED (
+            SELECT o.doctor_id, o.timeslot_id, o.date, o.place_name,
+                   o.start_time, o.end_time, o.free
+            FROM timeslot_openings(COALESCE($4::date, $5::timestamp::date), COALESCE($4, $6::date)) o
+            WHERE o.date + o.start_time >= $5
+              AND o.date <= $6
+              AND o.free > 0
+        ),
+        matches AS (
+            SELECT
+                u.user_id,
+                concat_ws(' ', u.first_name, u.last_name) AS doctor_name,
+                dp.department,
+                dp.position
+            FROM users u
+            JOIN user_roles ur ON ur.user_id = u.user_id AND ur.role = 'DOCTOR'
+            LEFT JOIN doctor_profile dp ON dp.user_id = u.user_id
+            WHERE u.deactivated_at IS NULL
+              AND ($1::text IS NULL
+                   OR to_tsvector('simple', u.first_name || ' ' || u.last_name)
+                      @@ to_tsquery('simple', $1))
+              AND ($2::text IS NULL OR lower(dp.department) = lower($2))
+              AND ($9::int IS NULL OR dp.department_id = $9)
+              AND ($3::text IS NULL OR lower(dp.position) = lower($3))
+              AND ($4::date IS NULL
+                   OR EXISTS (SELECT 1 FROM bookable b WHERE b.doctor_id = u.user_id))
+        ),
+        page AS (
+            SELECT *
+            FROM matches m
+            WHERE $7::uuid IS NULL
+               OR (m.doctor_name, m.user_id) > (
+                    SELECT concat_ws(' ', c.first_name, c.last_name), c.user_id
+                    FROM users c
+                    WHERE c.user_id = $7
+                  )
+            ORDER BY m.doctor_name, m.user_id
+            LIMIT $8
+        )
+        SELECT
+            t.total AS "total!",
+            p.user_id AS "doctor_id?",
+            p.doctor_name AS "doctor_name?",
+            p.department,
+            p.position,
+            n.timeslot_id AS "next_timeslot_id?",
+            n.date AS "next_date?",
+            n.place_name AS "next_place_name?",
+            n.start_time AS "next_start_time?",
+            n.end_time AS "next_end_time?",
+            n.free AS "next_available?"
+        FROM (SELECT COUNT(*) AS total FROM matches) t
+        LEFT JOIN page p ON true
+        LEFT JOIN LATERAL (
+            SELECT b.timeslot_id, b.date, b.place_name, b.start_time, b.end_time, b.free
+            FROM bookable b
+            WHERE b.doctor_id = p.user_id
+            ORDER BY b.date, b.start_time
+            LIMIT 1
+        ) n ON true
+        ORDER BY p.doctor_name, p.user_id
+        "#,
+        name_query,
+        department,
+        position,
+        available_on,
+        earliest,
+        last_date,
+        cursor,
+        limit + 1,
+        query.department_id
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+
+    let total = rows.first().map_or(0, |row| row.total);
+    let mut items: Vec<DoctorListItem> = rows
+        .into_iter()
+        .filter_map(DirectoryRow::into_item)
+        .collect();
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|item| item.doctor_id.to_string())
+    } else {
+        None
+    };
+
+    Ok(Json(DoctorDirectoryPage {
+        items,
+        total,
+        next_cursor,
+    }))
+}
+
+pub(super) fn routes() -> Router<Ctx> {
+    Router::new().route("/appointments/doctor", get(list_doctors))
+}
+
+/// A prefix query over the words of `q`, e.g. `som:* & ja:*`, or `None`
+/// when nothing searchable is left. ASCII punctuation is dropped since it
+/// carries meaning in tsquery syntax.
+fn name_tsquery(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| !c.is_ascii() || c.is_ascii_alphanumeric())
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{word}:*"))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+fn non_blank(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+/// One doctor of the page, or a bare totals row when the page is empty.
+struct DirectoryRow {
+    total: i64,
+    doctor_id: Option<Uuid>,
+    doctor_name: Option<String>,
+    department: Option<String>,
+    position: Option<String>,
+    next_timeslot_id: Option<i32>,
+    next_date: Option<Date>,
+    next_place_name: Option<String>,
+    next_start_time: Option<Time>,
+    next_end_time: Option<Time>,
+    next_available: Option<i64>,
+}
+
+impl DirectoryRow {
+    /// The listed doctor; `None` for the totals row of an empty page.
+    fn into_item(self) -> Option<DoctorListItem> {
+        let (Some(doctor_id), Some(doctor_name)) = (self.doctor_id, self.doctor_name) else {
+            return None;
+        };
+        let next_available = match (
+            self.next_timeslot_id,
+            self.next_date,
+            self.next_place_name,
+            self.next_start_time,
+            self.next_end_time,
+            self.next_available,
+        ) {
+            (
+                Some(timeslot_id),
+                Some(date),
+                Some(place_name),
+                Some(start),
+                Some(end),
+                Some(available),
+            ) => Some(NextAvailableSlot {
+                timeslot_id,
+                date: format_date(date),
+                place_name,
+                start_time: format_time(start),
+                end_time: format_time(end),
+                available: available as i32,
+            }),
+            _ => None,
+        };
+        Some(DoctorListItem {
+            doctor_id,
+            doctor_name,
+            department: self.department,
+            position: self.position,
+            next_available,
+        })
+    }
+}
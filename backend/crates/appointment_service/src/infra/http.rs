@@ -1,39 +1,64 @@
-use super::repo_sqlx::SqlxAppointmentRepo;
+use super::{
+    calendar_http,
+    catalogue_http::{self, resolve_location},
+    directory_http, intake_http, queue_http,
+    repo_sqlx::SqlxAppointmentRepo,
+    schedule_http, waitlist_http,
+};
 use crate::{
     app::{AppointmentRepo, AppointmentService},
     domain::{
-        Appointment, AppointmentOverview, AppointmentStatus, CreateAppointmentReq,
-        DoctorAppointmentView, DoctorListItem, DoctorTimeslotView, NewAppointment,
-        UpdateTimeslotReq,
+        Appointment, AppointmentDetail, AppointmentOverview, AppointmentReschedule,
+        AppointmentStatus, AppointmentTransition, BookingErrorBody, CalendarFeedCreated,
+        CalendarFeedInfo, ClinicHoliday, ClinicHolidayReq, CreateAppointmentReq,
+        CreateOneOffTimeslotReq, CreateTimeslotReq, Department, DepartmentReq,
+        DoctorAppointmentView, DoctorBookingPolicy, DoctorDirectoryPage, DoctorListItem,
+        DoctorTimeslotView, HeldPlace, IntakeAnswer, IntakeAnswerReq, IntakeQuestion,
+        IntakeQuestionKind, IntakeQuestionReq, JoinWaitlistReq, Location, LocationReq,
+        NewAppointment, NextAvailableSlot, PreviousSlot, QueueEntry, QueuePosition,
+        RescheduleAppointment, RescheduleReq, ScheduleException, ScheduleExceptionCreated,
+        ScheduleExceptionKind, ScheduleExceptionReq, StatusChange, TimeRangeReq,
+        TimeslotAvailability, UpdateTimeslotReq, VisitIntakeReq, VisitQueue, WaitlistEntryView,
+        WaitlistStatus, WeeklyTemplateReq,
     },
 };
 use axum::{
     Extension, Json, Router,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     routing::{get, patch, post},
 };
 use common::{
-    auth::{AuthUser, JwtKeys, Role, ensure_user_role, user_has_role},
+    auth::{AuthUser, Doctor, Patient, RequireRole, Role, jwt_keys_from_config},
     config::AppConfig,
-    error::{AppError, AppResult},
+    error::{AppError, AppResult, violates_constraint},
 };
+use db::PgTx;
 use sqlx::{PgPool, Row};
 use time::macros::format_description;
-use time::{Date, OffsetDateTime, Time, Weekday};
+use time::{Date, Duration, OffsetDateTime, Time, Weekday};
 use utoipa::OpenApi;
 use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct Ctx {
-    pool: PgPool,
-    svc: AppointmentService<SqlxAppointmentRepo>,
+    pub(super) pool: PgPool,
+    pub(super) svc: AppointmentService<SqlxAppointmentRepo>,
+    pub(super) public_api_url: Option<String>,
 }
 
 impl Ctx {
-    pub fn new(pool: PgPool) -> Self {
-        let svc = AppointmentService::new(SqlxAppointmentRepo::new(pool.clone()));
-        Self { pool, svc }
+    pub fn new(pool: PgPool, cfg: &AppConfig) -> Self {
+        let svc = AppointmentService::new(
+            SqlxAppointmentRepo::new(pool.clone()),
+            cfg.booking_rules.clone(),
+            cfg.clinic_utc_offset_minutes,
+        );
+        Self {
+            pool,
+            svc,
+            public_api_url: cfg.public_api_url.clone(),
+        }
     }
 }
 
@@ -45,43 +70,30 @@ impl Ctx {
         (status = 200, description = "Book appointment successfully", body = Appointment),
         (status = 400, description = "Invalid payload"),
         (status = 404, description = "Doctor or timeslot not found"),
+        (status = 409, description = "Timeslot full or unavailable, already booked, or too many pending bookings", body = BookingErrorBody),
+        (status = 422, description = "Outside the booking window", body = BookingErrorBody),
     ),
     tag = "appointments",
     security(("bearerAuth" = []))
 )]
 async fn book(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Json(req): Json<CreateAppointmentReq>,
 ) -> AppResult<Json<Appointment>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
-    let date = parse_date(&req.date)?;
-    let start_time = parse_time(&req.start_time)?;
-    let end_time = parse_time(&req.end_time)?;
-    if start_time >= end_time {
-        return Err(AppError::BadRequest(
-            "start_time must be before end_time".into(),
-        ));
-    }
-    let day_of_week = weekday_to_i32(date.weekday());
-
-    let timeslot_id = sqlx::query_scalar!(
-        r#"
-        SELECT timeslot_id
-        FROM time_slots
-        WHERE doctor_id = $1
-          AND day_of_weeks = $2
-          AND start_time = $3
-          AND end_time = $4
-        "#,
-        req.doctor_id,
-        day_of_week,
-        start_time,
-        end_time
-    )
-    .fetch_optional(&ctx.pool)
-    .await?
-    .ok_or(AppError::NotFound)?;
+    let (timeslot_id, date) = match req.timeslot_id {
+        Some(timeslot_id) => (timeslot_id, parse_date(&req.date)?),
+        None => {
+            let (Some(doctor_id), Some(start_time), Some(end_time)) =
+                (req.doctor_id, &req.start_time, &req.end_time)
+            else {
+                return Err(AppError::BadRequest(
+                    "either timeslot_id or doctor_id, start_time and end_time are required".into(),
+                ));
+            };
+            resolve_slot(&ctx.pool, doctor_id, &req.date, start_time, end_time).await?
+        }
+    };
 
     let mut tx = ctx.pool.begin().await?;
     let appt = ctx
@@ -92,6 +104,7 @@ async fn book(
                 patient_id: user_id,
                 timeslot_id,
                 date,
+                intake: req.intake,
             },
         )
         .await?;
@@ -106,53 +119,50 @@ async fn book(
         ("appointment_id" = i32, Path, description = "Appointment ID")
     ),
     responses(
-        (status = 200, description = "Appointment found", body = Appointment),
+        (status = 200, description = "Appointment found, with the reason for the visit", body = AppointmentDetail),
         (status = 404, description = "Appointment not found"),
     ),
     tag = "appointments",
     security(("bearerAuth" = []))
 )]
 async fn get_by_id(
-    AuthUser { user_id, .. }: AuthUser,
+    user: AuthUser,
     State(ctx): State<Ctx>,
     Path(appointment_id): Path<i32>,
-) -> AppResult<Json<Appointment>> {
-    let Some(access) = sqlx::query(
-        r#"
-        SELECT a.patient_id, ts.doctor_id
-        FROM appointments a
-        JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
-        WHERE a.appointment_id = $1
-        "#,
-    )
-    .bind(appointment_id)
-    .fetch_optional(&ctx.pool)
-    .await?
-    else {
-        return Err(AppError::NotFound);
-    };
-
-    let patient_id: Uuid = access.try_get("patient_id")?;
-    let doctor_id: Uuid = access.try_get("doctor_id")?;
-
-    let mut allowed = patient_id == user_id;
-
-    if !allowed && doctor_id == user_id {
-        allowed = user_has_role(&ctx.pool, user_id, Role::Doctor).await?;
-    }
-
-    if !allowed {
-        allowed = user_has_role(&ctx.pool, user_id, Role::Admin).await?;
-    }
-
-    if !allowed {
-        return Err(AppError::Forbidden);
-    }
+) -> AppResult<Json<AppointmentDetail>> {
+    ensure_can_view(&ctx.pool, &user, appointment_id).await?;
 
-    let Some(a) = ctx.svc.repo.by_id(appointment_id).await? else {
+    let Some(appointment) = ctx.svc.repo.by_id(appointment_id).await? else {
         return Err(AppError::NotFound);
     };
-    Ok(Json(a))
+    let reason = sqlx::query!(
+        r#"
+        SELECT chief_complaint, patient_notes
+        FROM appointments
+        WHERE appointment_id = $1
+        "#,
+        appointment_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    let intake_answers = sqlx::query_as!(
+        IntakeAnswer,
+        r#"
+        SELECT question_id, prompt, answer
+        FROM appointment_intake_answers
+        WHERE appointment_id = $1
+        ORDER BY position
+        "#,
+        appointment_id
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    Ok(Json(AppointmentDetail {
+        appointment,
+        chief_complaint: reason.chief_complaint,
+        notes: reason.patient_notes,
+        intake_answers,
+    }))
 }
 
 #[utoipa::path(
@@ -166,11 +176,10 @@ async fn get_by_id(
     security(("bearerAuth" = []))
 )]
 async fn patient_upcoming(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<Vec<AppointmentOverview>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
-    let today = OffsetDateTime::now_utc().date();
+    let today = ctx.svc.today();
     let rows = sqlx::query_as!(
         AppointmentRow,
         r#"
@@ -178,20 +187,26 @@ async fn patient_upcoming(
             a.appointment_id,
             concat_ws(' ', u.first_name, u.last_name) AS "doctor_name!",
             dp.department AS "department?",
+            dp.department_id AS "department_id?",
             ts.place_name,
             a.date,
             ts.start_time,
             ts.end_time,
-            a.status as "status: _"
+            a.status as "status: _",
+            l.location_id,
+            l.building,
+            l.floor,
+            l.room,
+            l.latitude,
+            l.longitude
         FROM appointments a
         JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+        JOIN locations l ON l.location_id = ts.location_id
         JOIN users u ON u.user_id = ts.doctor_id
         LEFT JOIN doctor_profile dp ON dp.user_id = ts.doctor_id
         WHERE a.patient_id = $1
-          AND (
-                (a.status = 'PENDING' AND a.date >= $2)
-             OR (a.status = 'ACCEPTED' AND a.date >= $2)
-          )
+          AND a.status IN ('PENDING', 'ACCEPTED', 'CHECKED_IN')
+          AND a.date >= $2
         ORDER BY a.date, ts.start_time
         "#,
         user_id,
@@ -215,11 +230,10 @@ async fn patient_upcoming(
     security(("bearerAuth" = []))
 )]
 async fn patient_others(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<Vec<AppointmentOverview>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
-    let today = OffsetDateTime::now_utc().date();
+    let today = ctx.svc.today();
     let rows = sqlx::query_as!(
         AppointmentRow,
         r#"
@@ -227,19 +241,27 @@ async fn patient_others(
             a.appointment_id,
             concat_ws(' ', u.first_name, u.last_name) AS "doctor_name!",
             dp.department AS "department?",
+            dp.department_id AS "department_id?",
             ts.place_name,
             a.date,
             ts.start_time,
             ts.end_time,
-            a.status as "status: _"
+            a.status as "status: _",
+            l.location_id,
+            l.building,
+            l.floor,
+            l.room,
+            l.latitude,
+            l.longitude
         FROM appointments a
         JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+        JOIN locations l ON l.location_id = ts.location_id
         JOIN users u ON u.user_id = ts.doctor_id
         LEFT JOIN doctor_profile dp ON dp.user_id = ts.doctor_id
         WHERE a.patient_id = $1
           AND (
-                a.status IN ('CANCELED', 'REJECTED')
-             OR (a.status = 'ACCEPTED' AND a.date < $2)
+                a.status IN ('CANCELED', 'REJECTED', 'COMPLETED', 'NO_SHOW')
+             OR (a.status IN ('ACCEPTED', 'CHECKED_IN') AND a.date < $2)
           )
         ORDER BY a.date DESC, ts.start_time
         "#,
@@ -265,11 +287,10 @@ async fn patient_others(
     security(("bearerAuth" = []))
 )]
 async fn patient_by_date(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Path(date_str): Path<String>,
 ) -> AppResult<Json<Vec<AppointmentOverview>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     let date = parse_date(&date_str)?;
     let rows = sqlx::query_as!(
         AppointmentRow,
@@ -278,17 +299,25 @@ async fn patient_by_date(
             a.appointment_id,
             concat_ws(' ', u.first_name, u.last_name) AS "doctor_name!",
             dp.department AS "department?",
+            dp.department_id AS "department_id?",
             ts.place_name,
             a.date,
             ts.start_time,
             ts.end_time,
-            a.status as "status: _"
+            a.status as "status: _",
+            l.location_id,
+            l.building,
+            l.floor,
+            l.room,
+            l.latitude,
+            l.longitude
         FROM appointments a
         JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+        JOIN locations l ON l.location_id = ts.location_id
         JOIN users u ON u.user_id = ts.doctor_id
         LEFT JOIN doctor_profile dp ON dp.user_id = ts.doctor_id
         WHERE a.patient_id = $1
-          AND a.status = 'ACCEPTED'
+          AND a.status IN ('ACCEPTED', 'CHECKED_IN', 'COMPLETED')
           AND a.date = $2
         ORDER BY ts.start_time
         "#,
@@ -304,109 +333,313 @@ async fn patient_by_date(
 
 #[utoipa::path(
     get,
-    path = "/doctor",
+    path = "/doctor/{doctor_id}",
+    params(("doctor_id" = Uuid, Path)),
     responses(
-        (status = 200, description = "Doctors list", body = [DoctorListItem])
+        (status = 200, description = "Weekly timeslots, then upcoming one-off timeslots", body = [DoctorTimeslotView]),
+        (status = 404, description = "Timeslot not found"),
     ),
     tag = "appointments"
 )]
-async fn list_doctors(State(ctx): State<Ctx>) -> AppResult<Json<Vec<DoctorListItem>>> {
+async fn list_doctor_timeslots_public(
+    State(ctx): State<Ctx>,
+    Path(doctor_id): Path<Uuid>,
+) -> AppResult<Json<Vec<DoctorTimeslotView>>> {
     let rows = sqlx::query_as!(
-        DoctorRow,
+        DoctorTimeslotRow,
         r#"
         SELECT
-            u.user_id as doctor_id,
-            concat_ws(' ', u.first_name, u.last_name) AS "doctor_name!",
-            dp.department AS "department?"
-        FROM users u
-        JOIN user_roles ur ON ur.user_id = u.user_id AND ur.role = 'DOCTOR'
-        LEFT JOIN doctor_profile dp ON dp.user_id = u.user_id
-        ORDER BY "doctor_name!"
-        "#
+            ts.timeslot_id,
+            ts.day_of_weeks,
+            ts.place_name,
+            ts.start_time,
+            ts.end_time,
+            ts.capacity,
+            ts.valid_on,
+            ts.location_id,
+            l.building,
+            l.floor,
+            l.room,
+            l.latitude,
+            l.longitude
+        FROM time_slots ts
+        JOIN locations l ON l.location_id = ts.location_id
+        WHERE ts.doctor_id = $1
+          AND (ts.valid_on IS NULL OR ts.valid_on >= $2)
+        ORDER BY ts.valid_on NULLS FIRST, ts.day_of_weeks, ts.start_time
+        "#,
+        doctor_id,
+        ctx.svc.today()
     )
     .fetch_all(&ctx.pool)
     .await?;
     Ok(Json(
-        rows.into_iter()
-            .map(|row| DoctorListItem {
-                doctor_id: row.doctor_id,
-                doctor_name: row.doctor_name,
-                department: row.department,
-            })
-            .collect(),
+        rows.into_iter().map(DoctorTimeslotView::from).collect(),
     ))
 }
 
+/// Longest date range served by the availability endpoint.
+const MAX_AVAILABILITY_DAYS: i64 = 62;
+/// Range served when `to` is omitted.
+const DEFAULT_AVAILABILITY_DAYS: i64 = 7;
+
+#[derive(serde::Deserialize)]
+pub(super) struct DateRangeQuery {
+    pub(super) from: Option<String>,
+    pub(super) to: Option<String>,
+}
+
 #[utoipa::path(
     get,
-    path = "/doctor/{doctor_id}",
-    params(("doctor_id" = Uuid, Path)),
+    path = "/doctor/{doctor_id}/availability",
+    params(
+        ("doctor_id" = Uuid, Path),
+        ("from" = Option<String>, Query, description = "First date (YYYY-MM-DD), default today"),
+        ("to" = Option<String>, Query, description = "Last date (YYYY-MM-DD), default a week from `from`; at most 62 days")
+    ),
     responses(
-        (status = 200, description = "Doctor timeslots", body = [DoctorTimeslotView]),
-        (status = 404, description = "Timeslot not found"),
+        (status = 200, description = "Free capacity per timeslot and date, leaving out holidays, leave and cancelled occurrences", body = [TimeslotAvailability]),
+        (status = 400, description = "Invalid date range"),
     ),
     tag = "appointments"
 )]
-async fn list_doctor_timeslots_public(
+async fn doctor_availability(
     State(ctx): State<Ctx>,
     Path(doctor_id): Path<Uuid>,
-) -> AppResult<Json<Vec<DoctorTimeslotView>>> {
+    Query(query): Query<DateRangeQuery>,
+) -> AppResult<Json<Vec<TimeslotAvailability>>> {
+    let from = match query.from {
+        Some(from) => parse_date(&from)?,
+        None => ctx.svc.today(),
+    };
+    let to = match query.to {
+        Some(to) => parse_date(&to)?,
+        None => from + Duration::days(DEFAULT_AVAILABILITY_DAYS - 1),
+    };
+    if to < from {
+        return Err(AppError::BadRequest("to must not be before from".into()));
+    }
+    if (to - from).whole_days() >= MAX_AVAILABILITY_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "date range must not exceed {MAX_AVAILABILITY_DAYS} days"
+        )));
+    }
     let rows = sqlx::query_as!(
-        DoctorTimeslotRow,
+        AvailabilityRow,
         r#"
         SELECT
-            timeslot_id,
-            day_of_weeks,
-            place_name,
-            start_time,
-            end_time
-        FROM time_slots
+            date AS "date!",
+            timeslot_id AS "timeslot_id!",
+            place_name AS "place_name!",
+            start_time AS "start_time!",
+            end_time AS "end_time!",
+            capacity AS "capacity!",
+            capacity - free AS "booked!"
+        FROM timeslot_openings($2, $3)
         WHERE doctor_id = $1
-        ORDER BY day_of_weeks, start_time
+        ORDER BY date, start_time
         "#,
-        doctor_id
+        doctor_id,
+        from,
+        to
     )
     .fetch_all(&ctx.pool)
     .await?;
     Ok(Json(
-        rows.into_iter().map(DoctorTimeslotView::from).collect(),
+        rows.into_iter().map(TimeslotAvailability::from).collect(),
     ))
 }
 
 #[utoipa::path(
     patch,
-    path = "/{appointment_id}/canceled",
+    path = "/{appointment_id}/reschedule",
     params(("appointment_id" = i32, Path)),
+    request_body = RescheduleReq,
     responses(
-        (status = 204, description = "Appointment canceled"),
+        (status = 200, description = "Appointment moved; PENDING again unless the doctor keeps accepted appointments", body = Appointment),
+        (status = 400, description = "Invalid payload or same slot"),
+        (status = 404, description = "Appointment or timeslot not found"),
+        (status = 409, description = "Not PENDING/ACCEPTED, past the cancellation cutoff, or the new slot is full, unavailable or of another doctor", body = BookingErrorBody),
+        (status = 422, description = "The new slot is outside the booking window", body = BookingErrorBody),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+async fn reschedule_appointment(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+    Path(appointment_id): Path<i32>,
+    Json(req): Json<RescheduleReq>,
+) -> AppResult<Json<Appointment>> {
+    let doctor_id = sqlx::query_scalar!(
+        r#"
+        SELECT ts.doctor_id
+        FROM appointments a
+        JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+        WHERE a.appointment_id = $1
+          AND a.patient_id = $2
+        "#,
+        appointment_id,
+        user_id
+    )
+    .fetch_optional(&ctx.pool)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    let (timeslot_id, date) = match (req.timeslot_id, &req.start_time, &req.end_time) {
+        (Some(timeslot_id), _, _) => (timeslot_id, parse_date(&req.date)?),
+        (None, Some(start_time), Some(end_time)) => {
+            resolve_slot(&ctx.pool, doctor_id, &req.date, start_time, end_time).await?
+        }
+        _ => {
+            return Err(AppError::BadRequest(
+                "either timeslot_id or start_time and end_time are required".into(),
+            ));
+        }
+    };
+
+    let mut tx = ctx.pool.begin().await?;
+    let appt = ctx
+        .svc
+        .reschedule(
+            &mut tx,
+            RescheduleAppointment {
+                appointment_id,
+                patient_id: user_id,
+                timeslot_id,
+                date,
+            },
+        )
+        .await?;
+    tx.commit().await?;
+    Ok(Json(appt))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{appointment_id}/reschedules",
+    params(("appointment_id" = i32, Path)),
+    responses(
+        (status = 200, description = "Slots the appointment was moved away from, oldest first", body = [AppointmentReschedule]),
+        (status = 403, description = "Not the patient, the doctor or an admin"),
         (status = 404, description = "Appointment not found"),
     ),
     tag = "appointments",
     security(("bearerAuth" = []))
 )]
-async fn cancel_appointment(
-    AuthUser { user_id, .. }: AuthUser,
+async fn appointment_reschedules(
+    user: AuthUser,
     State(ctx): State<Ctx>,
     Path(appointment_id): Path<i32>,
+) -> AppResult<Json<Vec<AppointmentReschedule>>> {
+    ensure_can_view(&ctx.pool, &user, appointment_id).await?;
+    let rows = sqlx::query_as!(
+        RescheduleRow,
+        r#"
+        SELECT
+            r.from_date,
+            fts.start_time AS from_start_time,
+            fts.end_time AS from_end_time,
+            fts.place_name AS from_place_name,
+            r.from_status AS "from_status: _",
+            r.to_date,
+            tts.start_time AS to_start_time,
+            tts.end_time AS to_end_time,
+            tts.place_name AS to_place_name,
+            r.rescheduled_at
+        FROM appointment_reschedules r
+        JOIN time_slots fts ON fts.timeslot_id = r.from_timeslot_id
+        JOIN time_slots tts ON tts.timeslot_id = r.to_timeslot_id
+        WHERE r.appointment_id = $1
+        ORDER BY r.reschedule_id
+        "#,
+        appointment_id
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    Ok(Json(
+        rows.into_iter().map(AppointmentReschedule::from).collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/policy",
+    responses(
+        (status = 200, description = "The doctor's booking policy", body = DoctorBookingPolicy),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+async fn get_booking_policy(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+) -> AppResult<Json<DoctorBookingPolicy>> {
+    let keep_accepted_on_reschedule = sqlx::query_scalar!(
+        r#"
+        SELECT keep_accepted_on_reschedule
+        FROM doctor_booking_policies
+        WHERE doctor_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(&ctx.pool)
+    .await?
+    .unwrap_or(false);
+    Ok(Json(DoctorBookingPolicy {
+        keep_accepted_on_reschedule,
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/policy",
+    request_body = DoctorBookingPolicy,
+    responses(
+        (status = 204, description = "Policy saved"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+async fn put_booking_policy(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<DoctorBookingPolicy>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
-    let mut tx = ctx.pool.begin().await?;
-    let rows = sqlx::query(
+    sqlx::query!(
         r#"
-        UPDATE appointments
-        SET status = $3
-        WHERE appointment_id = $1
-          AND patient_id = $2
+        INSERT INTO doctor_booking_policies (doctor_id, keep_accepted_on_reschedule)
+        VALUES ($1, $2)
+        ON CONFLICT (doctor_id) DO UPDATE
+        SET keep_accepted_on_reschedule = EXCLUDED.keep_accepted_on_reschedule,
+            updated_at = now()
         "#,
+        user_id,
+        req.keep_accepted_on_reschedule
     )
-    .bind(appointment_id)
-    .bind(user_id)
-    .bind(AppointmentStatus::CANCELED)
-    .execute(&mut *tx)
+    .execute(&ctx.pool)
     .await?;
-    if rows.rows_affected() == 0 {
-        return Err(AppError::NotFound);
-    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    patch,
+    path = "/{appointment_id}/canceled",
+    params(("appointment_id" = i32, Path)),
+    responses(
+        (status = 204, description = "Appointment canceled"),
+        (status = 404, description = "Appointment not found"),
+        (status = 409, description = "The appointment can no longer be canceled", body = BookingErrorBody),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+async fn cancel_appointment(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+    Path(appointment_id): Path<i32>,
+) -> AppResult<StatusCode> {
+    let mut tx = ctx.pool.begin().await?;
+    ctx.svc.cancel(&mut tx, appointment_id, user_id).await?;
     tx.commit().await?;
     Ok(StatusCode::NO_CONTENT)
 }
@@ -416,33 +649,20 @@ async fn cancel_appointment(
     path = "/{appointment_id}",
     params(("appointment_id" = i32, Path)),
     responses(
-        (status = 204, description = "Appointment deleted"),
+        (status = 204, description = "Appointment canceled; it stays on record with its history"),
         (status = 404, description = "Appointment not found"),
+        (status = 409, description = "The appointment can no longer be canceled", body = BookingErrorBody),
     ),
     tag = "appointments",
     security(("bearerAuth" = []))
 )]
 async fn delete_appointment(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Path(appointment_id): Path<i32>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     let mut tx = ctx.pool.begin().await?;
-    let rows = sqlx::query!(
-        r#"
-        DELETE FROM appointments
-        WHERE appointment_id = $1
-          AND patient_id = $2
-        "#,
-        appointment_id,
-        user_id
-    )
-    .execute(&mut *tx)
-    .await?;
-    if rows.rows_affected() == 0 {
-        return Err(AppError::NotFound);
-    }
+    ctx.svc.cancel(&mut tx, appointment_id, user_id).await?;
     tx.commit().await?;
     Ok(StatusCode::NO_CONTENT)
 }
@@ -459,11 +679,10 @@ async fn delete_appointment(
     security(("bearerAuth" = []))
 )]
 async fn doctor_schedule_by_date(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
     State(ctx): State<Ctx>,
     Path(date_str): Path<String>,
 ) -> AppResult<Json<Vec<DoctorAppointmentView>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
     let date = parse_date(&date_str)?;
     let rows = sqlx::query_as!(
         DoctorAppointmentRow,
@@ -475,10 +694,25 @@ async fn doctor_schedule_by_date(
             a.date,
             ts.start_time,
             ts.end_time,
-            a.status as "status: _"
+            a.status as "status: _",
+            (SELECT COUNT(*) FROM appointment_reschedules r
+             WHERE r.appointment_id = a.appointment_id)::int AS "reschedule_count!",
+            prev.from_date AS "previous_date?",
+            prev.start_time AS "previous_start_time?",
+            prev.end_time AS "previous_end_time?",
+            a.chief_complaint,
+            a.patient_notes AS notes
         FROM appointments a
         JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
         JOIN users up ON up.user_id = a.patient_id
+        LEFT JOIN LATERAL (
+            SELECT r.from_date, fts.start_time, fts.end_time
+            FROM appointment_reschedules r
+            JOIN time_slots fts ON fts.timeslot_id = r.from_timeslot_id
+            WHERE r.appointment_id = a.appointment_id
+            ORDER BY r.reschedule_id DESC
+            LIMIT 1
+        ) prev ON true
         WHERE ts.doctor_id = $1
           AND a.date = $2
         ORDER BY ts.start_time
@@ -488,9 +722,9 @@ async fn doctor_schedule_by_date(
     )
     .fetch_all(&ctx.pool)
     .await?;
-    Ok(Json(
-        rows.into_iter().map(DoctorAppointmentView::from).collect(),
-    ))
+    let mut views: Vec<_> = rows.into_iter().map(DoctorAppointmentView::from).collect();
+    attach_intake_answers(&ctx.pool, &mut views).await?;
+    Ok(Json(views))
 }
 
 #[utoipa::path(
@@ -504,10 +738,9 @@ async fn doctor_schedule_by_date(
     security(("bearerAuth" = []))
 )]
 async fn doctor_pending_requests(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<Vec<DoctorAppointmentView>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
     let rows = sqlx::query_as!(
         DoctorAppointmentRow,
         r#"
@@ -518,10 +751,25 @@ async fn doctor_pending_requests(
             a.date,
             ts.start_time,
             ts.end_time,
-            a.status as "status: _"
+            a.status as "status: _",
+            (SELECT COUNT(*) FROM appointment_reschedules r
+             WHERE r.appointment_id = a.appointment_id)::int AS "reschedule_count!",
+            prev.from_date AS "previous_date?",
+            prev.start_time AS "previous_start_time?",
+            prev.end_time AS "previous_end_time?",
+            a.chief_complaint,
+            a.patient_notes AS notes
         FROM appointments a
         JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
         JOIN users up ON up.user_id = a.patient_id
+        LEFT JOIN LATERAL (
+            SELECT r.from_date, fts.start_time, fts.end_time
+            FROM appointment_reschedules r
+            JOIN time_slots fts ON fts.timeslot_id = r.from_timeslot_id
+            WHERE r.appointment_id = a.appointment_id
+            ORDER BY r.reschedule_id DESC
+            LIMIT 1
+        ) prev ON true
         WHERE ts.doctor_id = $1
           AND a.status = 'PENDING'
         ORDER BY a.date, ts.start_time
@@ -530,9 +778,9 @@ async fn doctor_pending_requests(
     )
     .fetch_all(&ctx.pool)
     .await?;
-    Ok(Json(
-        rows.into_iter().map(DoctorAppointmentView::from).collect(),
-    ))
+    let mut views: Vec<_> = rows.into_iter().map(DoctorAppointmentView::from).collect();
+    attach_intake_answers(&ctx.pool, &mut views).await?;
+    Ok(Json(views))
 }
 
 #[utoipa::path(
@@ -546,10 +794,9 @@ async fn doctor_pending_requests(
     security(("bearerAuth" = []))
 )]
 async fn doctor_assessed_requests(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<Vec<DoctorAppointmentView>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
     let rows = sqlx::query_as!(
         DoctorAppointmentRow,
         r#"
@@ -560,10 +807,25 @@ async fn doctor_assessed_requests(
             a.date,
             ts.start_time,
             ts.end_time,
-            a.status as "status: _"
+            a.status as "status: _",
+            (SELECT COUNT(*) FROM appointment_reschedules r
+             WHERE r.appointment_id = a.appointment_id)::int AS "reschedule_count!",
+            prev.from_date AS "previous_date?",
+            prev.start_time AS "previous_start_time?",
+            prev.end_time AS "previous_end_time?",
+            a.chief_complaint,
+            a.patient_notes AS notes
         FROM appointments a
         JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
         JOIN users up ON up.user_id = a.patient_id
+        LEFT JOIN LATERAL (
+            SELECT r.from_date, fts.start_time, fts.end_time
+            FROM appointment_reschedules r
+            JOIN time_slots fts ON fts.timeslot_id = r.from_timeslot_id
+            WHERE r.appointment_id = a.appointment_id
+            ORDER BY r.reschedule_id DESC
+            LIMIT 1
+        ) prev ON true
         WHERE ts.doctor_id = $1
           AND a.status <> 'PENDING'
         ORDER BY a.date DESC, ts.start_time
@@ -572,40 +834,50 @@ async fn doctor_assessed_requests(
     )
     .fetch_all(&ctx.pool)
     .await?;
-    Ok(Json(
-        rows.into_iter().map(DoctorAppointmentView::from).collect(),
-    ))
+    let mut views: Vec<_> = rows.into_iter().map(DoctorAppointmentView::from).collect();
+    attach_intake_answers(&ctx.pool, &mut views).await?;
+    Ok(Json(views))
 }
 
 #[utoipa::path(
     get,
     path = "/timeslots",
     responses(
-        (status = 200, description = "Doctor timeslots", body = [DoctorTimeslotView]),
+        (status = 200, description = "Weekly timeslots, then upcoming one-off timeslots", body = [DoctorTimeslotView]),
         (status = 404, description = "Timeslot not found"),
     ),
     tag = "appointments",
     security(("bearerAuth" = []))
 )]
 async fn list_my_timeslots(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<Vec<DoctorTimeslotView>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
     let rows = sqlx::query_as!(
         DoctorTimeslotRow,
         r#"
         SELECT
-            timeslot_id,
-            day_of_weeks,
-            place_name,
-            start_time,
-            end_time
-        FROM time_slots
-        WHERE doctor_id = $1
-        ORDER BY day_of_weeks, start_time
+            ts.timeslot_id,
+            ts.day_of_weeks,
+            ts.place_name,
+            ts.start_time,
+            ts.end_time,
+            ts.capacity,
+            ts.valid_on,
+            ts.location_id,
+            l.building,
+            l.floor,
+            l.room,
+            l.latitude,
+            l.longitude
+        FROM time_slots ts
+        JOIN locations l ON l.location_id = ts.location_id
+        WHERE ts.doctor_id = $1
+          AND (ts.valid_on IS NULL OR ts.valid_on >= $2)
+        ORDER BY ts.valid_on NULLS FIRST, ts.day_of_weeks, ts.start_time
         "#,
-        user_id
+        user_id,
+        ctx.svc.today()
     )
     .fetch_all(&ctx.pool)
     .await?;
@@ -615,57 +887,253 @@ async fn list_my_timeslots(
 }
 
 #[utoipa::path(
-    patch,
-    path = "/timeslots/{timeslot_id}",
-    params(("timeslot_id" = i32, Path)),
-    request_body = UpdateTimeslotReq,
+    post,
+    path = "/timeslots",
+    request_body = CreateTimeslotReq,
     responses(
-        (status = 204, description = "Timeslot updated"),
+        (status = 201, description = "Timeslot created", body = DoctorTimeslotView),
         (status = 400, description = "Invalid payload"),
-        (status = 404, description = "Timeslot not found"),
+        (status = 409, description = "Overlaps an existing timeslot or an upcoming one-off timeslot"),
     ),
     tag = "appointments",
     security(("bearerAuth" = []))
 )]
-async fn update_timeslot(
-    AuthUser { user_id, .. }: AuthUser,
+async fn create_timeslot(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
     State(ctx): State<Ctx>,
-    Path(timeslot_id): Path<i32>,
-    Json(req): Json<UpdateTimeslotReq>,
-) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-    if !(0..=6).contains(&req.day_of_weeks) {
-        return Err(AppError::BadRequest(
-            "day_of_weeks must be between 0 and 6".into(),
-        ));
-    }
-    let start_time = parse_time(&req.start_time)?;
-    let end_time = parse_time(&req.end_time)?;
-    if start_time >= end_time {
-        return Err(AppError::BadRequest(
-            "start_time must be before end_time".into(),
-        ));
-    }
+    Json(req): Json<CreateTimeslotReq>,
+) -> AppResult<(StatusCode, Json<DoctorTimeslotView>)> {
+    let location = resolve_location(&ctx.pool, req.location_id, &req.place_name).await?;
+    let slot = validate_timeslot(
+        req.day_of_weeks,
+        &location,
+        &req.start_time,
+        &req.end_time,
+        req.capacity,
+    )?;
     let mut tx = ctx.pool.begin().await?;
-    let rows = sqlx::query!(
+    if let Some(clash) = one_off_clash(&mut tx, user_id, &slot, None, ctx.svc.today()).await? {
+        return Err(overlap_conflict(&clash));
+    }
+    let created = match insert_timeslot(&mut tx, user_id, &slot).await {
+        Ok(row) => row,
+        Err(err) if violates_constraint(&err, TIMESLOT_OVERLAP_CONSTRAINT) => {
+            drop(tx);
+            return Err(timeslot_conflict(&ctx.pool, user_id, &slot, None).await);
+        }
+        Err(err) => return Err(err.into()),
+    };
+    tx.commit().await?;
+    Ok((StatusCode::CREATED, Json(created.into())))
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeslots/bulk",
+    request_body = WeeklyTemplateReq,
+    responses(
+        (status = 201, description = "Timeslots created", body = [DoctorTimeslotView]),
+        (status = 400, description = "Invalid payload or overlapping periods"),
+        (status = 409, description = "Overlaps an existing timeslot; nothing was created"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+async fn create_timeslots_from_template(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<WeeklyTemplateReq>,
+) -> AppResult<(StatusCode, Json<Vec<DoctorTimeslotView>>)> {
+    if req.days.is_empty() || req.periods.is_empty() {
+        return Err(AppError::BadRequest(
+            "days and periods must not be empty".into(),
+        ));
+    }
+    let mut days = req.days.clone();
+    days.sort_unstable();
+    days.dedup();
+    let location = resolve_location(&ctx.pool, req.location_id, &req.place_name).await?;
+    let mut slots = Vec::with_capacity(days.len() * req.periods.len());
+    for &day in &days {
+        for period in &req.periods {
+            slots.push(validate_timeslot(
+                day,
+                &location,
+                &period.start_time,
+                &period.end_time,
+                req.capacity,
+            )?);
+        }
+    }
+    let mut periods: Vec<_> = slots
+        .iter()
+        .filter(|slot| slot.day_of_weeks == days[0])
+        .collect();
+    periods.sort_by_key(|slot| slot.start_time);
+    if let Some(pair) = periods
+        .windows(2)
+        .find(|pair| pair[1].start_time < pair[0].end_time)
+    {
+        return Err(AppError::BadRequest(format!(
+            "periods {}-{} and {}-{} overlap",
+            format_time(pair[0].start_time),
+            format_time(pair[0].end_time),
+            format_time(pair[1].start_time),
+            format_time(pair[1].end_time),
+        )));
+    }
+
+    let mut tx = ctx.pool.begin().await?;
+    let mut created = Vec::with_capacity(slots.len());
+    let today = ctx.svc.today();
+    for slot in &slots {
+        if let Some(clash) = one_off_clash(&mut tx, user_id, slot, None, today).await? {
+            return Err(overlap_conflict(&clash));
+        }
+        match insert_timeslot(&mut tx, user_id, slot).await {
+            Ok(row) => created.push(DoctorTimeslotView::from(row)),
+            Err(err) if violates_constraint(&err, TIMESLOT_OVERLAP_CONSTRAINT) => {
+                drop(tx);
+                return Err(timeslot_conflict(&ctx.pool, user_id, slot, None).await);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    tx.commit().await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/timeslots/{timeslot_id}",
+    params(("timeslot_id" = i32, Path)),
+    request_body = UpdateTimeslotReq,
+    responses(
+        (status = 204, description = "Timeslot updated"),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "No such weekly timeslot; one-off timeslots cannot be edited"),
+        (status = 409, description = "Overlaps another timeslot, moves the day or time under upcoming appointments, or drops capacity below the places taken"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+async fn update_timeslot(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Path(timeslot_id): Path<i32>,
+    Json(req): Json<UpdateTimeslotReq>,
+) -> AppResult<StatusCode> {
+    let location = resolve_location(&ctx.pool, req.location_id, &req.place_name).await?;
+    let slot = validate_timeslot(
+        req.day_of_weeks,
+        &location,
+        &req.start_time,
+        &req.end_time,
+        req.capacity,
+    )?;
+    let mut tx = ctx.pool.begin().await?;
+    // Bookings of the timeslot serialize on this lock, so the counts below
+    // stay true until the update commits.
+    let current = sqlx::query!(
+        r#"
+        SELECT day_of_weeks, start_time, end_time
+        FROM time_slots
+        WHERE timeslot_id = $1
+          AND doctor_id = $2
+          AND valid_on IS NULL
+        FOR UPDATE
+        "#,
+        timeslot_id,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    let taken = sqlx::query!(
+        r#"
+        WITH taken AS (
+            SELECT date, status::text AS status
+            FROM appointments
+            WHERE timeslot_id = $1
+              AND date >= $2
+              AND status NOT IN ('REJECTED', 'CANCELED')
+            UNION ALL
+            SELECT date, 'OFFERED'
+            FROM waitlist_entries
+            WHERE offered_timeslot_id = $1
+              AND date >= $2
+              AND status = 'OFFERED'
+              AND offer_expires_at > now()
+        )
+        SELECT
+            COUNT(*) FILTER (WHERE status IN ('PENDING', 'ACCEPTED', 'CHECKED_IN', 'OFFERED'))
+                AS "upcoming!",
+            COALESCE(
+                (SELECT MAX(n) FROM (SELECT COUNT(*) AS n FROM taken GROUP BY date) per_date),
+                0
+            ) AS "busiest!"
+        FROM taken
+        "#,
+        timeslot_id,
+        ctx.svc.today()
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let moves = current.day_of_weeks != slot.day_of_weeks
+        || current.start_time != slot.start_time
+        || current.end_time != slot.end_time;
+    if moves && taken.upcoming > 0 {
+        return Err(AppError::Conflict(format!(
+            "{} upcoming appointments or held places are in this timeslot; \
+             cancel or move them before changing its day or time",
+            taken.upcoming
+        )));
+    }
+    if let Some(capacity) = slot.capacity
+        && i64::from(capacity) < taken.busiest
+    {
+        return Err(AppError::Conflict(format!(
+            "capacity cannot drop below {}, the places already taken on one date",
+            taken.busiest
+        )));
+    }
+    if let Some(clash) =
+        one_off_clash(&mut tx, user_id, &slot, Some(timeslot_id), ctx.svc.today()).await?
+    {
+        return Err(overlap_conflict(&clash));
+    }
+    let result = sqlx::query!(
         r#"
         UPDATE time_slots
         SET day_of_weeks = $3,
             place_name = $4,
-            start_time = $5,
-            end_time = $6
+            location_id = $5,
+            start_time = $6,
+            end_time = $7,
+            capacity = COALESCE($8, capacity)
         WHERE timeslot_id = $1
           AND doctor_id = $2
+          AND valid_on IS NULL
         "#,
         timeslot_id,
         user_id,
-        req.day_of_weeks,
-        req.place_name,
-        start_time,
-        end_time
+        slot.day_of_weeks,
+        slot.place_name,
+        slot.location_id,
+        slot.start_time,
+        slot.end_time,
+        slot.capacity
     )
     .execute(&mut *tx)
-    .await?;
+    .await;
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(err) if violates_constraint(&err, TIMESLOT_OVERLAP_CONSTRAINT) => {
+            drop(tx);
+            return Err(timeslot_conflict(&ctx.pool, user_id, &slot, Some(timeslot_id)).await);
+        }
+        Err(err) => return Err(err.into()),
+    };
     if rows.rows_affected() == 0 {
         return Err(AppError::NotFound);
     }
@@ -685,11 +1153,10 @@ async fn update_timeslot(
     security(("bearerAuth" = []))
 )]
 async fn remove_timeslot(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
     State(ctx): State<Ctx>,
     Path(timeslot_id): Path<i32>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
     let mut tx = ctx.pool.begin().await?;
     let rows = sqlx::query!(
         r#"
@@ -714,58 +1181,99 @@ async fn remove_timeslot(
     path = "/{appointment_id}/status/{action}",
     params(
         ("appointment_id" = i32, Path),
-        ("action" = String, Path, description = "accept or reject")
+        ("action" = String, Path, description = "accept, reject, check-in, complete, no-show or cancel")
     ),
     responses(
         (status = 204, description = "Appointment status updated"),
         (status = 400, description = "Invalid action"),
         (status = 404, description = "Appointment not found"),
+        (status = 409, description = "Not allowed from the current status or on this date"),
     ),
     tag = "appointments",
     security(("bearerAuth" = []))
 )]
 async fn doctor_update_appointment_status(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
     State(ctx): State<Ctx>,
     Path((appointment_id, action)): Path<(i32, String)>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-    let status = match action.as_str() {
-        "accept" | "ACCEPT" => AppointmentStatus::ACCEPTED,
-        "reject" | "REJECT" => AppointmentStatus::REJECTED,
+    let status = match action.to_ascii_lowercase().replace('_', "-").as_str() {
+        "accept" => AppointmentStatus::ACCEPTED,
+        "reject" => AppointmentStatus::REJECTED,
+        "check-in" => AppointmentStatus::CHECKED_IN,
+        "complete" => AppointmentStatus::COMPLETED,
+        "no-show" => AppointmentStatus::NO_SHOW,
+        "cancel" => AppointmentStatus::CANCELED,
         _ => {
             return Err(AppError::BadRequest(
-                "action must be accept or reject".into(),
+                "action must be accept, reject, check-in, complete, no-show or cancel".into(),
             ));
         }
     };
+    match appointment_parties(&ctx.pool, appointment_id).await? {
+        Some((_, doctor_id)) if doctor_id == user_id => {}
+        _ => return Err(AppError::NotFound),
+    }
     let mut tx = ctx.pool.begin().await?;
-    let rows = sqlx::query(
+    ctx.svc
+        .change_status(
+            &mut tx,
+            StatusChange {
+                appointment_id,
+                to: status,
+                actor_id: user_id,
+                note: None,
+            },
+        )
+        .await?;
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/{appointment_id}/transitions",
+    params(("appointment_id" = i32, Path)),
+    responses(
+        (status = 200, description = "Status changes, oldest first", body = [AppointmentTransition]),
+        (status = 403, description = "Not the patient, the doctor or an admin"),
+        (status = 404, description = "Appointment not found"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+async fn appointment_transitions(
+    user: AuthUser,
+    State(ctx): State<Ctx>,
+    Path(appointment_id): Path<i32>,
+) -> AppResult<Json<Vec<AppointmentTransition>>> {
+    ensure_can_view(&ctx.pool, &user, appointment_id).await?;
+    let rows = sqlx::query_as!(
+        AppointmentTransition,
         r#"
-        UPDATE appointments a
-        SET status = $3
-        FROM time_slots ts
-        WHERE a.appointment_id = $1
-          AND a.timeslot_id = ts.timeslot_id
-          AND ts.doctor_id = $2
+        SELECT
+            t.from_status AS "from_status: _",
+            t.to_status AS "to_status: _",
+            t.actor_id,
+            NULLIF(concat_ws(' ', u.first_name, u.last_name), '') AS actor_name,
+            t.note,
+            t.created_at
+        FROM appointment_transitions t
+        LEFT JOIN users u ON u.user_id = t.actor_id
+        WHERE t.appointment_id = $1
+        ORDER BY t.transition_id
         "#,
+        appointment_id
     )
-    .bind(appointment_id)
-    .bind(user_id)
-    .bind(status)
-    .execute(&mut *tx)
+    .fetch_all(&ctx.pool)
     .await?;
-    if rows.rows_affected() == 0 {
-        return Err(AppError::NotFound);
-    }
-    tx.commit().await?;
-    Ok(StatusCode::NO_CONTENT)
+    Ok(Json(rows))
 }
 
 pub fn router(pool: PgPool) -> Router {
-    let ctx = Ctx::new(pool.clone());
     let cfg = AppConfig::from_env();
-    let jwt_keys = JwtKeys::from_secret(&cfg.jwt_secret);
+    let ctx = Ctx::new(pool.clone(), &cfg);
+    let jwt_keys = jwt_keys_from_config(&cfg);
     Router::new()
         .route("/appointments", post(book))
         .route(
@@ -775,15 +1283,34 @@ pub fn router(pool: PgPool) -> Router {
         .route("/appointments/status", get(patient_upcoming))
         .route("/appointments/status/others", get(patient_others))
         .route("/appointments/by-date/{date}", get(patient_by_date))
-        .route("/appointments/doctor", get(list_doctors))
         .route(
             "/appointments/doctor/{doctor_id}",
             get(list_doctor_timeslots_public),
         )
+        .route(
+            "/appointments/doctor/{doctor_id}/availability",
+            get(doctor_availability),
+        )
         .route(
             "/appointments/{appointment_id}/canceled",
             patch(cancel_appointment),
         )
+        .route(
+            "/appointments/{appointment_id}/reschedule",
+            patch(reschedule_appointment),
+        )
+        .route(
+            "/appointments/{appointment_id}/reschedules",
+            get(appointment_reschedules),
+        )
+        .route(
+            "/appointments/{appointment_id}/transitions",
+            get(appointment_transitions),
+        )
+        .route(
+            "/appointments/policy",
+            get(get_booking_policy).put(put_booking_policy),
+        )
         .route(
             "/appointments/{appointment_id}/status/{action}",
             patch(doctor_update_appointment_status),
@@ -794,13 +1321,28 @@ pub fn router(pool: PgPool) -> Router {
             "/appointments/by-doctor/{date}",
             get(doctor_schedule_by_date),
         )
-        .route("/appointments/timeslots", get(list_my_timeslots))
+        .route(
+            "/appointments/timeslots",
+            get(list_my_timeslots).post(create_timeslot),
+        )
+        .route(
+            "/appointments/timeslots/bulk",
+            post(create_timeslots_from_template),
+        )
         .route(
             "/appointments/timeslots/{timeslot_id}",
             patch(update_timeslot).delete(remove_timeslot),
         )
+        .merge(schedule_http::routes())
+        .merge(waitlist_http::routes())
+        .merge(calendar_http::routes())
+        .merge(directory_http::routes())
+        .merge(catalogue_http::routes())
+        .merge(intake_http::routes())
+        .merge(queue_http::routes())
         .with_state(ctx)
         .layer(Extension(jwt_keys))
+        .layer(Extension(pool))
 }
 
 #[derive(OpenApi, Default)]
@@ -811,26 +1353,106 @@ pub fn router(pool: PgPool) -> Router {
         patient_upcoming,
         patient_others,
         patient_by_date,
-        list_doctors,
+        directory_http::list_doctors,
         list_doctor_timeslots_public,
+        doctor_availability,
         doctor_schedule_by_date,
         doctor_pending_requests,
         doctor_assessed_requests,
         list_my_timeslots,
+        create_timeslot,
+        create_timeslots_from_template,
         update_timeslot,
         remove_timeslot,
+        schedule_http::create_one_off_timeslot,
+        schedule_http::list_schedule_exceptions,
+        schedule_http::create_schedule_exception,
+        schedule_http::schedule_exception_appointments,
+        schedule_http::delete_schedule_exception,
+        schedule_http::list_holidays,
+        schedule_http::put_holiday,
+        schedule_http::delete_holiday,
+        waitlist_http::join_waitlist,
+        waitlist_http::my_waitlist,
+        waitlist_http::doctor_waitlist,
+        waitlist_http::confirm_waitlist_offer,
+        waitlist_http::leave_waitlist,
+        calendar_http::appointment_ics,
+        calendar_http::get_calendar_feed,
+        calendar_http::create_calendar_feed,
+        calendar_http::delete_calendar_feed,
+        calendar_http::calendar_feed,
+        catalogue_http::list_departments,
+        catalogue_http::create_department,
+        catalogue_http::update_department,
+        catalogue_http::delete_department,
+        catalogue_http::list_locations,
+        catalogue_http::create_location,
+        catalogue_http::update_location,
+        catalogue_http::delete_location,
+        intake_http::department_questionnaire,
+        intake_http::put_department_questionnaire,
+        intake_http::doctor_questionnaire,
+        queue_http::check_in,
+        queue_http::appointment_queue_position,
+        queue_http::doctor_queue,
+        queue_http::call_next,
         cancel_appointment,
+        reschedule_appointment,
+        appointment_reschedules,
+        get_booking_policy,
+        put_booking_policy,
         delete_appointment,
-        doctor_update_appointment_status
+        doctor_update_appointment_status,
+        appointment_transitions
     ),
     components(schemas(
         Appointment,
         AppointmentOverview,
         CreateAppointmentReq,
+        BookingErrorBody,
         DoctorListItem,
+        NextAvailableSlot,
+        DoctorDirectoryPage,
         DoctorTimeslotView,
         DoctorAppointmentView,
-        UpdateTimeslotReq
+        UpdateTimeslotReq,
+        CreateTimeslotReq,
+        TimeRangeReq,
+        WeeklyTemplateReq,
+        TimeslotAvailability,
+        CreateOneOffTimeslotReq,
+        ScheduleExceptionKind,
+        ScheduleException,
+        ScheduleExceptionReq,
+        ScheduleExceptionCreated,
+        ClinicHoliday,
+        ClinicHolidayReq,
+        RescheduleReq,
+        AppointmentReschedule,
+        PreviousSlot,
+        DoctorBookingPolicy,
+        AppointmentTransition,
+        JoinWaitlistReq,
+        WaitlistStatus,
+        WaitlistEntryView,
+        HeldPlace,
+        CalendarFeedCreated,
+        CalendarFeedInfo,
+        Department,
+        DepartmentReq,
+        Location,
+        LocationReq,
+        IntakeQuestionKind,
+        IntakeQuestion,
+        IntakeQuestionReq,
+        VisitIntakeReq,
+        IntakeAnswerReq,
+        IntakeAnswer,
+        AppointmentDetail,
+        QueueEntry,
+        VisitQueue,
+        QueuePosition
     )),
     modifiers(&SecurityAddon),
     tags((name = "appointments", description = "Appointment APIs"))
@@ -857,65 +1479,134 @@ struct AppointmentRow {
     appointment_id: i32,
     doctor_name: String,
     department: Option<String>,
+    department_id: Option<i32>,
     place_name: String,
     date: Date,
     start_time: Time,
     end_time: Time,
     status: AppointmentStatus,
+    location_id: i32,
+    building: Option<String>,
+    floor: Option<String>,
+    room: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
 }
 
 impl From<AppointmentRow> for AppointmentOverview {
     fn from(row: AppointmentRow) -> Self {
+        let department_detail =
+            row.department_id
+                .zip(row.department.clone())
+                .map(|(department_id, name)| Department {
+                    department_id,
+                    name,
+                });
         Self {
             appointment_id: row.appointment_id,
             doctor_name: row.doctor_name,
             department: row.department,
-            place_name: row.place_name,
             date: format_date(row.date),
             start_time: format_time(row.start_time),
             end_time: format_time(row.end_time),
             status: row.status,
+            department_detail,
+            location: Location {
+                location_id: row.location_id,
+                name: row.place_name.clone(),
+                building: row.building,
+                floor: row.floor,
+                room: row.room,
+                latitude: row.latitude,
+                longitude: row.longitude,
+            },
+            place_name: row.place_name,
         }
     }
 }
 
 #[derive(sqlx::FromRow)]
-struct DoctorRow {
-    doctor_id: Uuid,
-    doctor_name: String,
-    department: Option<String>,
+pub(super) struct DoctorTimeslotRow {
+    pub(super) timeslot_id: i32,
+    pub(super) day_of_weeks: i32,
+    pub(super) place_name: String,
+    pub(super) start_time: Time,
+    pub(super) end_time: Time,
+    pub(super) capacity: i32,
+    pub(super) valid_on: Option<Date>,
+    pub(super) location_id: i32,
+    pub(super) building: Option<String>,
+    pub(super) floor: Option<String>,
+    pub(super) room: Option<String>,
+    pub(super) latitude: Option<f64>,
+    pub(super) longitude: Option<f64>,
+}
+
+impl From<DoctorTimeslotRow> for DoctorTimeslotView {
+    fn from(row: DoctorTimeslotRow) -> Self {
+        Self {
+            timeslot_id: row.timeslot_id,
+            day_of_weeks: row.day_of_weeks,
+            start_time: format_time(row.start_time),
+            end_time: format_time(row.end_time),
+            capacity: row.capacity,
+            valid_on: row.valid_on.map(format_date),
+            location: Location {
+                location_id: row.location_id,
+                name: row.place_name.clone(),
+                building: row.building,
+                floor: row.floor,
+                room: row.room,
+                latitude: row.latitude,
+                longitude: row.longitude,
+            },
+            place_name: row.place_name,
+        }
+    }
 }
 
 #[derive(sqlx::FromRow)]
-struct DoctorTimeslotRow {
+struct AvailabilityRow {
+    date: Date,
     timeslot_id: i32,
-    day_of_weeks: i32,
     place_name: String,
     start_time: Time,
     end_time: Time,
+    capacity: i32,
+    booked: i64,
 }
 
-impl From<DoctorTimeslotRow> for DoctorTimeslotView {
-    fn from(row: DoctorTimeslotRow) -> Self {
+impl From<AvailabilityRow> for TimeslotAvailability {
+    fn from(row: AvailabilityRow) -> Self {
+        let booked = i32::try_from(row.booked).unwrap_or(i32::MAX);
         Self {
+            date: format_date(row.date),
             timeslot_id: row.timeslot_id,
-            day_of_weeks: row.day_of_weeks,
             place_name: row.place_name,
             start_time: format_time(row.start_time),
             end_time: format_time(row.end_time),
+            capacity: row.capacity,
+            booked,
+            available: (row.capacity - booked).max(0),
         }
     }
 }
 
 #[derive(sqlx::FromRow)]
-struct DoctorAppointmentRow {
-    appointment_id: i32,
-    patient_id: Uuid,
-    patient_name: String,
-    date: Date,
-    start_time: Time,
-    end_time: Time,
-    status: AppointmentStatus,
+pub(super) struct DoctorAppointmentRow {
+    pub(super) appointment_id: i32,
+    pub(super) patient_id: Uuid,
+    pub(super) patient_name: String,
+    pub(super) date: Date,
+    pub(super) start_time: Time,
+    pub(super) end_time: Time,
+    pub(super) status: AppointmentStatus,
+    pub(super) reschedule_count: i32,
+    pub(super) previous_date: Option<Date>,
+    pub(super) previous_start_time: Option<Time>,
+    pub(super) previous_end_time: Option<Time>,
+    pub(super) chief_complaint: Option<String>,
+    pub(super) notes: Option<String>,
 }
 
 impl From<DoctorAppointmentRow> for DoctorAppointmentView {
@@ -929,17 +1620,404 @@ impl From<DoctorAppointmentRow> for DoctorAppointmentView {
             end_time: format_time(row.end_time),
             status: row.status,
             status_code: status_to_code(row.status),
+            reschedule_count: row.reschedule_count,
+            rescheduled_from: match (
+                row.previous_date,
+                row.previous_start_time,
+                row.previous_end_time,
+            ) {
+                (Some(date), Some(start_time), Some(end_time)) => Some(PreviousSlot {
+                    date: format_date(date),
+                    start_time: format_time(start_time),
+                    end_time: format_time(end_time),
+                }),
+                _ => None,
+            },
+            chief_complaint: row.chief_complaint,
+            notes: row.notes,
+            intake_answers: Vec::new(),
         }
     }
 }
 
-fn parse_date(value: &str) -> AppResult<Date> {
+/// Fill in the intake answers of `views`, which come back from the row
+/// conversion without them.
+pub(super) async fn attach_intake_answers(
+    executor: impl sqlx::PgExecutor<'_>,
+    views: &mut [DoctorAppointmentView],
+) -> AppResult<()> {
+    let ids: Vec<i32> = views.iter().map(|v| v.appointment_id).collect();
+    let rows = sqlx::query!(
+        r#"
+        SELECT appointment_id, question_id, prompt, answer
+        FROM appointment_intake_answers
+        WHERE appointment_id = ANY($1)
+        ORDER BY appointment_id, position
+        "#,
+        &ids
+    )
+    .fetch_all(executor)
+    .await?;
+    for row in rows {
+        if let Some(view) = views
+            .iter_mut()
+            .find(|v| v.appointment_id == row.appointment_id)
+        {
+            view.intake_answers.push(IntakeAnswer {
+                question_id: row.question_id,
+                prompt: row.prompt,
+                answer: row.answer,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct RescheduleRow {
+    from_date: Date,
+    from_start_time: Time,
+    from_end_time: Time,
+    from_place_name: String,
+    from_status: AppointmentStatus,
+    to_date: Date,
+    to_start_time: Time,
+    to_end_time: Time,
+    to_place_name: String,
+    rescheduled_at: OffsetDateTime,
+}
+
+impl From<RescheduleRow> for AppointmentReschedule {
+    fn from(row: RescheduleRow) -> Self {
+        Self {
+            from_date: format_date(row.from_date),
+            from_start_time: format_time(row.from_start_time),
+            from_end_time: format_time(row.from_end_time),
+            from_place_name: row.from_place_name,
+            from_status: row.from_status,
+            to_date: format_date(row.to_date),
+            to_start_time: format_time(row.to_start_time),
+            to_end_time: format_time(row.to_end_time),
+            to_place_name: row.to_place_name,
+            rescheduled_at: row.rescheduled_at,
+        }
+    }
+}
+
+pub(super) const TIMESLOT_OVERLAP_CONSTRAINT: &str = "time_slots_no_overlap";
+
+/// A validated timeslot about to be written.
+pub(super) struct TimeslotInput {
+    pub(super) day_of_weeks: i32,
+    pub(super) place_name: String,
+    pub(super) location_id: i32,
+    pub(super) start_time: Time,
+    pub(super) end_time: Time,
+    /// `None` keeps the current capacity on update and means 1 on insert.
+    pub(super) capacity: Option<i32>,
+    /// The date of a one-off timeslot; `None` for a weekly one.
+    pub(super) valid_on: Option<Date>,
+}
+
+pub(super) fn validate_timeslot(
+    day_of_weeks: i32,
+    location: &Location,
+    start_time: &str,
+    end_time: &str,
+    capacity: Option<i32>,
+) -> AppResult<TimeslotInput> {
+    if capacity.is_some_and(|c| c < 1) {
+        return Err(AppError::BadRequest("capacity must be at least 1".into()));
+    }
+    if !(0..=6).contains(&day_of_weeks) {
+        return Err(AppError::BadRequest(
+            "day_of_weeks must be between 0 and 6".into(),
+        ));
+    }
+    let start_time = parse_time(start_time)?;
+    let end_time = parse_time(end_time)?;
+    if start_time >= end_time {
+        return Err(AppError::BadRequest(
+            "start_time must be before end_time".into(),
+        ));
+    }
+    Ok(TimeslotInput {
+        day_of_weeks,
+        place_name: location.name.clone(),
+        location_id: location.location_id,
+        start_time,
+        end_time,
+        capacity,
+        valid_on: None,
+    })
+}
+
+pub(super) async fn insert_timeslot(
+    tx: &mut PgTx<'_>,
+    doctor_id: Uuid,
+    slot: &TimeslotInput,
+) -> sqlx::Result<DoctorTimeslotRow> {
+    sqlx::query_as!(
+        DoctorTimeslotRow,
+        r#"
+        WITH created AS (
+            INSERT INTO time_slots
+                (doctor_id, day_of_weeks, place_name, location_id, start_time, end_time,
+                 capacity, valid_on)
+            VALUES ($1, $2, $3, $4, $5, $6, COALESCE($7, 1), $8)
+            RETURNING *
+        )
+        SELECT
+            ts.timeslot_id AS "timeslot_id!",
+            ts.day_of_weeks AS "day_of_weeks!",
+            ts.place_name AS "place_name!",
+            ts.start_time AS "start_time!",
+            ts.end_time AS "end_time!",
+            ts.capacity AS "capacity!",
+            ts.valid_on,
+            ts.location_id AS "location_id!",
+            l.building,
+            l.floor,
+            l.room,
+            l.latitude,
+            l.longitude
+        FROM created ts
+        JOIN locations l ON l.location_id = ts.location_id
+        "#,
+        doctor_id,
+        slot.day_of_weeks,
+        slot.place_name,
+        slot.location_id,
+        slot.start_time,
+        slot.end_time,
+        slot.capacity,
+        slot.valid_on
+    )
+    .fetch_one(&mut **tx)
+    .await
+}
+
+/// An upcoming one-off timeslot of the doctor that the weekly `slot` would
+/// overlap. The exclusion constraint only compares weekly timeslots with
+/// each other, so this is the reverse of the check made when adding a
+/// one-off timeslot. `weekly_id` is the weekly timeslot being edited; its
+/// cancelled occurrences do not clash.
+pub(super) async fn one_off_clash(
+    tx: &mut PgTx<'_>,
+    doctor_id: Uuid,
+    slot: &TimeslotInput,
+    weekly_id: Option<i32>,
+    from: Date,
+) -> AppResult<Option<DoctorTimeslotRow>> {
+    let clash = sqlx::query_as!(
+        DoctorTimeslotRow,
+        r#"
+        SELECT
+            ts.timeslot_id,
+            ts.day_of_weeks,
+            ts.place_name,
+            ts.start_time,
+            ts.end_time,
+            ts.capacity,
+            ts.valid_on,
+            ts.location_id,
+            l.building,
+            l.floor,
+            l.room,
+            l.latitude,
+            l.longitude
+        FROM time_slots ts
+        JOIN locations l ON l.location_id = ts.location_id
+        WHERE ts.doctor_id = $1
+          AND ts.valid_on >= $5
+          AND ts.day_of_weeks = $2
+          AND ts.start_time < $4
+          AND ts.end_time > $3
+          AND NOT EXISTS (
+                SELECT 1
+                FROM schedule_exceptions se
+                WHERE se.timeslot_id IN (ts.timeslot_id, $6)
+                  AND se.kind = 'CANCELLED'
+                  AND se.start_date = ts.valid_on
+          )
+        ORDER BY ts.valid_on, ts.start_time
+        LIMIT 1
+        "#,
+        doctor_id,
+        slot.day_of_weeks,
+        slot.start_time,
+        slot.end_time,
+        from,
+        weekly_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(clash)
+}
+
+/// `409` naming the doctor's timeslot that `slot` overlaps, other than
+/// `exclude`. Called after `time_slots_no_overlap` rejected a write, so only
+/// timeslots of the same kind (weekly, or one-off on the same date) count.
+pub(super) async fn timeslot_conflict(
+    pool: &PgPool,
+    doctor_id: Uuid,
+    slot: &TimeslotInput,
+    exclude: Option<i32>,
+) -> AppError {
+    let clash = sqlx::query_as!(
+        DoctorTimeslotRow,
+        r#"
+        SELECT
+            ts.timeslot_id,
+            ts.day_of_weeks,
+            ts.place_name,
+            ts.start_time,
+            ts.end_time,
+            ts.capacity,
+            ts.valid_on,
+            ts.location_id,
+            l.building,
+            l.floor,
+            l.room,
+            l.latitude,
+            l.longitude
+        FROM time_slots ts
+        JOIN locations l ON l.location_id = ts.location_id
+        WHERE ts.doctor_id = $1
+          AND ts.day_of_weeks = $2
+          AND ts.start_time < $4
+          AND ts.end_time > $3
+          AND ($5::int IS NULL OR ts.timeslot_id <> $5)
+          AND ts.valid_on IS NOT DISTINCT FROM $6
+        ORDER BY ts.start_time
+        LIMIT 1
+        "#,
+        doctor_id,
+        slot.day_of_weeks,
+        slot.start_time,
+        slot.end_time,
+        exclude,
+        slot.valid_on
+    )
+    .fetch_optional(pool)
+    .await;
+    match clash {
+        Ok(Some(clash)) => overlap_conflict(&clash),
+        Ok(None) => AppError::Conflict("timeslot overlaps an existing timeslot".into()),
+        Err(err) => err.into(),
+    }
+}
+
+/// `409` naming `clash`, e.g. "timeslot overlaps timeslot 3 (Mon 09:00-12:00
+/// at Room 1)".
+pub(super) fn overlap_conflict(clash: &DoctorTimeslotRow) -> AppError {
+    let day = match clash.valid_on {
+        Some(date) => format_date(date),
+        None => day_name(clash.day_of_weeks).to_owned(),
+    };
+    AppError::Conflict(format!(
+        "timeslot overlaps timeslot {} ({} {}-{} at {})",
+        clash.timeslot_id,
+        day,
+        format_time(clash.start_time),
+        format_time(clash.end_time),
+        clash.place_name
+    ))
+}
+
+/// The doctor's timeslot held on `date` from `start_time` to `end_time`,
+/// preferring a one-off timeslot on that date over a weekly one.
+async fn resolve_slot(
+    pool: &PgPool,
+    doctor_id: Uuid,
+    date: &str,
+    start_time: &str,
+    end_time: &str,
+) -> AppResult<(i32, Date)> {
+    let date = parse_date(date)?;
+    let start_time = parse_time(start_time)?;
+    let end_time = parse_time(end_time)?;
+    if start_time >= end_time {
+        return Err(AppError::BadRequest(
+            "start_time must be before end_time".into(),
+        ));
+    }
+    let timeslot_id = sqlx::query_scalar!(
+        r#"
+        SELECT timeslot_id
+        FROM time_slots
+        WHERE doctor_id = $1
+          AND day_of_weeks = $2
+          AND start_time = $3
+          AND end_time = $4
+          AND (valid_on IS NULL OR valid_on = $5)
+        ORDER BY valid_on NULLS LAST
+        LIMIT 1
+        "#,
+        doctor_id,
+        weekday_to_i32(date.weekday()),
+        start_time,
+        end_time,
+        date
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    Ok((timeslot_id, date))
+}
+
+/// The patient and the doctor of an appointment.
+pub(super) async fn appointment_parties(
+    pool: &PgPool,
+    appointment_id: i32,
+) -> AppResult<Option<(Uuid, Uuid)>> {
+    let Some(access) = sqlx::query(
+        r#"
+        SELECT a.patient_id, ts.doctor_id
+        FROM appointments a
+        JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+        WHERE a.appointment_id = $1
+        "#,
+    )
+    .bind(appointment_id)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+    Ok(Some((
+        access.try_get("patient_id")?,
+        access.try_get("doctor_id")?,
+    )))
+}
+
+/// The patient, the doctor and admins may see an appointment.
+pub(super) async fn ensure_can_view(
+    pool: &PgPool,
+    user: &AuthUser,
+    appointment_id: i32,
+) -> AppResult<()> {
+    let Some((patient_id, doctor_id)) = appointment_parties(pool, appointment_id).await? else {
+        return Err(AppError::NotFound);
+    };
+
+    let allowed = patient_id == user.user_id
+        || (doctor_id == user.user_id && user.has_role(Role::Doctor))
+        || user.has_role(Role::Admin);
+
+    if !allowed {
+        return Err(AppError::Forbidden);
+    }
+    Ok(())
+}
+
+pub(super) fn parse_date(value: &str) -> AppResult<Date> {
     let fmt = format_description!("[year]-[month]-[day]");
     Date::parse(value, &fmt)
         .map_err(|_| AppError::BadRequest("date must be in YYYY-MM-DD format".into()))
 }
 
-fn parse_time(value: &str) -> AppResult<Time> {
+pub(super) fn parse_time(value: &str) -> AppResult<Time> {
     let fmt_hms = format_description!("[hour]:[minute]:[second]");
     let fmt_hm = format_description!("[hour]:[minute]");
     Time::parse(value, &fmt_hms)
@@ -947,17 +2025,17 @@ fn parse_time(value: &str) -> AppResult<Time> {
         .map_err(|_| AppError::BadRequest("time must be in HH:MM or HH:MM:SS format".into()))
 }
 
-fn format_date(date: Date) -> String {
+pub(super) fn format_date(date: Date) -> String {
     let fmt = format_description!("[year]-[month]-[day]");
     date.format(&fmt).expect("valid date format")
 }
 
-fn format_time(time: Time) -> String {
+pub(super) fn format_time(time: Time) -> String {
     let fmt = format_description!("[hour]:[minute]");
     time.format(&fmt).expect("valid time format")
 }
 
-fn weekday_to_i32(weekday: Weekday) -> i32 {
+pub(super) fn weekday_to_i32(weekday: Weekday) -> i32 {
     match weekday {
         Weekday::Sunday => 0,
         Weekday::Monday => 1,
@@ -969,11 +2047,27 @@ fn weekday_to_i32(weekday: Weekday) -> i32 {
     }
 }
 
+fn day_name(day_of_weeks: i32) -> &'static str {
+    match day_of_weeks {
+        0 => "Sun",
+        1 => "Mon",
+        2 => "Tue",
+        3 => "Wed",
+        4 => "Thu",
+        5 => "Fri",
+        6 => "Sat",
+        _ => "?",
+    }
+}
+
 fn status_to_code(status: AppointmentStatus) -> i32 {
     match status {
         AppointmentStatus::ACCEPTED => 1,
         AppointmentStatus::PENDING => 2,
         AppointmentStatus::REJECTED => 3,
         AppointmentStatus::CANCELED => 4,
+        AppointmentStatus::CHECKED_IN => 5,
+        AppointmentStatus::COMPLETED => 6,
+        AppointmentStatus::NO_SHOW => 7,
     }
 }
@@ -0,0 +1,108 @@
+//! Minimal RFC 5545 (iCalendar) writer for appointment events.
+use crate::domain::AppointmentStatus;
+use time::{OffsetDateTime, UtcOffset, macros::format_description};
+
+const PRODID: &str = "-//Therapeia//Appointments//EN";
+/// Content lines longer than this many octets are folded.
+const MAX_LINE_OCTETS: usize = 75;
+
+pub(super) struct CalendarEvent {
+    /// Stays the same for the life of the appointment, so calendar apps
+    /// replace the event when it changes.
+    pub(super) uid: String,
+    /// Bumped on every change to the appointment.
+    pub(super) sequence: i64,
+    pub(super) start: OffsetDateTime,
+    pub(super) end: OffsetDateTime,
+    pub(super) summary: String,
+    pub(super) location: String,
+    pub(super) description: String,
+    pub(super) status: AppointmentStatus,
+    pub(super) last_modified: OffsetDateTime,
+}
+
+/// A VCALENDAR holding `events`, with CRLF line endings.
+pub(super) fn calendar(name: &str, events: &[CalendarEvent]) -> String {
+    let now = OffsetDateTime::now_utc();
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, &format!("PRODID:{PRODID}"));
+    line(&mut out, "CALSCALE:GREGORIAN");
+    line(&mut out, "METHOD:PUBLISH");
+    line(&mut out, &format!("X-WR-CALNAME:{}", escape(name)));
+    for event in events {
+        line(&mut out, "BEGIN:VEVENT");
+        line(&mut out, &format!("UID:{}", event.uid));
+        line(&mut out, &format!("SEQUENCE:{}", event.sequence));
+        line(&mut out, &format!("DTSTAMP:{}", utc(now)));
+        line(
+            &mut out,
+            &format!("LAST-MODIFIED:{}", utc(event.last_modified)),
+        );
+        line(&mut out, &format!("DTSTART:{}", utc(event.start)));
+        line(&mut out, &format!("DTEND:{}", utc(event.end)));
+        line(&mut out, &format!("SUMMARY:{}", escape(&event.summary)));
+        line(&mut out, &format!("LOCATION:{}", escape(&event.location)));
+        line(
+            &mut out,
+            &format!("DESCRIPTION:{}", escape(&event.description)),
+        );
+        line(&mut out, &format!("STATUS:{}", event_status(event.status)));
+        line(&mut out, "END:VEVENT");
+    }
+    line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// iCalendar has no notion of a rejected or missed visit; those show as
+/// cancelled so calendar apps stop treating them as busy time.
+fn event_status(status: AppointmentStatus) -> &'static str {
+    match status {
+        AppointmentStatus::PENDING => "TENTATIVE",
+        AppointmentStatus::ACCEPTED
+        | AppointmentStatus::CHECKED_IN
+        | AppointmentStatus::COMPLETED => "CONFIRMED",
+        AppointmentStatus::REJECTED | AppointmentStatus::CANCELED | AppointmentStatus::NO_SHOW => {
+            "CANCELLED"
+        }
+    }
+}
+
+fn utc(at: OffsetDateTime) -> String {
+    let fmt = format_description!("[year][month][day]T[hour][minute][second]Z");
+    at.to_offset(UtcOffset::UTC)
+        .format(&fmt)
+        .expect("valid datetime format")
+}
+
+/// Escape a TEXT value (RFC 5545 section 3.3.11).
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Append a content line, folded at 75 octets without splitting a UTF-8
+/// character (RFC 5545 section 3.1).
+fn line(out: &mut String, content: &str) {
+    let mut width = 0;
+    for c in content.chars() {
+        if width + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
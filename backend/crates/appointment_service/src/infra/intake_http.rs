@@ -0,0 +1,230 @@
+//! Pre-visit intake questionnaires. Each department has one, maintained by
+//! admins; patients answer the questionnaire of the doctor's department when
+//! booking.
+use super::{http::Ctx, repo_sqlx::IntakeQuestionRow};
+use crate::domain::{IntakeQuestion, IntakeQuestionKind, IntakeQuestionReq};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::get,
+};
+use common::{
+    auth::{Admin, RequireRole, Strict},
+    error::{AppError, AppResult},
+};
+use uuid::Uuid;
+
+const MAX_QUESTIONS: usize = 50;
+const MAX_PROMPT_CHARS: usize = 500;
+
+#[utoipa::path(
+    get,
+    path = "/departments/{department_id}/questionnaire",
+    params(("department_id" = i32, Path)),
+    responses(
+        (status = 200, description = "The department's questions in order", body = [IntakeQuestion]),
+        (status = 404, description = "Department not found"),
+    ),
+    tag = "appointments"
+)]
+pub(super) async fn department_questionnaire(
+    State(ctx): State<Ctx>,
+    Path(department_id): Path<i32>,
+) -> AppResult<Json<Vec<IntakeQuestion>>> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM departments WHERE department_id = $1) AS "exists!""#,
+        department_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    if !exists {
+        return Err(AppError::NotFound);
+    }
+    let rows = sqlx::query_as!(
+        IntakeQuestionRow,
+        r#"
+        SELECT question_id, prompt, kind, options, required
+        FROM intake_questions
+        WHERE department_id = $1
+        ORDER BY position
+        "#,
+        department_id
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    Ok(Json(rows.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/departments/{department_id}/questionnaire",
+    params(("department_id" = i32, Path)),
+    request_body = [IntakeQuestionReq],
+    responses(
+        (status = 200, description = "Questionnaire replaced; questions get new ids", body = [IntakeQuestion]),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Department not found"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn put_department_questionnaire(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(department_id): Path<i32>,
+    Json(req): Json<Vec<IntakeQuestionReq>>,
+) -> AppResult<Json<Vec<IntakeQuestion>>> {
+    let questions = validate_questions(req)?;
+    let mut tx = ctx.pool.begin().await?;
+    // Lock the department so concurrent replacements do not interleave.
+    sqlx::query_scalar!(
+        "SELECT department_id FROM departments WHERE department_id = $1 FOR UPDATE",
+        department_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    // Answers already given keep their copy of the wording.
+    sqlx::query!(
+        "DELETE FROM intake_questions WHERE department_id = $1",
+        department_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let mut created = Vec::with_capacity(questions.len());
+    for (position, question) in questions.into_iter().enumerate() {
+        let row = sqlx::query_as!(
+            IntakeQuestionRow,
+            r#"
+            INSERT INTO intake_questions (department_id, position, prompt, kind, options, required)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING question_id, prompt, kind, options, required
+            "#,
+            department_id,
+            position as i32,
+            question.prompt,
+            question.kind.as_str(),
+            &question.options,
+            question.required
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        created.push(IntakeQuestion::from(row));
+    }
+    tx.commit().await?;
+    Ok(Json(created))
+}
+
+#[utoipa::path(
+    get,
+    path = "/doctor/{doctor_id}/questionnaire",
+    params(("doctor_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The questions to answer when booking the doctor; empty when the doctor has no department", body = [IntakeQuestion]),
+        (status = 404, description = "Doctor not found"),
+    ),
+    tag = "appointments"
+)]
+pub(super) async fn doctor_questionnaire(
+    State(ctx): State<Ctx>,
+    Path(doctor_id): Path<Uuid>,
+) -> AppResult<Json<Vec<IntakeQuestion>>> {
+    let department_id = sqlx::query_scalar!(
+        "SELECT department_id FROM doctor_profile WHERE user_id = $1",
+        doctor_id
+    )
+    .fetch_optional(&ctx.pool)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    let Some(department_id) = department_id else {
+        return Ok(Json(Vec::new()));
+    };
+    let rows = sqlx::query_as!(
+        IntakeQuestionRow,
+        r#"
+        SELECT question_id, prompt, kind, options, required
+        FROM intake_questions
+        WHERE department_id = $1
+        ORDER BY position
+        "#,
+        department_id
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    Ok(Json(rows.into_iter().map(Into::into).collect()))
+}
+
+pub(super) fn routes() -> Router<Ctx> {
+    Router::new()
+        .route(
+            "/appointments/departments/{department_id}/questionnaire",
+            get(department_questionnaire).put(put_department_questionnaire),
+        )
+        .route(
+            "/appointments/doctor/{doctor_id}/questionnaire",
+            get(doctor_questionnaire),
+        )
+}
+
+/// Trim prompts and options. CHOICE questions need at least one option and
+/// no repeats; other kinds take none.
+fn validate_questions(req: Vec<IntakeQuestionReq>) -> AppResult<Vec<IntakeQuestionReq>> {
+    if req.len() > MAX_QUESTIONS {
+        return Err(AppError::BadRequest(format!(
+            "a questionnaire has at most {MAX_QUESTIONS} questions"
+        )));
+    }
+    req.into_iter()
+        .enumerate()
+        .map(|(i, question)| {
+            let prompt = question.prompt.trim().to_owned();
+            if prompt.is_empty() || prompt.chars().count() > MAX_PROMPT_CHARS {
+                return Err(AppError::BadRequest(format!(
+                    "question {}: prompt must be 1 to {MAX_PROMPT_CHARS} characters",
+                    i + 1
+                )));
+            }
+            let mut options: Vec<String> = Vec::with_capacity(question.options.len());
+            for option in &question.options {
+                let option = option.trim();
+                if option.is_empty() {
+                    return Err(AppError::BadRequest(format!(
+                        "question {}: options must not be blank",
+                        i + 1
+                    )));
+                }
+                if options
+                    .iter()
+                    .any(|o| o.to_lowercase() == option.to_lowercase())
+                {
+                    return Err(AppError::BadRequest(format!(
+                        "question {}: option `{option}` is repeated",
+                        i + 1
+                    )));
+                }
+                options.push(option.to_owned());
+            }
+            match (question.kind, options.is_empty()) {
+                (IntakeQuestionKind::Choice, true) => {
+                    return Err(AppError::BadRequest(format!(
+                        "question {}: a CHOICE question needs options",
+                        i + 1
+                    )));
+                }
+                (IntakeQuestionKind::Text | IntakeQuestionKind::YesNo, false) => {
+                    return Err(AppError::BadRequest(format!(
+                        "question {}: only CHOICE questions take options",
+                        i + 1
+                    )));
+                }
+                _ => {}
+            }
+            Ok(IntakeQuestionReq {
+                prompt,
+                kind: question.kind,
+                options,
+                required: question.required,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,90 @@
+//! Background work of the appointment service, started by the API binary.
+use super::{
+    offer_notices::OfferNoticeDispatcher, reminders::ReminderDispatcher,
+    repo_sqlx::SqlxAppointmentRepo,
+};
+use crate::app::AppointmentService;
+use common::{config::AppConfig, error::AppResult, notify::notifier_from_config};
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Start every background job.
+pub fn spawn_jobs(pool: PgPool, cfg: &AppConfig) -> Vec<JoinHandle<()>> {
+    let mut jobs = vec![spawn_waitlist_expiry(pool.clone(), cfg)];
+    if cfg.reminders.offsets_minutes.is_empty() || cfg.reminders.channels.is_empty() {
+        tracing::info!("appointment reminders disabled");
+    } else {
+        jobs.push(spawn_reminders(pool, cfg));
+    }
+    jobs
+}
+
+/// Periodically expire lapsed waitlist offers, pass the held places on to
+/// the next patients waiting, and send the notices of committed offers.
+pub fn spawn_waitlist_expiry(pool: PgPool, cfg: &AppConfig) -> JoinHandle<()> {
+    let svc = AppointmentService::new(
+        SqlxAppointmentRepo::new(pool.clone()),
+        cfg.booking_rules.clone(),
+        cfg.clinic_utc_offset_minutes,
+    );
+    let notices = OfferNoticeDispatcher::new(pool.clone(), notifier_from_config(cfg), cfg);
+    let period = Duration::from_secs(cfg.waitlist_sweep_seconds.max(1));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            match sweep_waitlist(&pool, &svc).await {
+                Ok(0) => {}
+                Ok(offered) => tracing::info!(offered, "passed expired waitlist holds on"),
+                Err(err) => tracing::warn!(error = %err, "waitlist expiry sweep failed"),
+            }
+            match notices.run_once().await {
+                Ok(run) if run.is_idle() => {}
+                Ok(run) => tracing::info!(
+                    skipped = run.skipped,
+                    sent = run.sent,
+                    retrying = run.retrying,
+                    failed = run.failed,
+                    "waitlist offer notices"
+                ),
+                Err(err) => tracing::warn!(error = %err, "waitlist offer notice run failed"),
+            }
+        }
+    })
+}
+
+/// Periodically queue and send appointment reminders through the configured
+/// notifier.
+pub fn spawn_reminders(pool: PgPool, cfg: &AppConfig) -> JoinHandle<()> {
+    let dispatcher = ReminderDispatcher::new(pool, notifier_from_config(cfg), cfg);
+    let period = Duration::from_secs(cfg.reminders.sweep_seconds.max(1));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            match dispatcher.run_once().await {
+                Ok(run) if run.is_idle() => {}
+                Ok(run) => tracing::info!(
+                    queued = run.queued,
+                    skipped = run.skipped,
+                    sent = run.sent,
+                    retrying = run.retrying,
+                    failed = run.failed,
+                    "appointment reminders"
+                ),
+                Err(err) => tracing::warn!(error = %err, "appointment reminder run failed"),
+            }
+        }
+    })
+}
+
+async fn sweep_waitlist(
+    pool: &PgPool,
+    svc: &AppointmentService<SqlxAppointmentRepo>,
+) -> AppResult<usize> {
+    let mut tx = pool.begin().await?;
+    let offers = svc.expire_waitlist_offers(&mut tx).await?;
+    tx.commit().await?;
+    Ok(offers.len())
+}
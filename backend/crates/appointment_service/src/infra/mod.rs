@@ -1,2 +1,13 @@
+pub mod calendar_http;
+pub mod catalogue_http;
+pub mod directory_http;
 pub mod http;
+pub mod ics;
+pub mod intake_http;
+pub mod jobs;
+pub mod offer_notices;
+pub mod queue_http;
+pub mod reminders;
 pub mod repo_sqlx;
+pub mod schedule_http;
+pub mod waitlist_http;
@@ -0,0 +1,174 @@
+//! Delivery of the messages that tell waitlisted patients a place is held
+//! for them. The service queues them in the transaction that makes the
+//! offer; each run here sends the ones that have since been committed.
+//!
+//! Claiming a row pushes its next attempt out by the retry backoff first, as
+//! for reminders, so a crash between sending and recording the result leads
+//! to a retry rather than a lost message.
+use common::{
+    config::AppConfig,
+    error::AppResult,
+    notify::{Channel, Notification, Notifier},
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Notices claimed per run.
+const BATCH_SIZE: i64 = 100;
+/// Retry delays stop doubling after this many attempts.
+const MAX_BACKOFF_DOUBLINGS: i32 = 10;
+
+/// Sends queued offer notices, retrying failures with the reminder settings.
+pub struct OfferNoticeDispatcher {
+    pool: PgPool,
+    notifier: Arc<dyn Notifier>,
+    max_attempts: i32,
+    retry_base_seconds: i64,
+}
+
+/// What one run did.
+#[derive(Debug, Default)]
+pub struct OfferNoticeRun {
+    pub skipped: u64,
+    pub sent: usize,
+    pub retrying: usize,
+    pub failed: usize,
+}
+
+impl OfferNoticeRun {
+    pub fn is_idle(&self) -> bool {
+        self.skipped == 0 && self.sent == 0 && self.retrying == 0 && self.failed == 0
+    }
+}
+
+impl OfferNoticeDispatcher {
+    pub fn new(pool: PgPool, notifier: Arc<dyn Notifier>, cfg: &AppConfig) -> Self {
+        Self {
+            pool,
+            notifier,
+            max_attempts: cfg.reminders.max_attempts,
+            retry_base_seconds: cfg.reminders.retry_base_seconds,
+        }
+    }
+
+    pub async fn run_once(&self) -> AppResult<OfferNoticeRun> {
+        let mut run = OfferNoticeRun {
+            skipped: self.skip_stale().await?,
+            ..OfferNoticeRun::default()
+        };
+        for notice in self.claim_due().await? {
+            let notification = notice.notification();
+            match self.notifier.send(&notification).await {
+                Ok(()) => {
+                    sqlx::query!(
+                        r#"
+                        UPDATE waitlist_offer_notices
+                        SET status = 'SENT', sent_at = now(), last_error = NULL
+                        WHERE notice_id = $1
+                        "#,
+                        notice.notice_id
+                    )
+                    .execute(&self.pool)
+                    .await?;
+                    run.sent += 1;
+                }
+                Err(err) => {
+                    let gave_up = notice.attempts >= self.max_attempts;
+                    sqlx::query!(
+                        r#"
+                        UPDATE waitlist_offer_notices
+                        SET status = CASE WHEN $2 THEN 'FAILED' ELSE status END,
+                            last_error = $3
+                        WHERE notice_id = $1
+                        "#,
+                        notice.notice_id,
+                        gave_up,
+                        err.to_string()
+                    )
+                    .execute(&self.pool)
+                    .await?;
+                    tracing::warn!(
+                        notice_id = notice.notice_id,
+                        attempts = notice.attempts,
+                        error = %err,
+                        "waitlist offer notice not delivered"
+                    );
+                    if gave_up {
+                        run.failed += 1;
+                    } else {
+                        run.retrying += 1;
+                    }
+                }
+            }
+        }
+        Ok(run)
+    }
+
+    /// Skip pending notices whose hold has ended: confirmed, left or lapsed.
+    async fn skip_stale(&self) -> AppResult<u64> {
+        let rows = sqlx::query!(
+            r#"
+            UPDATE waitlist_offer_notices n
+            SET status = 'SKIPPED'
+            FROM waitlist_entries w
+            WHERE n.status = 'PENDING'
+              AND w.entry_id = n.entry_id
+              AND (w.status <> 'OFFERED' OR w.offer_expires_at <= now())
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(rows.rows_affected())
+    }
+
+    /// Take a batch of due notices, counting the attempt and moving their
+    /// next attempt out by the backoff before anything is sent.
+    async fn claim_due(&self) -> AppResult<Vec<DueNotice>> {
+        let rows = sqlx::query_as!(
+            DueNotice,
+            r#"
+            UPDATE waitlist_offer_notices n
+            SET attempts = n.attempts + 1,
+                next_attempt_at = now()
+                    + make_interval(secs => $2 * power(2, LEAST(n.attempts, $3)))
+            WHERE n.notice_id IN (
+                    SELECT notice_id
+                    FROM waitlist_offer_notices
+                    WHERE status = 'PENDING'
+                      AND next_attempt_at <= now()
+                    ORDER BY next_attempt_at
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                  )
+            RETURNING n.notice_id, n.channel, n.recipient, n.subject, n.body, n.attempts
+            "#,
+            BATCH_SIZE,
+            self.retry_base_seconds as f64,
+            MAX_BACKOFF_DOUBLINGS
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+}
+
+struct DueNotice {
+    notice_id: i64,
+    channel: String,
+    recipient: String,
+    subject: String,
+    body: String,
+    /// Including the one about to be made.
+    attempts: i32,
+}
+
+impl DueNotice {
+    fn notification(&self) -> Notification {
+        Notification {
+            channel: Channel::parse(&self.channel).unwrap_or(Channel::Email),
+            to: self.recipient.clone(),
+            subject: self.subject.clone(),
+            body: self.body.clone(),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! The day of the visit: check-in, the per-doctor queue and calling the next
+//! patient in. Waits are estimated from each visit's share of its timeslot
+//! and how long the patient with the doctor has been in.
+use super::http::{Ctx, appointment_parties, ensure_can_view, format_date, format_time};
+use crate::domain::{AppointmentStatus, QueueEntry, QueuePosition, StatusChange, VisitQueue};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post},
+};
+use common::{
+    auth::{AuthUser, Doctor, RequireRole, Role},
+    error::{AppError, AppResult},
+};
+use sqlx::PgPool;
+use time::{Date, Duration, OffsetDateTime, Time};
+use uuid::Uuid;
+
+#[utoipa::path(
+    post,
+    path = "/{appointment_id}/check-in",
+    params(("appointment_id" = i32, Path)),
+    responses(
+        (status = 200, description = "Checked in; the queue number and estimated wait", body = QueuePosition),
+        (status = 403, description = "Not the patient, the doctor or an admin"),
+        (status = 404, description = "Appointment not found"),
+        (status = 409, description = "Not ACCEPTED or not the appointment date"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn check_in(
+    user: AuthUser,
+    State(ctx): State<Ctx>,
+    Path(appointment_id): Path<i32>,
+) -> AppResult<Json<QueuePosition>> {
+    ensure_can_view(&ctx.pool, &user, appointment_id).await?;
+    let mut tx = ctx.pool.begin().await?;
+    ctx.svc
+        .change_status(
+            &mut tx,
+            StatusChange {
+                appointment_id,
+                to: AppointmentStatus::CHECKED_IN,
+                actor_id: user.user_id,
+                note: None,
+            },
+        )
+        .await?;
+    tx.commit().await?;
+    queue_position(&ctx, appointment_id).await.map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "/{appointment_id}/queue",
+    params(("appointment_id" = i32, Path)),
+    responses(
+        (status = 200, description = "The appointment's place in the queue", body = QueuePosition),
+        (status = 403, description = "Not the patient, the doctor or an admin"),
+        (status = 404, description = "Appointment not found"),
+        (status = 409, description = "The patient has not checked in"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn appointment_queue_position(
+    user: AuthUser,
+    State(ctx): State<Ctx>,
+    Path(appointment_id): Path<i32>,
+) -> AppResult<Json<QueuePosition>> {
+    ensure_can_view(&ctx.pool, &user, appointment_id).await?;
+    queue_position(&ctx, appointment_id).await.map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "/doctor/{doctor_id}/queue",
+    params(("doctor_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Today's queue with estimated waits", body = VisitQueue),
+        (status = 403, description = "Not the doctor or an admin"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn doctor_queue(
+    user: AuthUser,
+    State(ctx): State<Ctx>,
+    Path(doctor_id): Path<Uuid>,
+) -> AppResult<Json<VisitQueue>> {
+    let allowed =
+        (user.user_id == doctor_id && user.has_role(Role::Doctor)) || user.has_role(Role::Admin);
+    if !allowed {
+        return Err(AppError::Forbidden);
+    }
+    let queue = load_queue(
+        &ctx.pool,
+        doctor_id,
+        ctx.svc.today(),
+        OffsetDateTime::now_utc(),
+    )
+    .await?;
+    Ok(Json(queue))
+}
+
+#[utoipa::path(
+    post,
+    path = "/queue/call-next",
+    responses(
+        (status = 200, description = "The visit in progress is completed and the next patient called in; `now_serving` is null when no one was waiting", body = VisitQueue),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn call_next(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+) -> AppResult<Json<VisitQueue>> {
+    let mut tx = ctx.pool.begin().await?;
+    ctx.svc.call_next(&mut tx, user_id, user_id).await?;
+    tx.commit().await?;
+    let queue = load_queue(
+        &ctx.pool,
+        user_id,
+        ctx.svc.today(),
+        OffsetDateTime::now_utc(),
+    )
+    .await?;
+    Ok(Json(queue))
+}
+
+pub(super) fn routes() -> Router<Ctx> {
+    Router::new()
+        .route("/appointments/{appointment_id}/check-in", post(check_in))
+        .route(
+            "/appointments/{appointment_id}/queue",
+            get(appointment_queue_position),
+        )
+        .route("/appointments/doctor/{doctor_id}/queue", get(doctor_queue))
+        .route("/appointments/queue/call-next", post(call_next))
+}
+
+async fn queue_position(ctx: &Ctx, appointment_id: i32) -> AppResult<QueuePosition> {
+    let Some((_, doctor_id)) = appointment_parties(&ctx.pool, appointment_id).await? else {
+        return Err(AppError::NotFound);
+    };
+    let date = sqlx::query_scalar!(
+        "SELECT date FROM appointments WHERE appointment_id = $1",
+        appointment_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    let queue = load_queue(&ctx.pool, doctor_id, date, OffsetDateTime::now_utc()).await?;
+    let Some(entry) = queue
+        .entries
+        .iter()
+        .find(|e| e.appointment_id == appointment_id)
+    else {
+        return Err(AppError::Conflict(
+            "the patient has not checked in for this appointment".into(),
+        ));
+    };
+    let ahead = if is_waiting(entry) {
+        queue
+            .entries
+            .iter()
+            .filter(|e| is_waiting(e) && e.queue_number < entry.queue_number)
+            .count() as i64
+    } else {
+        0
+    };
+    Ok(QueuePosition {
+        appointment_id,
+        queue_number: entry.queue_number,
+        now_serving: queue.now_serving,
+        ahead,
+        estimated_wait_minutes: entry.estimated_wait_minutes,
+        status: entry.status,
+    })
+}
+
+/// The doctor's queue on `date` as of `now`.
+async fn load_queue(
+    pool: &PgPool,
+    doctor_id: Uuid,
+    date: Date,
+    now: OffsetDateTime,
+) -> AppResult<VisitQueue> {
+    let rows = sqlx::query_as!(
+        QueueRow,
+        r#"
+        SELECT
+            a.appointment_id,
+            a.queue_number AS "queue_number!",
+            a.patient_id,
+            concat_ws(' ', up.first_name, up.last_name) AS "patient_name!",
+            a.status AS "status: _",
+            ts.start_time,
+            ts.end_time,
+            ts.capacity,
+            a.checked_in_at AS "checked_in_at!",
+            a.called_at
+        FROM appointments a
+        JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+        JOIN users up ON up.user_id = a.patient_id
+        WHERE ts.doctor_id = $1
+          AND a.date = $2
+          AND a.queue_number IS NOT NULL
+        ORDER BY a.queue_number
+        "#,
+        doctor_id,
+        date
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // The patient with the doctor still needs the rest of their visit; each
+    // patient waiting ahead then needs a full one.
+    let serving = rows
+        .iter()
+        .filter(|row| row.status == AppointmentStatus::CHECKED_IN)
+        .filter_map(|row| row.called_at.map(|called_at| (row, called_at)))
+        .max_by_key(|(_, called_at)| *called_at);
+    let mut clock = serving
+        .map(|(row, called_at)| (row.visit_length() - (now - called_at)).max(Duration::ZERO))
+        .unwrap_or(Duration::ZERO);
+    let now_serving = serving.map(|(row, _)| row.queue_number);
+
+    let mut waiting = 0;
+    let entries = rows
+        .into_iter()
+        .map(|row| {
+            let estimated_wait_minutes = (row.status == AppointmentStatus::CHECKED_IN
+                && row.called_at.is_none())
+            .then(|| {
+                let wait = clock;
+                clock += row.visit_length();
+                waiting += 1;
+                // Round up: a patient due in 30 seconds is due in a minute.
+                (wait.whole_seconds() + 59) / 60
+            });
+            QueueEntry {
+                appointment_id: row.appointment_id,
+                queue_number: row.queue_number,
+                patient_id: row.patient_id,
+                patient_name: row.patient_name,
+                status: row.status,
+                start_time: format_time(row.start_time),
+                end_time: format_time(row.end_time),
+                checked_in_at: row.checked_in_at,
+                called_at: row.called_at,
+                estimated_wait_minutes,
+            }
+        })
+        .collect();
+    Ok(VisitQueue {
+        doctor_id,
+        date: format_date(date),
+        now_serving,
+        waiting,
+        entries,
+    })
+}
+
+fn is_waiting(entry: &QueueEntry) -> bool {
+    entry.status == AppointmentStatus::CHECKED_IN && entry.called_at.is_none()
+}
+
+struct QueueRow {
+    appointment_id: i32,
+    queue_number: i32,
+    patient_id: Uuid,
+    patient_name: String,
+    status: AppointmentStatus,
+    start_time: Time,
+    end_time: Time,
+    capacity: i32,
+    checked_in_at: OffsetDateTime,
+    called_at: Option<OffsetDateTime>,
+}
+
+impl QueueRow {
+    /// The timeslot shared evenly between its places.
+    fn visit_length(&self) -> Duration {
+        (self.end_time - self.start_time) / self.capacity.max(1)
+    }
+}
@@ -0,0 +1,259 @@
+//! Reminders of accepted appointments. Each run queues the reminders that
+//! have come due, then delivers pending ones through the notifier.
+//!
+//! Queueing is idempotent: a delivery row is unique per appointment, visit
+//! start, offset and channel. Claiming a row pushes its next attempt out by
+//! the retry backoff first, so a crash between sending and recording the
+//! result leads to a retry rather than a lost reminder.
+use super::http::{format_date, format_time};
+use common::{
+    config::{AppConfig, ReminderConfig},
+    error::AppResult,
+    notify::{Channel, Notification, Notifier},
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use time::{Date, Time};
+
+/// Deliveries claimed per run.
+const BATCH_SIZE: i64 = 100;
+/// Retry delays stop doubling after this many attempts.
+const MAX_BACKOFF_DOUBLINGS: i32 = 10;
+
+pub struct ReminderDispatcher {
+    pool: PgPool,
+    notifier: Arc<dyn Notifier>,
+    cfg: ReminderConfig,
+    clinic_utc_offset_minutes: i32,
+}
+
+/// What one run did.
+#[derive(Debug, Default)]
+pub struct ReminderRun {
+    pub queued: u64,
+    pub skipped: u64,
+    pub sent: usize,
+    pub retrying: usize,
+    pub failed: usize,
+}
+
+impl ReminderRun {
+    pub fn is_idle(&self) -> bool {
+        self.queued == 0
+            && self.skipped == 0
+            && self.sent == 0
+            && self.retrying == 0
+            && self.failed == 0
+    }
+}
+
+impl ReminderDispatcher {
+    pub fn new(pool: PgPool, notifier: Arc<dyn Notifier>, cfg: &AppConfig) -> Self {
+        Self {
+            pool,
+            notifier,
+            cfg: cfg.reminders.clone(),
+            clinic_utc_offset_minutes: cfg.clinic_utc_offset_minutes,
+        }
+    }
+
+    pub async fn run_once(&self) -> AppResult<ReminderRun> {
+        let mut run = ReminderRun {
+            queued: self.queue_due().await?,
+            skipped: self.skip_stale().await?,
+            ..ReminderRun::default()
+        };
+        for delivery in self.claim_due().await? {
+            let notification = delivery.notification();
+            match self.notifier.send(&notification).await {
+                Ok(()) => {
+                    sqlx::query!(
+                        r#"
+                        UPDATE reminder_deliveries
+                        SET status = 'SENT', sent_at = now(), last_error = NULL
+                        WHERE delivery_id = $1
+                        "#,
+                        delivery.delivery_id
+                    )
+                    .execute(&self.pool)
+                    .await?;
+                    run.sent += 1;
+                }
+                Err(err) => {
+                    let gave_up = delivery.attempts >= self.cfg.max_attempts;
+                    sqlx::query!(
+                        r#"
+                        UPDATE reminder_deliveries
+                        SET status = CASE WHEN $2 THEN 'FAILED' ELSE status END,
+                            last_error = $3
+                        WHERE delivery_id = $1
+                        "#,
+                        delivery.delivery_id,
+                        gave_up,
+                        err.to_string()
+                    )
+                    .execute(&self.pool)
+                    .await?;
+                    tracing::warn!(
+                        delivery_id = delivery.delivery_id,
+                        attempts = delivery.attempts,
+                        error = %err,
+                        "appointment reminder not delivered"
+                    );
+                    if gave_up {
+                        run.failed += 1;
+                    } else {
+                        run.retrying += 1;
+                    }
+                }
+            }
+        }
+        Ok(run)
+    }
+
+    /// Queue the latest reminder that has come due for every upcoming
+    /// accepted appointment. Earlier offsets that were missed, e.g. because
+    /// the appointment was accepted late, are not sent.
+    async fn queue_due(&self) -> AppResult<u64> {
+        let Some(max_offset) = self.cfg.offsets_minutes.iter().max() else {
+            return Ok(0);
+        };
+        let channels: Vec<String> = self
+            .cfg
+            .channels
+            .iter()
+            .map(|channel| channel.as_str().to_owned())
+            .collect();
+        let horizon_days = max_offset / (24 * 60) + 2;
+        let rows = sqlx::query!(
+            r#"
+            INSERT INTO reminder_deliveries
+                (appointment_id, visit_start, offset_minutes, channel, recipient)
+            SELECT a.appointment_id, v.visit_start, due.offset_minutes, c.channel, r.recipient
+            FROM appointments a
+            JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+            JOIN users u ON u.user_id = a.patient_id
+            CROSS JOIN LATERAL (
+                SELECT ((a.date + ts.start_time) - make_interval(mins => $1)) AT TIME ZONE 'UTC'
+                       AS visit_start
+            ) v
+            CROSS JOIN LATERAL (
+                SELECT MIN(o.minutes) AS offset_minutes
+                FROM unnest($2::int[]) AS o(minutes)
+                WHERE v.visit_start - make_interval(mins => o.minutes) <= now()
+            ) due
+            CROSS JOIN unnest($3::text[]) AS c(channel)
+            CROSS JOIN LATERAL (
+                SELECT CASE c.channel
+                           WHEN 'email' THEN u.email
+                           WHEN 'sms' THEN u.phone
+                           ELSE u.user_id::text
+                       END AS recipient
+            ) r
+            WHERE a.status = 'ACCEPTED'
+              AND a.date BETWEEN current_date - 1 AND current_date + $4::int
+              AND v.visit_start > now()
+              AND due.offset_minutes IS NOT NULL
+              AND r.recipient IS NOT NULL
+            ON CONFLICT ON CONSTRAINT uniq_reminder_delivery DO NOTHING
+            "#,
+            self.clinic_utc_offset_minutes,
+            &self.cfg.offsets_minutes,
+            &channels,
+            horizon_days
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(rows.rows_affected())
+    }
+
+    /// Skip pending reminders of appointments that are no longer ACCEPTED,
+    /// have moved, or have already started.
+    async fn skip_stale(&self) -> AppResult<u64> {
+        let rows = sqlx::query!(
+            r#"
+            UPDATE reminder_deliveries d
+            SET status = 'SKIPPED'
+            FROM appointments a
+            JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+            WHERE d.status = 'PENDING'
+              AND a.appointment_id = d.appointment_id
+              AND (
+                    a.status <> 'ACCEPTED'
+                 OR ((a.date + ts.start_time) - make_interval(mins => $1)) AT TIME ZONE 'UTC'
+                    <> d.visit_start
+                 OR d.visit_start <= now()
+              )
+            "#,
+            self.clinic_utc_offset_minutes
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(rows.rows_affected())
+    }
+
+    /// Take a batch of due deliveries, counting the attempt and moving their
+    /// next attempt out by the backoff before anything is sent.
+    async fn claim_due(&self) -> AppResult<Vec<DueReminder>> {
+        let rows = sqlx::query_as!(
+            DueReminder,
+            r#"
+            UPDATE reminder_deliveries d
+            SET attempts = d.attempts + 1,
+                next_attempt_at = now()
+                    + make_interval(secs => $2 * power(2, LEAST(d.attempts, $3)))
+            FROM appointments a
+            JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+            JOIN users ud ON ud.user_id = ts.doctor_id
+            WHERE d.delivery_id IN (
+                    SELECT delivery_id
+                    FROM reminder_deliveries
+                    WHERE status = 'PENDING'
+                      AND next_attempt_at <= now()
+                    ORDER BY next_attempt_at
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                  )
+              AND a.appointment_id = d.appointment_id
+            RETURNING d.delivery_id, d.channel, d.recipient, d.attempts,
+                      concat_ws(' ', ud.first_name, ud.last_name) AS "doctor_name!",
+                      ts.place_name, a.date, ts.start_time
+            "#,
+            BATCH_SIZE,
+            self.cfg.retry_base_seconds as f64,
+            MAX_BACKOFF_DOUBLINGS
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+}
+
+struct DueReminder {
+    delivery_id: i64,
+    channel: String,
+    recipient: String,
+    /// Including the one about to be made.
+    attempts: i32,
+    doctor_name: String,
+    place_name: String,
+    date: Date,
+    start_time: Time,
+}
+
+impl DueReminder {
+    fn notification(&self) -> Notification {
+        Notification {
+            channel: Channel::parse(&self.channel).unwrap_or(Channel::Email),
+            to: self.recipient.clone(),
+            subject: "Appointment reminder".into(),
+            body: format!(
+                "Reminder: you have an appointment with {} on {} at {} ({}).",
+                self.doctor_name,
+                format_date(self.date),
+                format_time(self.start_time),
+                self.place_name
+            ),
+        }
+    }
+}
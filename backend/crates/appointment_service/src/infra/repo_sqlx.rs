@@ -1,8 +1,13 @@
 use super::super::app::AppointmentRepo;
 use super::super::domain::*;
-use common::error::{AppError, AppResult};
+use common::{
+    error::{AppError, AppResult, violates_constraint},
+    notify::Notification,
+};
 use db::PgTx;
 use sqlx::PgPool;
+use time::{Date, OffsetDateTime, PrimitiveDateTime};
+use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct SqlxAppointmentRepo {
@@ -34,7 +39,220 @@ impl AppointmentRepo for SqlxAppointmentRepo {
             cmd.date
         )
         .fetch_one(&mut **tx)
+        .await
+        .map_err(duplicate_booking)?;
+        log_transition(
+            tx,
+            rec.appointment_id,
+            None,
+            rec.status,
+            Some(cmd.patient_id),
+            None,
+        )
+        .await?;
+        Ok(rec)
+    }
+
+    async fn lock_timeslot(
+        &self,
+        tx: &mut PgTx<'_>,
+        timeslot_id: i32,
+    ) -> AppResult<Option<LockedTimeslot>> {
+        let slot = sqlx::query_as!(
+            LockedTimeslot,
+            r#"
+            SELECT doctor_id, capacity, start_time
+            FROM time_slots
+            WHERE timeslot_id = $1
+            FOR UPDATE
+            "#,
+            timeslot_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        Ok(slot)
+    }
+
+    async fn schedule_block(
+        &self,
+        tx: &mut PgTx<'_>,
+        timeslot_id: i32,
+        date: Date,
+    ) -> AppResult<Option<String>> {
+        // Whether the timeslot is held comes from `timeslot_openings`, as for
+        // availability and the directory; the ranked reasons only say why not.
+        let reason = sqlx::query_scalar!(
+            r#"
+            SELECT reason AS "reason!"
+            FROM (
+                SELECT 1 AS rank, 'the clinic is closed on this date (' || h.name || ')' AS reason
+                FROM clinic_holidays h
+                WHERE h.holiday_date = $2
+                UNION ALL
+                SELECT 2, 'the doctor is on leave on this date'
+                FROM time_slots ts
+                JOIN schedule_exceptions se
+                  ON se.doctor_id = ts.doctor_id
+                 AND se.kind = 'LEAVE'
+                 AND $2 BETWEEN se.start_date AND se.end_date
+                WHERE ts.timeslot_id = $1
+                UNION ALL
+                SELECT 3, 'the timeslot is cancelled on this date'
+                FROM schedule_exceptions se
+                WHERE se.timeslot_id = $1
+                  AND se.kind = 'CANCELLED'
+                  AND se.start_date = $2
+                UNION ALL
+                SELECT 4, 'the timeslot is not offered on this date'
+            ) blocks
+            WHERE NOT EXISTS (
+                    SELECT 1 FROM timeslot_openings($2, $2) o WHERE o.timeslot_id = $1
+                  )
+            ORDER BY rank
+            LIMIT 1
+            "#,
+            timeslot_id,
+            date
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        Ok(reason)
+    }
+
+    async fn booked_count(
+        &self,
+        tx: &mut PgTx<'_>,
+        timeslot_id: i32,
+        date: Date,
+    ) -> AppResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT timeslot_places_taken($1, $2) AS "count!""#,
+            timeslot_id,
+            date
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        Ok(count)
+    }
+
+    async fn pending_count(
+        &self,
+        tx: &mut PgTx<'_>,
+        patient_id: Uuid,
+        from: Date,
+    ) -> AppResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            WITH patient AS (
+                SELECT user_id FROM users WHERE user_id = $1 FOR NO KEY UPDATE
+            )
+            SELECT COUNT(a.appointment_id) AS "count!"
+            FROM patient p
+            LEFT JOIN appointments a
+              ON a.patient_id = p.user_id
+             AND a.status = 'PENDING'
+             AND a.date >= $2
+            "#,
+            patient_id,
+            from
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        Ok(count)
+    }
+
+    async fn lock_appointment(&self, tx: &mut PgTx<'_>, id: i32) -> AppResult<Option<Appointment>> {
+        let rec = sqlx::query_as!(
+            Appointment,
+            r#"
+            SELECT appointment_id, patient_id, timeslot_id, date,
+                   status as "status: _", created_at
+            FROM appointments WHERE appointment_id = $1
+            FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        Ok(rec)
+    }
+
+    async fn keeps_accepted_on_reschedule(
+        &self,
+        tx: &mut PgTx<'_>,
+        timeslot_id: i32,
+    ) -> AppResult<bool> {
+        let keep = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(p.keep_accepted_on_reschedule, false) AS "keep!"
+            FROM time_slots ts
+            LEFT JOIN doctor_booking_policies p ON p.doctor_id = ts.doctor_id
+            WHERE ts.timeslot_id = $1
+            "#,
+            timeslot_id
+        )
+        .fetch_optional(&mut **tx)
         .await?;
+        Ok(keep.unwrap_or(false))
+    }
+
+    async fn move_appointment(
+        &self,
+        tx: &mut PgTx<'_>,
+        cmd: MoveAppointment,
+    ) -> AppResult<Appointment> {
+        let from_status = sqlx::query_scalar!(
+            r#"
+            INSERT INTO appointment_reschedules
+                (appointment_id, from_timeslot_id, from_date, from_status,
+                 to_timeslot_id, to_date, rescheduled_by)
+            SELECT appointment_id, timeslot_id, date, status, $2, $3, $4
+            FROM appointments
+            WHERE appointment_id = $1
+            RETURNING from_status AS "from_status: AppointmentStatus"
+            "#,
+            cmd.appointment_id,
+            cmd.timeslot_id,
+            cmd.date,
+            cmd.moved_by
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(AppError::NotFound)?;
+        let rec = sqlx::query_as!(
+            Appointment,
+            r#"
+            UPDATE appointments
+            SET timeslot_id = $2, date = $3, status = $4
+            WHERE appointment_id = $1
+            RETURNING
+              appointment_id,
+              patient_id,
+              timeslot_id,
+              date,
+              status as "status: _",
+              created_at
+            "#,
+            cmd.appointment_id,
+            cmd.timeslot_id,
+            cmd.date,
+            cmd.status as AppointmentStatus
+        )
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(duplicate_booking)?
+        .ok_or(AppError::NotFound)?;
+        if from_status != rec.status {
+            log_transition(
+                tx,
+                rec.appointment_id,
+                Some(from_status),
+                rec.status,
+                Some(cmd.moved_by),
+                Some("rescheduled"),
+            )
+            .await?;
+        }
         Ok(rec)
     }
 
@@ -53,16 +271,520 @@ impl AppointmentRepo for SqlxAppointmentRepo {
         Ok(rec)
     }
 
-    async fn accept(&self, tx: &mut PgTx<'_>, id: i32) -> AppResult<()> {
+    async fn set_status(
+        &self,
+        tx: &mut PgTx<'_>,
+        from: AppointmentStatus,
+        cmd: StatusChange,
+    ) -> AppResult<Appointment> {
+        let rec = sqlx::query_as!(
+            Appointment,
+            r#"
+            UPDATE appointments
+            SET status = $2
+            WHERE appointment_id = $1
+            RETURNING
+              appointment_id,
+              patient_id,
+              timeslot_id,
+              date,
+              status as "status: _",
+              created_at
+            "#,
+            cmd.appointment_id,
+            cmd.to as AppointmentStatus
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(AppError::NotFound)?;
+        log_transition(
+            tx,
+            rec.appointment_id,
+            Some(from),
+            rec.status,
+            Some(cmd.actor_id),
+            cmd.note.as_deref(),
+        )
+        .await?;
+        Ok(rec)
+    }
+
+    async fn free_places(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+        date: Date,
+        earliest: PrimitiveDateTime,
+    ) -> AppResult<Option<i64>> {
+        let rec = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "slots!", COALESCE(SUM(free), 0)::bigint AS "free!"
+            FROM timeslot_openings($2, $2)
+            WHERE doctor_id = $1
+              AND date + start_time >= $3
+            "#,
+            doctor_id,
+            date,
+            earliest
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        Ok((rec.slots > 0).then_some(rec.free))
+    }
+
+    async fn join_waitlist(
+        &self,
+        tx: &mut PgTx<'_>,
+        patient_id: Uuid,
+        doctor_id: Uuid,
+        date: Date,
+    ) -> AppResult<WaitlistEntry> {
+        let row = sqlx::query_as!(
+            WaitlistEntryRow,
+            r#"
+            INSERT INTO waitlist_entries (patient_id, doctor_id, date)
+            VALUES ($1, $2, $3)
+            RETURNING entry_id, patient_id, doctor_id, date,
+                      status AS "status: WaitlistStatus",
+                      offered_timeslot_id, offer_expires_at
+            "#,
+            patient_id,
+            doctor_id,
+            date
+        )
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(|err| {
+            if violates_constraint(&err, "uniq_waitlist_active_entry") {
+                BookingError::AlreadyWaitlisted.into()
+            } else {
+                AppError::from(err)
+            }
+        })?;
+        Ok(row.into())
+    }
+
+    async fn lock_waitlist_entry(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+    ) -> AppResult<Option<WaitlistEntry>> {
+        let row = sqlx::query_as!(
+            WaitlistEntryRow,
+            r#"
+            SELECT entry_id, patient_id, doctor_id, date,
+                   status AS "status: WaitlistStatus",
+                   offered_timeslot_id, offer_expires_at
+            FROM waitlist_entries
+            WHERE entry_id = $1
+            FOR UPDATE
+            "#,
+            entry_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        Ok(row.map(WaitlistEntry::from))
+    }
+
+    async fn close_waitlist_entry(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+        status: WaitlistStatus,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE waitlist_entries
+            SET status = $2, updated_at = now()
+            WHERE entry_id = $1
+            "#,
+            entry_id,
+            status.as_str()
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn attach_waitlist_appointment(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+        appointment_id: i32,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            "UPDATE waitlist_entries SET appointment_id = $2 WHERE entry_id = $1",
+            entry_id,
+            appointment_id
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn offer_next(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+        timeslot_id: i32,
+        date: Date,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<Option<WaitlistOffer>> {
+        // Patients already holding this timeslot on the date are passed over.
+        let offer = sqlx::query_as!(
+            WaitlistOffer,
+            r#"
+            UPDATE waitlist_entries
+            SET status = 'OFFERED',
+                offered_timeslot_id = $2,
+                offer_expires_at = $4,
+                updated_at = now()
+            WHERE entry_id = (
+                SELECT w.entry_id
+                FROM waitlist_entries w
+                WHERE w.doctor_id = $1
+                  AND w.date = $3
+                  AND w.status = 'WAITING'
+                  AND NOT EXISTS (
+                        SELECT 1
+                        FROM appointments a
+                        WHERE a.patient_id = w.patient_id
+                          AND a.timeslot_id = $2
+                          AND a.date = $3
+                          AND a.status NOT IN ('REJECTED', 'CANCELED')
+                      )
+                ORDER BY w.created_at, w.entry_id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING entry_id, doctor_id, offered_timeslot_id AS "timeslot_id!",
+                      date, offer_expires_at AS "expires_at!"
+            "#,
+            doctor_id,
+            timeslot_id,
+            date,
+            expires_at
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        Ok(offer)
+    }
+
+    async fn offer_contact(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+    ) -> AppResult<Option<OfferContact>> {
+        let contact = sqlx::query_as!(
+            OfferContact,
+            r#"
+            SELECT up.email, up.phone,
+                   concat_ws(' ', ud.first_name, ud.last_name) AS "doctor_name!",
+                   ts.place_name, ts.start_time
+            FROM waitlist_entries w
+            JOIN time_slots ts ON ts.timeslot_id = w.offered_timeslot_id
+            JOIN users up ON up.user_id = w.patient_id
+            JOIN users ud ON ud.user_id = w.doctor_id
+            WHERE w.entry_id = $1
+            "#,
+            entry_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        Ok(contact)
+    }
+
+    async fn queue_offer_notice(
+        &self,
+        tx: &mut PgTx<'_>,
+        entry_id: i32,
+        notification: &Notification,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO waitlist_offer_notices (entry_id, channel, recipient, subject, body)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            entry_id,
+            notification.channel.as_str(),
+            notification.to,
+            notification.subject,
+            notification.body
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn expire_waitlist(
+        &self,
+        tx: &mut PgTx<'_>,
+        today: Date,
+    ) -> AppResult<Vec<WaitlistOffer>> {
         let rows = sqlx::query!(
-            r#"UPDATE appointments SET status = 'ACCEPTED' WHERE appointment_id = $1"#,
-            id
+            r#"
+            UPDATE waitlist_entries
+            SET status = 'EXPIRED', updated_at = now()
+            WHERE entry_id IN (
+                SELECT entry_id
+                FROM waitlist_entries
+                WHERE (status = 'OFFERED' AND offer_expires_at <= now())
+                   OR (status = 'WAITING' AND date < $1)
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING entry_id, doctor_id, offered_timeslot_id, date, offer_expires_at
+            "#,
+            today
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(WaitlistOffer {
+                    entry_id: row.entry_id,
+                    doctor_id: row.doctor_id,
+                    timeslot_id: row.offered_timeslot_id?,
+                    date: row.date,
+                    expires_at: row.offer_expires_at?,
+                })
+            })
+            .collect())
+    }
+
+    async fn intake_questions(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+    ) -> AppResult<Vec<IntakeQuestion>> {
+        let rows = sqlx::query_as!(
+            IntakeQuestionRow,
+            r#"
+            SELECT q.question_id, q.prompt, q.kind, q.options, q.required
+            FROM intake_questions q
+            JOIN doctor_profile dp ON dp.department_id = q.department_id
+            WHERE dp.user_id = $1
+            ORDER BY q.position
+            "#,
+            doctor_id
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn save_intake(
+        &self,
+        tx: &mut PgTx<'_>,
+        appointment_id: i32,
+        intake: &VisitIntake,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE appointments
+            SET chief_complaint = $2, patient_notes = $3
+            WHERE appointment_id = $1
+            "#,
+            appointment_id,
+            intake.chief_complaint,
+            intake.notes
         )
         .execute(&mut **tx)
         .await?;
-        if rows.rows_affected() == 0 {
-            return Err(AppError::NotFound);
+        for (position, answer) in intake.answers.iter().enumerate() {
+            sqlx::query!(
+                r#"
+                INSERT INTO appointment_intake_answers
+                    (appointment_id, position, question_id, prompt, answer)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                appointment_id,
+                position as i32,
+                answer.question_id,
+                answer.prompt,
+                answer.answer
+            )
+            .execute(&mut **tx)
+            .await?;
         }
         Ok(())
     }
+
+    async fn take_queue_number(&self, tx: &mut PgTx<'_>, appointment_id: i32) -> AppResult<i32> {
+        let number = sqlx::query_scalar!(
+            r#"
+            WITH taken AS (
+                INSERT INTO visit_queue_counters (doctor_id, date, last_number)
+                SELECT ts.doctor_id, a.date, 1
+                FROM appointments a
+                JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+                WHERE a.appointment_id = $1
+                ON CONFLICT (doctor_id, date)
+                DO UPDATE SET last_number = visit_queue_counters.last_number + 1
+                RETURNING last_number
+            )
+            UPDATE appointments
+            SET queue_number = (SELECT last_number FROM taken),
+                checked_in_at = now(),
+                called_at = NULL
+            WHERE appointment_id = $1
+            RETURNING queue_number AS "queue_number!"
+            "#,
+            appointment_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(AppError::NotFound)?;
+        Ok(number)
+    }
+
+    async fn lock_queue(&self, tx: &mut PgTx<'_>, doctor_id: Uuid, date: Date) -> AppResult<bool> {
+        let locked = sqlx::query_scalar!(
+            r#"
+            SELECT last_number
+            FROM visit_queue_counters
+            WHERE doctor_id = $1 AND date = $2
+            FOR UPDATE
+            "#,
+            doctor_id,
+            date
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        Ok(locked.is_some())
+    }
+
+    async fn in_consultation(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+        date: Date,
+    ) -> AppResult<Option<i32>> {
+        let id = sqlx::query_scalar!(
+            r#"
+            SELECT a.appointment_id
+            FROM appointments a
+            JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+            WHERE ts.doctor_id = $1
+              AND a.date = $2
+              AND a.status = 'CHECKED_IN'
+              AND a.called_at IS NOT NULL
+            ORDER BY a.called_at DESC
+            LIMIT 1
+            "#,
+            doctor_id,
+            date
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        Ok(id)
+    }
+
+    async fn call_next(
+        &self,
+        tx: &mut PgTx<'_>,
+        doctor_id: Uuid,
+        date: Date,
+    ) -> AppResult<Option<i32>> {
+        let id = sqlx::query_scalar!(
+            r#"
+            UPDATE appointments
+            SET called_at = now()
+            WHERE appointment_id = (
+                SELECT a.appointment_id
+                FROM appointments a
+                JOIN time_slots ts ON ts.timeslot_id = a.timeslot_id
+                WHERE ts.doctor_id = $1
+                  AND a.date = $2
+                  AND a.status = 'CHECKED_IN'
+                  AND a.called_at IS NULL
+                ORDER BY a.queue_number
+                LIMIT 1
+            )
+            RETURNING appointment_id
+            "#,
+            doctor_id,
+            date
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        Ok(id)
+    }
+}
+
+async fn log_transition(
+    tx: &mut PgTx<'_>,
+    appointment_id: i32,
+    from: Option<AppointmentStatus>,
+    to: AppointmentStatus,
+    actor_id: Option<Uuid>,
+    note: Option<&str>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO appointment_transitions (appointment_id, from_status, to_status, actor_id, note)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        appointment_id,
+        from as Option<AppointmentStatus>,
+        to as AppointmentStatus,
+        actor_id,
+        note
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// `ALREADY_BOOKED` when the patient already holds the timeslot on that date.
+fn duplicate_booking(err: sqlx::Error) -> AppError {
+    if violates_constraint(&err, "uniq_patient_timeslot_date_active") {
+        BookingError::AlreadyBooked.into()
+    } else {
+        err.into()
+    }
+}
+
+struct WaitlistEntryRow {
+    entry_id: i32,
+    patient_id: Uuid,
+    doctor_id: Uuid,
+    date: Date,
+    status: WaitlistStatus,
+    offered_timeslot_id: Option<i32>,
+    offer_expires_at: Option<OffsetDateTime>,
+}
+
+impl From<WaitlistEntryRow> for WaitlistEntry {
+    fn from(row: WaitlistEntryRow) -> Self {
+        Self {
+            entry_id: row.entry_id,
+            patient_id: row.patient_id,
+            doctor_id: row.doctor_id,
+            date: row.date,
+            status: row.status,
+            offered_timeslot_id: row.offered_timeslot_id,
+            offer_expires_at: row.offer_expires_at,
+        }
+    }
+}
+
+pub(super) struct IntakeQuestionRow {
+    pub(super) question_id: i32,
+    pub(super) prompt: String,
+    pub(super) kind: String,
+    pub(super) options: Vec<String>,
+    pub(super) required: bool,
+}
+
+impl From<IntakeQuestionRow> for IntakeQuestion {
+    fn from(row: IntakeQuestionRow) -> Self {
+        Self {
+            question_id: row.question_id,
+            prompt: row.prompt,
+            kind: IntakeQuestionKind::parse(&row.kind).unwrap_or(IntakeQuestionKind::Text),
+            options: row.options,
+            required: row.required,
+        }
+    }
 }
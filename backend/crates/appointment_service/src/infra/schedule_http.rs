@@ -0,0 +1,605 @@
+//! Exceptions to the weekly schedule: doctor leave, cancelled occurrences,
+//! one-off timeslots and clinic holidays.
+use super::{
+    catalogue_http::resolve_location,
+    http::{
+        Ctx, DateRangeQuery, DoctorAppointmentRow, DoctorTimeslotRow, TIMESLOT_OVERLAP_CONSTRAINT,
+        attach_intake_answers, format_date, insert_timeslot, overlap_conflict, parse_date,
+        timeslot_conflict, validate_timeslot, weekday_to_i32,
+    },
+};
+use crate::domain::{
+    ClinicHoliday, ClinicHolidayReq, CreateOneOffTimeslotReq, DoctorAppointmentView,
+    DoctorTimeslotView, ScheduleException, ScheduleExceptionCreated, ScheduleExceptionKind,
+    ScheduleExceptionReq,
+};
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get, post, put},
+};
+use common::{
+    auth::{Admin, AuthUser, Doctor, RequireRole, Strict},
+    error::{AppError, AppResult, violates_constraint},
+};
+use db::PgTx;
+use time::Date;
+use uuid::Uuid;
+
+const OVERLAPPING_LEAVE_CONSTRAINT: &str = "schedule_exceptions_no_overlapping_leave";
+const CANCELLED_OCCURRENCE_CONSTRAINT: &str = "uniq_cancelled_occurrence";
+
+#[utoipa::path(
+    get,
+    path = "/exceptions",
+    params(
+        ("from" = Option<String>, Query, description = "Earliest date (YYYY-MM-DD), default today"),
+        ("to" = Option<String>, Query, description = "Latest date (YYYY-MM-DD), default unbounded")
+    ),
+    responses(
+        (status = 200, description = "Leave and cancelled occurrences in the range", body = [ScheduleException]),
+        (status = 400, description = "Invalid date"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn list_schedule_exceptions(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Query(query): Query<DateRangeQuery>,
+) -> AppResult<Json<Vec<ScheduleException>>> {
+    let (from, to) = date_range(query, ctx.svc.today())?;
+    let rows = sqlx::query_as!(
+        ScheduleExceptionRow,
+        r#"
+        SELECT exception_id, kind, start_date, end_date, timeslot_id, reason
+        FROM schedule_exceptions
+        WHERE doctor_id = $1
+          AND end_date >= $2
+          AND ($3::date IS NULL OR start_date <= $3)
+        ORDER BY start_date, exception_id
+        "#,
+        user_id,
+        from,
+        to
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    Ok(Json(
+        rows.into_iter().map(ScheduleException::from).collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/exceptions",
+    request_body = ScheduleExceptionReq,
+    responses(
+        (status = 201, description = "Exception added; lists the appointments that fall in it", body = ScheduleExceptionCreated),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Timeslot not found"),
+        (status = 409, description = "Overlaps existing leave, or the occurrence is already cancelled"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn create_schedule_exception(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<ScheduleExceptionReq>,
+) -> AppResult<(StatusCode, Json<ScheduleExceptionCreated>)> {
+    let today = ctx.svc.today();
+    let start_date = parse_date(&req.start_date)?;
+    let reason = req
+        .reason
+        .map(|reason| reason.trim().to_owned())
+        .filter(|reason| !reason.is_empty());
+
+    let mut tx = ctx.pool.begin().await?;
+    let (end_date, timeslot_id) = match req.kind {
+        ScheduleExceptionKind::Leave => {
+            if req.timeslot_id.is_some() {
+                return Err(AppError::BadRequest(
+                    "timeslot_id only applies to CANCELLED".into(),
+                ));
+            }
+            let end_date = match req.end_date {
+                Some(end_date) => parse_date(&end_date)?,
+                None => start_date,
+            };
+            if end_date < start_date {
+                return Err(AppError::BadRequest(
+                    "end_date must not be before start_date".into(),
+                ));
+            }
+            if end_date < today {
+                return Err(AppError::BadRequest(
+                    "leave must not end in the past".into(),
+                ));
+            }
+            // Bookings lock their timeslot first, so holding every timeslot
+            // of the doctor makes the affected list below complete.
+            sqlx::query!(
+                "SELECT timeslot_id FROM time_slots WHERE doctor_id = $1 FOR UPDATE",
+                user_id
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+            (end_date, None)
+        }
+        ScheduleExceptionKind::Cancelled => {
+            let timeslot_id = req.timeslot_id.ok_or_else(|| {
+                AppError::BadRequest("timeslot_id is required for CANCELLED".into())
+            })?;
+            if start_date < today {
+                return Err(AppError::BadRequest(
+                    "start_date must not be in the past".into(),
+                ));
+            }
+            let slot = sqlx::query!(
+                r#"
+                SELECT day_of_weeks, valid_on
+                FROM time_slots
+                WHERE timeslot_id = $1
+                  AND doctor_id = $2
+                FOR UPDATE
+                "#,
+                timeslot_id,
+                user_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(AppError::NotFound)?;
+            if slot.valid_on.is_some() {
+                return Err(AppError::BadRequest(
+                    "one-off timeslots are removed, not cancelled".into(),
+                ));
+            }
+            if slot.day_of_weeks != weekday_to_i32(start_date.weekday()) {
+                return Err(AppError::BadRequest(format!(
+                    "timeslot {timeslot_id} is not held on {}",
+                    format_date(start_date)
+                )));
+            }
+            (start_date, Some(timeslot_id))
+        }
+    };
+
+    let row = sqlx::query_as!(
+        ScheduleExceptionRow,
+        r#"
+        INSERT INTO schedule_exceptions (doctor_id, kind, start_date, end_date, timeslot_id, reason)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING exception_id, kind, start_date, end_date, timeslot_id, reason
+        "#,
+        user_id,
+        req.kind.as_str(),
+        start_date,
+        end_date,
+        timeslot_id,
+        reason
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| {
+        if violates_constraint(&err, OVERLAPPING_LEAVE_CONSTRAINT) {
+            AppError::Conflict("leave overlaps existing leave".into())
+        } else if violates_constraint(&err, CANCELLED_OCCURRENCE_CONSTRAINT) {
+            AppError::Conflict("timeslot is already cancelled on this date".into())
+        } else {
+            err.into()
+        }
+    })?;
+    let affected = affected_appointments(&mut tx, user_id, row.exception_id).await?;
+    tx.commit().await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ScheduleExceptionCreated {
+            exception: row.into(),
+            affected_appointments: affected,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/exceptions/{exception_id}/appointments",
+    params(("exception_id" = i32, Path)),
+    responses(
+        (status = 200, description = "PENDING and ACCEPTED appointments that fall in the exception", body = [DoctorAppointmentView]),
+        (status = 404, description = "Exception not found"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn schedule_exception_appointments(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Path(exception_id): Path<i32>,
+) -> AppResult<Json<Vec<DoctorAppointmentView>>> {
+    let mut tx = ctx.pool.begin().await?;
+    let exists = sqlx::query_scalar!(
+        r#"
+        SELECT exception_id
+        FROM schedule_exceptions
+        WHERE exception_id = $1
+          AND doctor_id = $2
+        "#,
+        exception_id,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if exists.is_none() {
+        return Err(AppError::NotFound);
+    }
+    let affected = affected_appointments(&mut tx, user_id, exception_id).await?;
+    tx.commit().await?;
+    Ok(Json(affected))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/exceptions/{exception_id}",
+    params(("exception_id" = i32, Path)),
+    responses(
+        (status = 204, description = "Exception removed; the dates are bookable again"),
+        (status = 404, description = "Exception not found"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn delete_schedule_exception(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Path(exception_id): Path<i32>,
+) -> AppResult<StatusCode> {
+    let rows = sqlx::query!(
+        r#"
+        DELETE FROM schedule_exceptions
+        WHERE exception_id = $1
+          AND doctor_id = $2
+        "#,
+        exception_id,
+        user_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+    if rows.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/timeslots/one-off",
+    request_body = CreateOneOffTimeslotReq,
+    responses(
+        (status = 201, description = "One-off timeslot created", body = DoctorTimeslotView),
+        (status = 400, description = "Invalid payload"),
+        (status = 409, description = "Overlaps a timeslot held that day, or the date is leave or a holiday"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn create_one_off_timeslot(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<CreateOneOffTimeslotReq>,
+) -> AppResult<(StatusCode, Json<DoctorTimeslotView>)> {
+    let date = parse_date(&req.date)?;
+    if date < ctx.svc.today() {
+        return Err(AppError::BadRequest("date must not be in the past".into()));
+    }
+    let location = resolve_location(&ctx.pool, req.location_id, &req.place_name).await?;
+    let mut slot = validate_timeslot(
+        weekday_to_i32(date.weekday()),
+        &location,
+        &req.start_time,
+        &req.end_time,
+        req.capacity,
+    )?;
+    slot.valid_on = Some(date);
+
+    let mut tx = ctx.pool.begin().await?;
+    let closed = sqlx::query_scalar!(
+        r#"
+        SELECT 'the clinic is closed on this date (' || name || ')' AS "reason!"
+        FROM clinic_holidays
+        WHERE holiday_date = $2
+        UNION ALL
+        SELECT 'you are on leave on this date'
+        FROM schedule_exceptions
+        WHERE doctor_id = $1
+          AND kind = 'LEAVE'
+          AND $2 BETWEEN start_date AND end_date
+        LIMIT 1
+        "#,
+        user_id,
+        date
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if let Some(reason) = closed {
+        return Err(AppError::Conflict(reason));
+    }
+    // The exclusion constraint only compares one-off timeslots with each
+    // other; weekly timeslots held that day are checked here.
+    let weekly_clash = sqlx::query_as!(
+        DoctorTimeslotRow,
+        r#"
+        SELECT
+            ts.timeslot_id,
+            ts.day_of_weeks,
+            ts.place_name,
+            ts.start_time,
+            ts.end_time,
+            ts.capacity,
+            ts.valid_on,
+            ts.location_id,
+            l.building,
+            l.floor,
+            l.room,
+            l.latitude,
+            l.longitude
+        FROM time_slots ts
+        JOIN locations l ON l.location_id = ts.location_id
+        WHERE ts.doctor_id = $1
+          AND ts.valid_on IS NULL
+          AND ts.day_of_weeks = $2
+          AND ts.start_time < $4
+          AND ts.end_time > $3
+          AND NOT EXISTS (
+                SELECT 1
+                FROM schedule_exceptions se
+                WHERE se.timeslot_id = ts.timeslot_id
+                  AND se.kind = 'CANCELLED'
+                  AND se.start_date = $5
+          )
+        ORDER BY ts.start_time
+        LIMIT 1
+        "#,
+        user_id,
+        slot.day_of_weeks,
+        slot.start_time,
+        slot.end_time,
+        date
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if let Some(clash) = weekly_clash {
+        return Err(overlap_conflict(&clash));
+    }
+    let created = match insert_timeslot(&mut tx, user_id, &slot).await {
+        Ok(row) => row,
+        Err(err) if violates_constraint(&err, TIMESLOT_OVERLAP_CONSTRAINT) => {
+            drop(tx);
+            return Err(timeslot_conflict(&ctx.pool, user_id, &slot, None).await);
+        }
+        Err(err) => return Err(err.into()),
+    };
+    tx.commit().await?;
+    Ok((StatusCode::CREATED, Json(created.into())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/holidays",
+    params(
+        ("from" = Option<String>, Query, description = "Earliest date (YYYY-MM-DD), default today"),
+        ("to" = Option<String>, Query, description = "Latest date (YYYY-MM-DD), default unbounded")
+    ),
+    responses(
+        (status = 200, description = "Clinic holidays in the range", body = [ClinicHoliday]),
+        (status = 400, description = "Invalid date"),
+    ),
+    tag = "appointments"
+)]
+pub(super) async fn list_holidays(
+    State(ctx): State<Ctx>,
+    Query(query): Query<DateRangeQuery>,
+) -> AppResult<Json<Vec<ClinicHoliday>>> {
+    let (from, to) = date_range(query, ctx.svc.today())?;
+    let rows = sqlx::query!(
+        r#"
+        SELECT holiday_date, name
+        FROM clinic_holidays
+        WHERE holiday_date >= $1
+          AND ($2::date IS NULL OR holiday_date <= $2)
+        ORDER BY holiday_date
+        "#,
+        from,
+        to
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| ClinicHoliday {
+                date: format_date(row.holiday_date),
+                name: row.name,
+            })
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    put,
+    path = "/holidays/{date}",
+    params(("date" = String, Path, description = "Date (YYYY-MM-DD)")),
+    request_body = ClinicHolidayReq,
+    responses(
+        (status = 204, description = "Holiday added or renamed"),
+        (status = 400, description = "Invalid payload"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn put_holiday(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(date_str): Path<String>,
+    Json(req): Json<ClinicHolidayReq>,
+) -> AppResult<StatusCode> {
+    let date = parse_date(&date_str)?;
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("name must not be empty".into()));
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO clinic_holidays (holiday_date, name)
+        VALUES ($1, $2)
+        ON CONFLICT (holiday_date) DO UPDATE SET name = EXCLUDED.name
+        "#,
+        date,
+        name
+    )
+    .execute(&ctx.pool)
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/holidays/{date}",
+    params(("date" = String, Path, description = "Date (YYYY-MM-DD)")),
+    responses(
+        (status = 204, description = "Holiday removed"),
+        (status = 404, description = "No holiday on that date"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn delete_holiday(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(date_str): Path<String>,
+) -> AppResult<StatusCode> {
+    let date = parse_date(&date_str)?;
+    let rows = sqlx::query!("DELETE FROM clinic_holidays WHERE holiday_date = $1", date)
+        .execute(&ctx.pool)
+        .await?;
+    if rows.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub(super) fn routes() -> Router<Ctx> {
+    Router::new()
+        .route(
+            "/appointments/exceptions",
+            get(list_schedule_exceptions).post(create_schedule_exception),
+        )
+        .route(
+            "/appointments/exceptions/{exception_id}",
+            delete(delete_schedule_exception),
+        )
+        .route(
+            "/appointments/exceptions/{exception_id}/appointments",
+            get(schedule_exception_appointments),
+        )
+        .route(
+            "/appointments/timeslots/one-off",
+            post(create_one_off_timeslot),
+        )
+        .route("/appointments/holidays", get(list_holidays))
+        .route(
+            "/appointments/holidays/{date}",
+            put(put_holiday).delete(delete_holiday),
+        )
+}
+
+/// `from` defaults to `today`; `to` is unbounded when omitted.
+fn date_range(query: DateRangeQuery, today: Date) -> AppResult<(Date, Option<Date>)> {
+    let from = match query.from {
+        Some(from) => parse_date(&from)?,
+        None => today,
+    };
+    let to = query.to.as_deref().map(parse_date).transpose()?;
+    if to.is_some_and(|to| to < from) {
+        return Err(AppError::BadRequest("to must not be before from".into()));
+    }
+    Ok((from, to))
+}
+
+/// PENDING and ACCEPTED appointments of `doctor_id` that fall in the
+/// exception.
+async fn affected_appointments(
+    tx: &mut PgTx<'_>,
+    doctor_id: Uuid,
+    exception_id: i32,
+) -> AppResult<Vec<DoctorAppointmentView>> {
+    let rows = sqlx::query_as!(
+        DoctorAppointmentRow,
+        r#"
+        SELECT
+            a.appointment_id,
+            a.patient_id,
+            concat_ws(' ', up.first_name, up.last_name) AS "patient_name!",
+            a.date,
+            ts.start_time,
+            ts.end_time,
+            a.status as "status: _",
+            (SELECT COUNT(*) FROM appointment_reschedules r
+             WHERE r.appointment_id = a.appointment_id)::int AS "reschedule_count!",
+            prev.from_date AS "previous_date?",
+            prev.start_time AS "previous_start_time?",
+            prev.end_time AS "previous_end_time?",
+            a.chief_complaint,
+            a.patient_notes AS notes
+        FROM schedule_exceptions se
+        JOIN time_slots ts ON ts.doctor_id = se.doctor_id
+        JOIN appointments a
+          ON a.timeslot_id = ts.timeslot_id
+         AND a.date BETWEEN se.start_date AND se.end_date
+        JOIN users up ON up.user_id = a.patient_id
+        LEFT JOIN LATERAL (
+            SELECT r.from_date, fts.start_time, fts.end_time
+            FROM appointment_reschedules r
+            JOIN time_slots fts ON fts.timeslot_id = r.from_timeslot_id
+            WHERE r.appointment_id = a.appointment_id
+            ORDER BY r.reschedule_id DESC
+            LIMIT 1
+        ) prev ON true
+        WHERE se.exception_id = $1
+          AND se.doctor_id = $2
+          AND (se.timeslot_id IS NULL OR se.timeslot_id = ts.timeslot_id)
+          AND a.status IN ('PENDING', 'ACCEPTED')
+        ORDER BY a.date, ts.start_time
+        "#,
+        exception_id,
+        doctor_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    let mut views: Vec<_> = rows.into_iter().map(DoctorAppointmentView::from).collect();
+    attach_intake_answers(&mut **tx, &mut views).await?;
+    Ok(views)
+}
+
+#[derive(sqlx::FromRow)]
+struct ScheduleExceptionRow {
+    exception_id: i32,
+    kind: String,
+    start_date: Date,
+    end_date: Date,
+    timeslot_id: Option<i32>,
+    reason: Option<String>,
+}
+
+impl From<ScheduleExceptionRow> for ScheduleException {
+    fn from(row: ScheduleExceptionRow) -> Self {
+        Self {
+            exception_id: row.exception_id,
+            kind: ScheduleExceptionKind::parse(&row.kind).unwrap_or(ScheduleExceptionKind::Leave),
+            start_date: format_date(row.start_date),
+            end_date: format_date(row.end_date),
+            timeslot_id: row.timeslot_id,
+            reason: row.reason,
+        }
+    }
+}
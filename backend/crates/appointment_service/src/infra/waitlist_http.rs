@@ -0,0 +1,281 @@
+//! Waitlists for fully booked doctors: joining, leaving and confirming the
+//! place held for a patient when one frees up.
+use super::http::{Ctx, format_date, format_time, parse_date};
+use crate::domain::{
+    Appointment, BookingErrorBody, HeldPlace, JoinWaitlistReq, VisitIntakeReq, WaitlistEntryView,
+    WaitlistStatus,
+};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+};
+use common::{
+    auth::{AuthUser, Doctor, Patient, RequireRole},
+    error::{AppError, AppResult},
+};
+use sqlx::PgPool;
+use time::{Date, OffsetDateTime, Time};
+use uuid::Uuid;
+
+#[utoipa::path(
+    post,
+    path = "/waitlist",
+    request_body = JoinWaitlistReq,
+    responses(
+        (status = 201, description = "Joined the waitlist", body = WaitlistEntryView),
+        (status = 400, description = "Invalid payload"),
+        (status = 409, description = "The doctor still has free places or no timeslots that day, or the patient is already waiting", body = BookingErrorBody),
+        (status = 422, description = "Date in the past or beyond the booking horizon", body = BookingErrorBody),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn join_waitlist(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<JoinWaitlistReq>,
+) -> AppResult<(StatusCode, Json<WaitlistEntryView>)> {
+    let date = parse_date(&req.date)?;
+    let mut tx = ctx.pool.begin().await?;
+    let entry = ctx
+        .svc
+        .join_waitlist(&mut tx, user_id, req.doctor_id, date)
+        .await?;
+    tx.commit().await?;
+    let view = waitlist_views(&ctx.pool, WaitlistFilter::Entry(entry.entry_id), entry.date)
+        .await?
+        .pop()
+        .ok_or(AppError::NotFound)?;
+    Ok((StatusCode::CREATED, Json(view)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/waitlist",
+    responses(
+        (status = 200, description = "The patient's waitlist entries from today on, including held places", body = [WaitlistEntryView]),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn my_waitlist(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+) -> AppResult<Json<Vec<WaitlistEntryView>>> {
+    let views =
+        waitlist_views(&ctx.pool, WaitlistFilter::Patient(user_id), ctx.svc.today()).await?;
+    Ok(Json(views))
+}
+
+#[utoipa::path(
+    get,
+    path = "/waitlist/by-doctor/{date}",
+    params(("date" = String, Path, description = "Date (YYYY-MM-DD)")),
+    responses(
+        (status = 200, description = "Patients waiting for the doctor on the date, in queue order", body = [WaitlistEntryView]),
+        (status = 400, description = "Invalid date"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn doctor_waitlist(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Path(date_str): Path<String>,
+) -> AppResult<Json<Vec<WaitlistEntryView>>> {
+    let date = parse_date(&date_str)?;
+    let views = waitlist_views(&ctx.pool, WaitlistFilter::DoctorOn(user_id), date).await?;
+    Ok(Json(views))
+}
+
+#[utoipa::path(
+    post,
+    path = "/waitlist/{entry_id}/confirm",
+    params(("entry_id" = i32, Path)),
+    request_body = VisitIntakeReq,
+    responses(
+        (status = 200, description = "The held place is booked as a PENDING appointment", body = Appointment),
+        (status = 400, description = "The visit reason does not satisfy the doctor's questionnaire"),
+        (status = 404, description = "Waitlist entry not found"),
+        (status = 409, description = "No place is held, or it can no longer be booked", body = BookingErrorBody),
+        (status = 422, description = "The held slot is outside the booking window", body = BookingErrorBody),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn confirm_waitlist_offer(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+    Path(entry_id): Path<i32>,
+    Json(intake): Json<VisitIntakeReq>,
+) -> AppResult<Json<Appointment>> {
+    let mut tx = ctx.pool.begin().await?;
+    let appt = ctx
+        .svc
+        .confirm_offer(&mut tx, entry_id, user_id, intake)
+        .await?;
+    tx.commit().await?;
+    Ok(Json(appt))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/waitlist/{entry_id}",
+    params(("entry_id" = i32, Path)),
+    responses(
+        (status = 204, description = "Left the waitlist; a held place goes to the next patient"),
+        (status = 404, description = "Waitlist entry not found"),
+        (status = 409, description = "The entry is already closed"),
+    ),
+    tag = "appointments",
+    security(("bearerAuth" = []))
+)]
+pub(super) async fn leave_waitlist(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+    Path(entry_id): Path<i32>,
+) -> AppResult<StatusCode> {
+    let mut tx = ctx.pool.begin().await?;
+    ctx.svc.leave_waitlist(&mut tx, entry_id, user_id).await?;
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub(super) fn routes() -> Router<Ctx> {
+    Router::new()
+        .route(
+            "/appointments/waitlist",
+            get(my_waitlist).post(join_waitlist),
+        )
+        .route(
+            "/appointments/waitlist/by-doctor/{date}",
+            get(doctor_waitlist),
+        )
+        .route("/appointments/waitlist/{entry_id}", delete(leave_waitlist))
+        .route(
+            "/appointments/waitlist/{entry_id}/confirm",
+            post(confirm_waitlist_offer),
+        )
+}
+
+enum WaitlistFilter {
+    Entry(i32),
+    Patient(Uuid),
+    /// The doctor's entries on the given date only.
+    DoctorOn(Uuid),
+}
+
+/// Entries matching `filter` dated `from` or later, in queue order.
+async fn waitlist_views(
+    pool: &PgPool,
+    filter: WaitlistFilter,
+    from: Date,
+) -> AppResult<Vec<WaitlistEntryView>> {
+    let (entry_id, patient_id, doctor_id) = match filter {
+        WaitlistFilter::Entry(entry_id) => (Some(entry_id), None, None),
+        WaitlistFilter::Patient(patient_id) => (None, Some(patient_id), None),
+        WaitlistFilter::DoctorOn(doctor_id) => (None, None, Some(doctor_id)),
+    };
+    let rows = sqlx::query_as!(
+        WaitlistEntryRow,
+        r#"
+        SELECT
+            w.entry_id,
+            w.doctor_id,
+            concat_ws(' ', ud.first_name, ud.last_name) AS "doctor_name!",
+            w.patient_id,
+            concat_ws(' ', up.first_name, up.last_name) AS "patient_name!",
+            w.date,
+            w.status AS "status: WaitlistStatus",
+            CASE WHEN w.status = 'WAITING' THEN (
+                SELECT COUNT(*)
+                FROM waitlist_entries q
+                WHERE q.doctor_id = w.doctor_id
+                  AND q.date = w.date
+                  AND q.status = 'WAITING'
+                  AND (q.created_at, q.entry_id) <= (w.created_at, w.entry_id)
+            ) END AS position,
+            ts.timeslot_id AS "held_timeslot_id?",
+            ts.place_name AS "held_place_name?",
+            ts.start_time AS "held_start_time?",
+            ts.end_time AS "held_end_time?",
+            w.offer_expires_at,
+            w.appointment_id,
+            w.created_at
+        FROM waitlist_entries w
+        JOIN users ud ON ud.user_id = w.doctor_id
+        JOIN users up ON up.user_id = w.patient_id
+        LEFT JOIN time_slots ts
+          ON ts.timeslot_id = w.offered_timeslot_id
+         AND w.status = 'OFFERED'
+        WHERE ($1::int IS NULL OR w.entry_id = $1)
+          AND ($2::uuid IS NULL OR w.patient_id = $2)
+          AND ($3::uuid IS NULL OR (w.doctor_id = $3 AND w.date = $4))
+          AND w.date >= $4
+        ORDER BY w.date, w.doctor_id, w.created_at, w.entry_id
+        "#,
+        entry_id,
+        patient_id,
+        doctor_id,
+        from
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(WaitlistEntryView::from).collect())
+}
+
+struct WaitlistEntryRow {
+    entry_id: i32,
+    doctor_id: Uuid,
+    doctor_name: String,
+    patient_id: Uuid,
+    patient_name: String,
+    date: Date,
+    status: WaitlistStatus,
+    position: Option<i64>,
+    held_timeslot_id: Option<i32>,
+    held_place_name: Option<String>,
+    held_start_time: Option<Time>,
+    held_end_time: Option<Time>,
+    offer_expires_at: Option<OffsetDateTime>,
+    appointment_id: Option<i32>,
+    created_at: OffsetDateTime,
+}
+
+impl From<WaitlistEntryRow> for WaitlistEntryView {
+    fn from(row: WaitlistEntryRow) -> Self {
+        let held_place = match (
+            row.held_timeslot_id,
+            row.held_place_name,
+            row.held_start_time,
+            row.held_end_time,
+            row.offer_expires_at,
+        ) {
+            (Some(timeslot_id), Some(place_name), Some(start), Some(end), Some(expires_at)) => {
+                Some(HeldPlace {
+                    timeslot_id,
+                    place_name,
+                    start_time: format_time(start),
+                    end_time: format_time(end),
+                    expires_at,
+                })
+            }
+            _ => None,
+        };
+        Self {
+            entry_id: row.entry_id,
+            doctor_id: row.doctor_id,
+            doctor_name: row.doctor_name,
+            patient_id: row.patient_id,
+            patient_name: row.patient_name,
+            date: format_date(row.date),
+            status: row.status,
+            position: row.position,
+            held_place,
+            appointment_id: row.appointment_id,
+            created_at: row.created_at,
+        }
+    }
+}
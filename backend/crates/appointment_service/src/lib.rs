@@ -3,3 +3,4 @@ pub mod domain;
 pub mod infra;
 
 pub use infra::http::{ApiDoc, router};
+pub use infra::jobs::spawn_jobs;
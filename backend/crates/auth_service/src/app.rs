@@ -1,42 +1,755 @@
 use crate::domain::{
-    DoctorLoginInput, DoctorProfileResp, DoctorSignupInput, MedicalRightItem, MedicalRightUpsert,
-    PatientLoginInput, PatientProfileResp, PatientSignupInput,
+    AdminLoginInput, AdminUserDetail, AdminUserPage, AdminUserSummary, ContactAddress, ContactKind,
+    DoctorLoginInput, DoctorProfileResp, DoctorSignupInput, LoginFailures, LoginOutcome,
+    MedicalRightCoverage, MedicalRightItem, MedicalRightRequestView, MedicalRightStatus,
+    MedicalRightTerms, MedicalRightUpsert, NewAdminInput, PatientLoginInput, PatientMedicalRight,
+    PatientProfileResp, PatientSignupInput, PendingVerification, ProfileUpdate, RefreshRotation,
+    SessionTokens, StoredCredentials, ThrottleKey, TotpEnrollment, TotpEnrollmentResp, UserSearch,
 };
-use common::error::AppResult;
+use common::{
+    auth::Role,
+    config::{AppConfig, LoginThrottleConfig},
+    error::{AppError, AppResult},
+    notify::{Channel, Notification, Notifier},
+    password::{PasswordPolicy, hash_password, needs_rehash, verify_password},
+    token::{generate_numeric_code, generate_opaque_token, hash_token},
+    totp,
+};
+use std::{net::IpAddr, sync::Arc};
+use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 
+/// Wrong codes tolerated per login challenge before it is discarded.
+const LOGIN_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+/// Wrong codes tolerated per verification code before a new one is needed.
+const VERIFICATION_MAX_ATTEMPTS: i32 = 5;
+const VERIFICATION_CODE_DIGITS: u32 = 6;
+/// Minimum gap between two verification codes for the same address.
+const VERIFICATION_RESEND_SECONDS: i64 = 60;
+const RECOVERY_CODE_COUNT: usize = 10;
+
 #[derive(Clone)]
 pub struct AuthService<R: AuthRepo> {
     repo: R,
+    password_policy: PasswordPolicy,
+    login_throttle: LoginThrottleConfig,
+    notifier: Arc<dyn Notifier>,
+    password_reset_ttl: Duration,
+    password_reset_url: Option<String>,
+    totp_required_roles: Vec<Role>,
+    totp_issuer: String,
+    login_challenge_ttl: Duration,
+    verification_code_ttl: Duration,
 }
 
 impl<R: AuthRepo> AuthService<R> {
-    pub fn new(repo: R) -> Self {
-        Self { repo }
+    pub fn new(repo: R, notifier: Arc<dyn Notifier>, cfg: &AppConfig) -> Self {
+        Self {
+            repo,
+            password_policy: cfg.password_policy.clone(),
+            login_throttle: cfg.login_throttle.clone(),
+            notifier,
+            password_reset_ttl: Duration::minutes(cfg.password_reset_ttl_minutes),
+            password_reset_url: cfg.password_reset_url.clone(),
+            totp_required_roles: cfg.totp_required_roles.clone(),
+            totp_issuer: cfg.totp_issuer.clone(),
+            login_challenge_ttl: Duration::minutes(cfg.login_challenge_ttl_minutes),
+            verification_code_ttl: Duration::minutes(cfg.verification_code_ttl_minutes),
+        }
     }
 
     pub async fn create_patient(&self, input: PatientSignupInput) -> AppResult<Uuid> {
+        self.password_policy.validate(&input.password)?;
         self.repo.create_patient(input).await
     }
 
-    pub async fn login_patient(&self, input: PatientLoginInput) -> AppResult<Uuid> {
-        self.repo.login_patient(input).await
+    pub async fn login_patient(
+        &self,
+        input: PatientLoginInput,
+        client_ip: IpAddr,
+    ) -> AppResult<LoginOutcome> {
+        let ip = ThrottleKey::Ip(client_ip.to_string());
+        self.check_login_throttle(&ip).await?;
+        let credentials = self
+            .repo
+            .patient_credentials(input.hn, &input.citizen_id)
+            .await?;
+        self.authenticate(credentials, &input.password, ip).await
     }
 
     pub async fn upsert_medical_rights(&self, items: Vec<MedicalRightUpsert>) -> AppResult<()> {
         self.repo.upsert_medical_rights(items).await
     }
 
+    pub async fn medical_right_coverage(&self, mr_id: i32) -> AppResult<MedicalRightCoverage> {
+        self.repo
+            .medical_right_coverage(mr_id)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    pub async fn set_medical_right_coverage(
+        &self,
+        mr_id: i32,
+        mut coverage: MedicalRightCoverage,
+    ) -> AppResult<()> {
+        if !(0.0..=100.0).contains(&coverage.copay_percent) {
+            return Err(AppError::BadRequest(
+                "copay_percent must be between 0 and 100".into(),
+            ));
+        }
+        if coverage
+            .order_cap
+            .is_some_and(|cap| cap.is_nan() || cap < 0.0)
+        {
+            return Err(AppError::BadRequest(
+                "order_cap must not be negative".into(),
+            ));
+        }
+        coverage.covered_medicine_ids.sort_unstable();
+        coverage.covered_medicine_ids.dedup();
+        self.repo.set_medical_right_coverage(mr_id, &coverage).await
+    }
+
     pub async fn user_medical_rights(&self, user_id: Uuid) -> AppResult<Vec<MedicalRightItem>> {
         self.repo.user_medical_rights(user_id).await
     }
 
+    /// Every medical right attached to `patient_id`, including pending,
+    /// rejected and expired ones.
+    pub async fn patient_medical_rights(
+        &self,
+        patient_id: Uuid,
+    ) -> AppResult<Vec<PatientMedicalRight>> {
+        self.repo.patient_medical_rights(patient_id).await
+    }
+
+    /// Patient self-service: ask for `mr_id` to be attached, pending admin
+    /// approval. A rejected or expired assignment may be requested again.
+    pub async fn request_medical_right(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+        terms: MedicalRightTerms,
+    ) -> AppResult<()> {
+        let terms = validate_medical_right_terms(terms)?;
+        if terms
+            .valid_until
+            .is_some_and(|until| until < OffsetDateTime::now_utc().date())
+        {
+            return Err(AppError::BadRequest(
+                "valid_until must not be in the past".into(),
+            ));
+        }
+        let existing = self.repo.patient_medical_right(patient_id, mr_id).await?;
+        match existing.map(|right| right.status) {
+            Some(MedicalRightStatus::Pending) => Err(AppError::Conflict(
+                "a request for this medical right is already pending".into(),
+            )),
+            Some(MedicalRightStatus::Approved) => Err(AppError::Conflict(
+                "medical right is already assigned".into(),
+            )),
+            _ => {
+                self.repo
+                    .save_medical_right(
+                        patient_id,
+                        mr_id,
+                        &terms,
+                        MedicalRightStatus::Pending,
+                        None,
+                    )
+                    .await
+            }
+        }
+    }
+
+    pub async fn withdraw_medical_right_request(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+    ) -> AppResult<()> {
+        self.repo
+            .delete_medical_right(patient_id, mr_id, Some(MedicalRightStatus::Pending))
+            .await
+    }
+
+    pub async fn pending_medical_right_requests(&self) -> AppResult<Vec<MedicalRightRequestView>> {
+        self.repo.pending_medical_right_requests().await
+    }
+
+    /// Attach `mr_id` to a patient as approved, replacing any existing
+    /// request or assignment.
+    pub async fn assign_medical_right(
+        &self,
+        admin_id: Uuid,
+        patient_id: Uuid,
+        mr_id: i32,
+        terms: MedicalRightTerms,
+    ) -> AppResult<()> {
+        let terms = validate_medical_right_terms(terms)?;
+        self.repo
+            .save_medical_right(
+                patient_id,
+                mr_id,
+                &terms,
+                MedicalRightStatus::Approved,
+                Some(admin_id),
+            )
+            .await
+    }
+
+    pub async fn unassign_medical_right(&self, patient_id: Uuid, mr_id: i32) -> AppResult<()> {
+        self.repo
+            .delete_medical_right(patient_id, mr_id, None)
+            .await
+    }
+
+    /// Approve or reject a pending request. `NotFound` unless one is pending.
+    pub async fn review_medical_right_request(
+        &self,
+        admin_id: Uuid,
+        patient_id: Uuid,
+        mr_id: i32,
+        approve: bool,
+        reason: Option<String>,
+    ) -> AppResult<()> {
+        let reason = reason
+            .map(|r| r.trim().to_owned())
+            .filter(|r| !r.is_empty());
+        self.repo
+            .review_medical_right(patient_id, mr_id, approve, admin_id, reason)
+            .await
+    }
+
     pub async fn create_doctor(&self, input: DoctorSignupInput) -> AppResult<Uuid> {
+        self.password_policy.validate(&input.password)?;
         self.repo.create_doctor(input).await
     }
 
-    pub async fn login_doctor(&self, input: DoctorLoginInput) -> AppResult<Uuid> {
-        self.repo.login_doctor(input).await
+    pub async fn login_doctor(
+        &self,
+        input: DoctorLoginInput,
+        client_ip: IpAddr,
+    ) -> AppResult<LoginOutcome> {
+        let ip = ThrottleKey::Ip(client_ip.to_string());
+        self.check_login_throttle(&ip).await?;
+        let credentials = self
+            .repo
+            .doctor_credentials(&input.mln, &input.citizen_id)
+            .await?;
+        self.authenticate(credentials, &input.password, ip).await
+    }
+
+    /// Admins have no patient or doctor profile and log in with their
+    /// citizen ID alone.
+    pub async fn login_admin(
+        &self,
+        input: AdminLoginInput,
+        client_ip: IpAddr,
+    ) -> AppResult<LoginOutcome> {
+        let ip = ThrottleKey::Ip(client_ip.to_string());
+        self.check_login_throttle(&ip).await?;
+        let credentials = self.repo.admin_credentials(&input.citizen_id).await?;
+        self.authenticate(credentials, &input.password, ip).await
+    }
+
+    /// Lift an account lockout and forget its failed attempts.
+    pub async fn unlock_account(&self, user_id: Uuid) -> AppResult<()> {
+        self.repo
+            .clear_login_failures(&ThrottleKey::Account(user_id))
+            .await
+    }
+
+    /// Change the password of a signed-in user after re-checking the current
+    /// one. Wrong current passwords count towards the account's login
+    /// throttle, so a stolen access token cannot be used to guess it. Every
+    /// other session of the user is revoked.
+    pub async fn change_password(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        old_password: &str,
+        new_password: &str,
+    ) -> AppResult<()> {
+        let credentials = self
+            .repo
+            .user_credentials(user_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let account = ThrottleKey::Account(user_id);
+        self.check_login_throttle(&account).await?;
+        if !verify_password(old_password, &credentials.password_hash)? {
+            self.record_login_failure(&account).await?;
+            return Err(AppError::BadRequest("current password is incorrect".into()));
+        }
+        self.repo.clear_login_failures(&account).await?;
+        self.password_policy.validate(new_password)?;
+        let password_hash = hash_password(new_password)?;
+        self.repo
+            .change_password(user_id, &password_hash, session_id)
+            .await
+    }
+
+    /// Send a single-use reset token to the given email address or phone
+    /// number. Unknown addresses are silently ignored so the response does
+    /// not reveal which accounts exist.
+    pub async fn request_password_reset(
+        &self,
+        email: Option<String>,
+        phone: Option<String>,
+    ) -> AppResult<()> {
+        let (channel, user_id, to) = match (email, phone) {
+            (Some(email), None) => (
+                Channel::Email,
+                self.repo.user_id_by_email(&email).await?,
+                email,
+            ),
+            (None, Some(phone)) => (
+                Channel::Sms,
+                self.repo.user_id_by_phone(&phone).await?,
+                phone,
+            ),
+            _ => {
+                return Err(AppError::BadRequest(
+                    "provide exactly one of email or phone".into(),
+                ));
+            }
+        };
+        let Some(user_id) = user_id else {
+            return Ok(());
+        };
+        let token = generate_opaque_token();
+        let expires_at = OffsetDateTime::now_utc() + self.password_reset_ttl;
+        self.repo
+            .create_password_reset(user_id, hash_token(&token), expires_at)
+            .await?;
+        let link = match &self.password_reset_url {
+            Some(url) => format!("{url}?token={token}"),
+            None => format!("Reset token: {token}"),
+        };
+        let notification = Notification {
+            channel,
+            to,
+            subject: "Reset your password".into(),
+            body: format!(
+                "{link}\nIt expires in {} minutes. If you did not ask to reset your password, ignore this message.",
+                self.password_reset_ttl.whole_minutes()
+            ),
+        };
+        if let Err(err) = self.notifier.send(&notification).await {
+            tracing::warn!(%user_id, error = %err, "failed to deliver password reset");
+        }
+        Ok(())
+    }
+
+    /// Redeem a reset token: set the new password, revoke every session and
+    /// lift any login lockout.
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> AppResult<()> {
+        self.password_policy.validate(new_password)?;
+        let password_hash = hash_password(new_password)?;
+        if !self
+            .repo
+            .reset_password(hash_token(token), &password_hash)
+            .await?
+        {
+            return Err(AppError::BadRequest(
+                "reset token is invalid or expired".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Second login step: redeem a challenge with a TOTP or recovery code.
+    pub async fn complete_two_factor_login(
+        &self,
+        challenge_token: &str,
+        code: &str,
+    ) -> AppResult<Uuid> {
+        let challenge_hash = hash_token(challenge_token);
+        let user_id = self
+            .repo
+            .attempt_login_challenge(&challenge_hash, LOGIN_CHALLENGE_MAX_ATTEMPTS)
+            .await?
+            .ok_or(AppError::Unauthorized)?;
+        let account = ThrottleKey::Account(user_id);
+        self.check_login_throttle(&account).await?;
+        if !self.verify_second_factor(user_id, code).await? {
+            self.record_login_failure(&account).await?;
+            return Err(AppError::Unauthorized);
+        }
+        if !self.repo.complete_login_challenge(&challenge_hash).await? {
+            return Err(AppError::Unauthorized);
+        }
+        self.repo.clear_login_failures(&account).await?;
+        Ok(user_id)
+    }
+
+    pub async fn update_patient_profile(
+        &self,
+        user_id: Uuid,
+        update: ProfileUpdate,
+    ) -> AppResult<PatientProfileResp> {
+        self.update_profile(user_id, update).await?;
+        self.repo.patient_profile(user_id).await
+    }
+
+    pub async fn update_doctor_profile(
+        &self,
+        user_id: Uuid,
+        update: ProfileUpdate,
+    ) -> AppResult<DoctorProfileResp> {
+        self.update_profile(user_id, update).await?;
+        self.repo.doctor_profile(user_id).await
+    }
+
+    /// Apply a validated profile edit. Email and phone clashes surface as
+    /// `Conflict` naming the field.
+    pub async fn update_profile(&self, user_id: Uuid, update: ProfileUpdate) -> AppResult<()> {
+        let update = validate_profile_update(update)?;
+        self.repo.update_profile(user_id, update).await
+    }
+
+    /// Send a fresh one-time code to the user's current email or phone,
+    /// replacing any earlier one.
+    pub async fn request_contact_verification(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+    ) -> AppResult<()> {
+        let ContactAddress { address, verified } = self.repo.contact_address(user_id, kind).await?;
+        let Some(address) = address else {
+            return Err(AppError::BadRequest(format!(
+                "no {} on file",
+                kind.as_str()
+            )));
+        };
+        if verified {
+            return Err(AppError::Conflict(format!(
+                "{} is already verified",
+                kind.as_str()
+            )));
+        }
+        let now = OffsetDateTime::now_utc();
+        if let Some(pending) = self.repo.pending_verification(user_id, kind).await? {
+            let resend_at = pending.created_at + Duration::seconds(VERIFICATION_RESEND_SECONDS);
+            if pending.target == address && resend_at > now {
+                return Err(AppError::TooManyRequests {
+                    retry_after: seconds_until(resend_at, now),
+                });
+            }
+        }
+        let code = generate_numeric_code(VERIFICATION_CODE_DIGITS);
+        self.repo
+            .store_pending_verification(
+                user_id,
+                kind,
+                &address,
+                hash_token(&code),
+                now + self.verification_code_ttl,
+            )
+            .await?;
+        let notification = Notification {
+            channel: match kind {
+                ContactKind::Email => Channel::Email,
+                ContactKind::Phone => Channel::Sms,
+            },
+            to: address,
+            subject: "Your verification code".into(),
+            body: format!(
+                "Your verification code is {code}. It expires in {} minutes.",
+                self.verification_code_ttl.whole_minutes()
+            ),
+        };
+        self.notifier.send(&notification).await
+    }
+
+    /// Check a verification code and mark the address verified.
+    pub async fn confirm_contact_verification(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+        code: &str,
+    ) -> AppResult<()> {
+        let Some(PendingVerification {
+            target,
+            code_hash,
+            expires_at,
+            attempts,
+            ..
+        }) = self.repo.pending_verification(user_id, kind).await?
+        else {
+            return Err(AppError::BadRequest(
+                "no verification code requested".into(),
+            ));
+        };
+        if expires_at <= OffsetDateTime::now_utc() {
+            return Err(AppError::BadRequest(
+                "verification code expired; request a new one".into(),
+            ));
+        }
+        if attempts >= VERIFICATION_MAX_ATTEMPTS
+            || !self
+                .repo
+                .count_verification_attempt(user_id, kind, VERIFICATION_MAX_ATTEMPTS)
+                .await?
+        {
+            return Err(AppError::BadRequest(
+                "too many wrong codes; request a new one".into(),
+            ));
+        }
+        if hash_token(code.trim()) != code_hash {
+            return Err(AppError::BadRequest("invalid code".into()));
+        }
+        if !self
+            .repo
+            .mark_contact_verified(user_id, kind, &target)
+            .await?
+        {
+            return Err(AppError::BadRequest(format!(
+                "{} changed since the code was sent; request a new one",
+                kind.as_str()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Start (or restart) TOTP enrollment for a doctor or admin. The secret
+    /// only takes effect once confirmed with a code.
+    pub async fn enroll_totp(&self, user_id: Uuid) -> AppResult<TotpEnrollmentResp> {
+        let roles = self.repo.user_roles(user_id).await?;
+        if !roles
+            .iter()
+            .any(|r| matches!(r, Role::Doctor | Role::Admin))
+        {
+            return Err(AppError::Forbidden);
+        }
+        let secret = totp::generate_secret();
+        if !self.repo.start_totp_enrollment(user_id, &secret).await? {
+            return Err(AppError::Conflict(
+                "two-factor authentication is already enabled".into(),
+            ));
+        }
+        let account = self.repo.user_account_label(user_id).await?;
+        Ok(TotpEnrollmentResp {
+            otpauth_uri: totp::otpauth_uri(&self.totp_issuer, &account, &secret),
+            secret,
+        })
+    }
+
+    /// Confirm a pending enrollment with the first code from the app and hand
+    /// out recovery codes.
+    pub async fn confirm_totp(&self, user_id: Uuid, code: &str) -> AppResult<Vec<String>> {
+        let enrollment = self
+            .repo
+            .totp_enrollment(user_id)
+            .await?
+            .ok_or_else(|| AppError::BadRequest("no pending TOTP enrollment".into()))?;
+        if enrollment.confirmed {
+            return Err(AppError::Conflict(
+                "two-factor authentication is already enabled".into(),
+            ));
+        }
+        let step = totp::verify_code(
+            &enrollment.secret,
+            code,
+            OffsetDateTime::now_utc().unix_timestamp(),
+        )
+        .ok_or_else(|| AppError::BadRequest("invalid code".into()))?;
+        let (codes, hashes) = new_recovery_codes();
+        self.repo.confirm_totp(user_id, step, hashes).await?;
+        Ok(codes)
+    }
+
+    /// Replace all recovery codes, after proving possession of the second factor.
+    pub async fn regenerate_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code: &str,
+    ) -> AppResult<Vec<String>> {
+        if !self.verify_second_factor(user_id, code).await? {
+            return Err(AppError::BadRequest("invalid code".into()));
+        }
+        let (codes, hashes) = new_recovery_codes();
+        self.repo.replace_recovery_codes(user_id, hashes).await?;
+        Ok(codes)
+    }
+
+    /// Turn TOTP off. Not allowed while one of the user's roles requires it.
+    pub async fn disable_totp(&self, user_id: Uuid, code: &str) -> AppResult<()> {
+        let roles = self.repo.user_roles(user_id).await?;
+        if roles.iter().any(|r| self.totp_required_roles.contains(r)) {
+            return Err(AppError::Forbidden);
+        }
+        if !self.verify_second_factor(user_id, code).await? {
+            return Err(AppError::BadRequest("invalid code".into()));
+        }
+        self.repo.disable_totp(user_id).await
+    }
+
+    /// Roles to embed in an access token. Roles listed in
+    /// `TOTP_REQUIRED_ROLES` are withheld until the user has confirmed TOTP.
+    pub async fn access_token_roles(&self, user_id: Uuid) -> AppResult<Vec<Role>> {
+        let roles = self.repo.user_roles(user_id).await?;
+        if !roles.iter().any(|r| self.totp_required_roles.contains(r)) {
+            return Ok(roles);
+        }
+        if self.totp_confirmed(user_id).await? {
+            return Ok(roles);
+        }
+        Ok(roles
+            .into_iter()
+            .filter(|r| !self.totp_required_roles.contains(r))
+            .collect())
+    }
+
+    async fn totp_confirmed(&self, user_id: Uuid) -> AppResult<bool> {
+        Ok(self
+            .repo
+            .totp_enrollment(user_id)
+            .await?
+            .is_some_and(|e| e.confirmed))
+    }
+
+    /// Accept a current TOTP code (each time step only once) or an unused
+    /// recovery code.
+    async fn verify_second_factor(&self, user_id: Uuid, code: &str) -> AppResult<bool> {
+        let Some(TotpEnrollment {
+            secret,
+            confirmed: true,
+        }) = self.repo.totp_enrollment(user_id).await?
+        else {
+            return Ok(false);
+        };
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        if let Some(step) = totp::verify_code(&secret, code, now) {
+            return self.repo.advance_totp_step(user_id, step).await;
+        }
+        let code_hash = hash_token(&totp::normalize_recovery_code(code));
+        self.repo.use_recovery_code(user_id, &code_hash).await
+    }
+
+    /// Verify `password` for the looked-up account, counting failures against
+    /// both the account and the client IP. Accounts with TOTP get a challenge
+    /// instead of a session; their failure count is only cleared once the
+    /// second factor checks out.
+    async fn authenticate(
+        &self,
+        credentials: Option<StoredCredentials>,
+        password: &str,
+        ip: ThrottleKey,
+    ) -> AppResult<LoginOutcome> {
+        let Some(StoredCredentials {
+            user_id,
+            password_hash,
+            deactivated,
+        }) = credentials
+        else {
+            self.record_login_failure(&ip).await?;
+            return Err(AppError::Unauthorized);
+        };
+        let account = ThrottleKey::Account(user_id);
+        self.check_login_throttle(&account).await?;
+        if !verify_password(password, &password_hash)? {
+            self.record_login_failure(&ip).await?;
+            self.record_login_failure(&account).await?;
+            return Err(AppError::Unauthorized);
+        }
+        if deactivated {
+            return Err(AppError::Forbidden);
+        }
+        if needs_rehash(&password_hash) {
+            self.upgrade_password_hash(user_id, password, &password_hash)
+                .await;
+        }
+        if self.totp_confirmed(user_id).await? {
+            let challenge_token = generate_opaque_token();
+            let expires_at = OffsetDateTime::now_utc() + self.login_challenge_ttl;
+            self.repo
+                .create_login_challenge(user_id, hash_token(&challenge_token), expires_at)
+                .await?;
+            return Ok(LoginOutcome::ChallengeIssued {
+                challenge_token,
+                expires_in: self.login_challenge_ttl.whole_seconds(),
+            });
+        }
+        self.repo.clear_login_failures(&account).await?;
+        Ok(LoginOutcome::Authenticated(user_id))
+    }
+
+    /// Reject the attempt while `key` is locked or still inside the
+    /// progressive delay that follows repeated account failures.
+    async fn check_login_throttle(&self, key: &ThrottleKey) -> AppResult<()> {
+        let Some(failures) = self.repo.login_failures(key).await? else {
+            return Ok(());
+        };
+        let now = OffsetDateTime::now_utc();
+        if let Some(until) = failures.locked_until
+            && until > now
+        {
+            let retry_after = seconds_until(until, now);
+            return Err(match key {
+                ThrottleKey::Account(_) => AppError::Locked { retry_after },
+                ThrottleKey::Ip(_) => AppError::TooManyRequests { retry_after },
+            });
+        }
+        if let ThrottleKey::Account(_) = key {
+            let next_attempt_at = failures.last_failed_at + self.login_delay(&failures);
+            if next_attempt_at > now {
+                return Err(AppError::TooManyRequests {
+                    retry_after: seconds_until(next_attempt_at, now),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Delay owed after `failures`: nothing for the first few, then doubling
+    /// from one second up to the configured maximum.
+    fn login_delay(&self, failures: &LoginFailures) -> Duration {
+        let cfg = &self.login_throttle;
+        let excess = failures.failed_count - cfg.free_attempts;
+        if excess < 0 {
+            return Duration::ZERO;
+        }
+        let seconds = 1i64
+            .checked_shl(excess as u32)
+            .unwrap_or(i64::MAX)
+            .min(cfg.max_delay_seconds);
+        Duration::seconds(seconds)
+    }
+
+    async fn record_login_failure(&self, key: &ThrottleKey) -> AppResult<()> {
+        let cfg = &self.login_throttle;
+        let now = OffsetDateTime::now_utc();
+        let window_start = now - Duration::minutes(cfg.failure_window_minutes);
+        let failures = self.repo.record_login_failure(key, window_start).await?;
+        let (threshold, lock_minutes) = match key {
+            ThrottleKey::Account(_) => (cfg.lockout_threshold, cfg.lockout_minutes),
+            ThrottleKey::Ip(_) => (cfg.ip_threshold, cfg.ip_block_minutes),
+        };
+        if failures.failed_count >= threshold {
+            tracing::warn!(?key, "too many failed logins; locking");
+            self.repo
+                .lock_login(key, now + Duration::minutes(lock_minutes))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Re-hash a just-verified password that uses an outdated algorithm or
+    /// cost. Failures are logged rather than failing the login.
+    async fn upgrade_password_hash(&self, user_id: Uuid, password: &str, stored: &str) {
+        let result = match hash_password(password) {
+            Ok(fresh) => {
+                self.repo
+                    .replace_password_hash(user_id, stored, &fresh)
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            tracing::warn!(%user_id, error = %err, "failed to upgrade password hash");
+        }
     }
 
     pub async fn doctor_profile(&self, user_id: Uuid) -> AppResult<DoctorProfileResp> {
@@ -46,23 +759,462 @@ impl<R: AuthRepo> AuthService<R> {
     pub async fn patient_profile(&self, user_id: Uuid) -> AppResult<PatientProfileResp> {
         self.repo.patient_profile(user_id).await
     }
+
+    /// Open a new session for `user_id` and hand out its first refresh token.
+    pub async fn start_session(
+        &self,
+        user_id: Uuid,
+        refresh_ttl: Duration,
+    ) -> AppResult<SessionTokens> {
+        let refresh_token = generate_opaque_token();
+        let expires_at = OffsetDateTime::now_utc() + refresh_ttl;
+        let session_id = self
+            .repo
+            .create_session(user_id, hash_token(&refresh_token), expires_at)
+            .await?;
+        Ok(SessionTokens {
+            user_id,
+            session_id,
+            refresh_token,
+        })
+    }
+
+    /// Exchange a refresh token for its successor. Presenting a token that was
+    /// already exchanged revokes the whole session.
+    pub async fn refresh_session(
+        &self,
+        refresh_token: &str,
+        refresh_ttl: Duration,
+    ) -> AppResult<SessionTokens> {
+        let next_token = generate_opaque_token();
+        let expires_at = OffsetDateTime::now_utc() + refresh_ttl;
+        let rotation = self
+            .repo
+            .rotate_refresh_token(
+                hash_token(refresh_token),
+                hash_token(&next_token),
+                expires_at,
+            )
+            .await?;
+        match rotation {
+            RefreshRotation::Rotated {
+                user_id,
+                session_id,
+            } => Ok(SessionTokens {
+                user_id,
+                session_id,
+                refresh_token: next_token,
+            }),
+            RefreshRotation::Reused { session_id } => {
+                tracing::warn!(%session_id, "refresh token reuse detected; session revoked");
+                Err(AppError::Unauthorized)
+            }
+            RefreshRotation::Invalid => Err(AppError::Unauthorized),
+        }
+    }
+
+    pub async fn logout(&self, user_id: Uuid, session_id: Uuid) -> AppResult<()> {
+        self.repo
+            .revoke_session(user_id, session_id, "logout")
+            .await
+    }
+
+    pub async fn logout_all(&self, user_id: Uuid) -> AppResult<()> {
+        self.repo.revoke_all_sessions(user_id, "logout_all").await
+    }
 }
 
 pub trait AuthRepo: Send + Sync {
     #[expect(async_fn_in_trait)]
     async fn create_patient(&self, input: PatientSignupInput) -> AppResult<Uuid>;
     #[expect(async_fn_in_trait)]
-    async fn login_patient(&self, input: PatientLoginInput) -> AppResult<Uuid>;
+    async fn patient_credentials(
+        &self,
+        hn: i32,
+        citizen_id: &str,
+    ) -> AppResult<Option<StoredCredentials>>;
     #[expect(async_fn_in_trait)]
     async fn upsert_medical_rights(&self, items: Vec<MedicalRightUpsert>) -> AppResult<()>;
     #[expect(async_fn_in_trait)]
+    async fn medical_right_coverage(&self, mr_id: i32) -> AppResult<Option<MedicalRightCoverage>>;
+    /// Replace the coverage rules. `NotFound` for an unknown right,
+    /// `BadRequest` for an unknown medicine.
+    #[expect(async_fn_in_trait)]
+    async fn set_medical_right_coverage(
+        &self,
+        mr_id: i32,
+        coverage: &MedicalRightCoverage,
+    ) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
     async fn user_medical_rights(&self, user_id: Uuid) -> AppResult<Vec<MedicalRightItem>>;
     #[expect(async_fn_in_trait)]
+    async fn patient_medical_rights(&self, patient_id: Uuid)
+    -> AppResult<Vec<PatientMedicalRight>>;
+    #[expect(async_fn_in_trait)]
+    async fn patient_medical_right(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+    ) -> AppResult<Option<PatientMedicalRight>>;
+    /// Insert or replace the assignment. `NotFound` if the patient or the
+    /// medical right does not exist.
+    #[expect(async_fn_in_trait)]
+    async fn save_medical_right(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+        terms: &MedicalRightTerms,
+        status: MedicalRightStatus,
+        decided_by: Option<Uuid>,
+    ) -> AppResult<()>;
+    /// `NotFound` if there is no assignment, or none in `status` when given.
+    #[expect(async_fn_in_trait)]
+    async fn delete_medical_right(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+        status: Option<MedicalRightStatus>,
+    ) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
+    async fn pending_medical_right_requests(&self) -> AppResult<Vec<MedicalRightRequestView>>;
+    /// Decide a pending request; `NotFound` if none is pending.
+    #[expect(async_fn_in_trait)]
+    async fn review_medical_right(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+        approve: bool,
+        decided_by: Uuid,
+        reason: Option<String>,
+    ) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
     async fn create_doctor(&self, input: DoctorSignupInput) -> AppResult<Uuid>;
     #[expect(async_fn_in_trait)]
-    async fn login_doctor(&self, input: DoctorLoginInput) -> AppResult<Uuid>;
+    async fn doctor_credentials(
+        &self,
+        mln: &str,
+        citizen_id: &str,
+    ) -> AppResult<Option<StoredCredentials>>;
+    #[expect(async_fn_in_trait)]
+    async fn admin_credentials(&self, citizen_id: &str) -> AppResult<Option<StoredCredentials>>;
+    #[expect(async_fn_in_trait)]
+    async fn user_credentials(&self, user_id: Uuid) -> AppResult<Option<StoredCredentials>>;
+    #[expect(async_fn_in_trait)]
+    async fn user_id_by_email(&self, email: &str) -> AppResult<Option<Uuid>>;
+    #[expect(async_fn_in_trait)]
+    async fn user_id_by_phone(&self, phone: &str) -> AppResult<Option<Uuid>>;
+    /// Store a new password and revoke all sessions except `keep_session`.
+    #[expect(async_fn_in_trait)]
+    async fn change_password(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+        keep_session: Uuid,
+    ) -> AppResult<()>;
+    /// Store a reset token, discarding any earlier unused ones for the user.
+    #[expect(async_fn_in_trait)]
+    async fn create_password_reset(
+        &self,
+        user_id: Uuid,
+        token_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<()>;
+    /// Consume a live reset token and apply the new password. Returns `false`
+    /// when the token is unknown, used or expired.
+    #[expect(async_fn_in_trait)]
+    async fn reset_password(&self, token_hash: String, password_hash: &str) -> AppResult<bool>;
+    /// Apply `update`; `NotFound` if the user does not exist and `BadRequest`
+    /// if doctor fields are given for a non-doctor.
+    #[expect(async_fn_in_trait)]
+    async fn update_profile(&self, user_id: Uuid, update: ProfileUpdate) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
+    async fn contact_address(&self, user_id: Uuid, kind: ContactKind) -> AppResult<ContactAddress>;
+    #[expect(async_fn_in_trait)]
+    async fn pending_verification(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+    ) -> AppResult<Option<PendingVerification>>;
+    /// Replace the outstanding code for `kind` and reset its attempt count.
+    #[expect(async_fn_in_trait)]
+    async fn store_pending_verification(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+        target: &str,
+        code_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<()>;
+    /// Count one attempt. Returns `false` when the attempts were already used up.
+    #[expect(async_fn_in_trait)]
+    async fn count_verification_attempt(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+        max_attempts: i32,
+    ) -> AppResult<bool>;
+    /// Mark the address verified if it still equals `target`, and drop the
+    /// pending code. Returns `false` if the address has changed.
+    #[expect(async_fn_in_trait)]
+    async fn mark_contact_verified(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+        target: &str,
+    ) -> AppResult<bool>;
+    #[expect(async_fn_in_trait)]
+    async fn user_roles(&self, user_id: Uuid) -> AppResult<Vec<Role>>;
+    /// Email, or phone when there is none; labels the authenticator entry.
+    #[expect(async_fn_in_trait)]
+    async fn user_account_label(&self, user_id: Uuid) -> AppResult<String>;
+    #[expect(async_fn_in_trait)]
+    async fn totp_enrollment(&self, user_id: Uuid) -> AppResult<Option<TotpEnrollment>>;
+    /// Store a pending secret. Returns `false` if TOTP is already confirmed.
+    #[expect(async_fn_in_trait)]
+    async fn start_totp_enrollment(&self, user_id: Uuid, secret: &str) -> AppResult<bool>;
+    /// Mark the enrollment confirmed at `step` and store fresh recovery codes.
+    #[expect(async_fn_in_trait)]
+    async fn confirm_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        recovery_code_hashes: Vec<String>,
+    ) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
+    async fn disable_totp(&self, user_id: Uuid) -> AppResult<()>;
+    /// Record `step` as used. Returns `false` if it (or a later step) was
+    /// already used, i.e. the code is being replayed.
+    #[expect(async_fn_in_trait)]
+    async fn advance_totp_step(&self, user_id: Uuid, step: i64) -> AppResult<bool>;
+    /// Mark a recovery code used. Returns `false` if unknown or already used.
+    #[expect(async_fn_in_trait)]
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> AppResult<bool>;
+    #[expect(async_fn_in_trait)]
+    async fn create_login_challenge(
+        &self,
+        user_id: Uuid,
+        challenge_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<()>;
+    /// Count an attempt against a live challenge and return its user, or
+    /// `None` once it is expired, used or out of attempts.
+    #[expect(async_fn_in_trait)]
+    async fn attempt_login_challenge(
+        &self,
+        challenge_hash: &str,
+        max_attempts: i32,
+    ) -> AppResult<Option<Uuid>>;
+    /// Mark a challenge used. Returns `false` if it was redeemed concurrently.
+    #[expect(async_fn_in_trait)]
+    async fn complete_login_challenge(&self, challenge_hash: &str) -> AppResult<bool>;
+    /// Swap the stored hash, unless it changed since `old_hash` was read.
+    #[expect(async_fn_in_trait)]
+    async fn replace_password_hash(
+        &self,
+        user_id: Uuid,
+        old_hash: &str,
+        new_hash: &str,
+    ) -> AppResult<()>;
     #[expect(async_fn_in_trait)]
     async fn doctor_profile(&self, user_id: Uuid) -> AppResult<DoctorProfileResp>;
     #[expect(async_fn_in_trait)]
     async fn patient_profile(&self, user_id: Uuid) -> AppResult<PatientProfileResp>;
+    #[expect(async_fn_in_trait)]
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        refresh_token_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<Uuid>;
+    #[expect(async_fn_in_trait)]
+    async fn rotate_refresh_token(
+        &self,
+        presented_hash: String,
+        next_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<RefreshRotation>;
+    #[expect(async_fn_in_trait)]
+    async fn revoke_session(&self, user_id: Uuid, session_id: Uuid, reason: &str) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
+    async fn revoke_all_sessions(&self, user_id: Uuid, reason: &str) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
+    async fn login_failures(&self, key: &ThrottleKey) -> AppResult<Option<LoginFailures>>;
+    /// Count one more failure for `key`, restarting the count when the previous
+    /// failure is older than `window_start`.
+    #[expect(async_fn_in_trait)]
+    async fn record_login_failure(
+        &self,
+        key: &ThrottleKey,
+        window_start: OffsetDateTime,
+    ) -> AppResult<LoginFailures>;
+    /// Lock `key` until the given instant and reset its failure count.
+    #[expect(async_fn_in_trait)]
+    async fn lock_login(&self, key: &ThrottleKey, until: OffsetDateTime) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
+    async fn clear_login_failures(&self, key: &ThrottleKey) -> AppResult<()>;
+}
+
+/// Largest accepted `per_page` for user listings.
+const MAX_PER_PAGE: i64 = 100;
+
+#[derive(Clone)]
+pub struct AdminService<R: AdminRepo> {
+    repo: R,
+    password_policy: PasswordPolicy,
+}
+
+impl<R: AdminRepo> AdminService<R> {
+    pub fn new(repo: R, password_policy: PasswordPolicy) -> Self {
+        Self {
+            repo,
+            password_policy,
+        }
+    }
+
+    pub async fn list_users(&self, mut search: UserSearch) -> AppResult<AdminUserPage> {
+        search.page = search.page.max(1);
+        search.per_page = search.per_page.clamp(1, MAX_PER_PAGE);
+        search.q = search
+            .q
+            .map(|q| q.trim().to_owned())
+            .filter(|q| !q.is_empty());
+        let (items, total) = self.repo.search_users(&search).await?;
+        Ok(AdminUserPage {
+            items,
+            total,
+            page: search.page,
+            per_page: search.per_page,
+        })
+    }
+
+    pub async fn user(&self, user_id: Uuid) -> AppResult<AdminUserDetail> {
+        self.repo
+            .user_detail(user_id)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    pub async fn grant_role(&self, user_id: Uuid, role: Role) -> AppResult<()> {
+        self.repo.grant_role(user_id, role).await
+    }
+
+    /// Revoke `role`. Admins cannot drop their own admin role, so the last
+    /// admin cannot lock everyone out by accident.
+    pub async fn revoke_role(&self, actor_id: Uuid, user_id: Uuid, role: Role) -> AppResult<()> {
+        if actor_id == user_id && role == Role::Admin {
+            return Err(AppError::BadRequest(
+                "cannot revoke your own admin role".into(),
+            ));
+        }
+        self.repo.revoke_role(user_id, role).await
+    }
+
+    /// Deactivate an account and revoke all of its sessions.
+    pub async fn deactivate(&self, actor_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        if actor_id == user_id {
+            return Err(AppError::BadRequest(
+                "cannot deactivate your own account".into(),
+            ));
+        }
+        self.repo.set_deactivated(user_id, true).await
+    }
+
+    pub async fn reactivate(&self, user_id: Uuid) -> AppResult<()> {
+        self.repo.set_deactivated(user_id, false).await
+    }
+
+    /// Create the first admin account. Refused once an active admin exists;
+    /// further admins are granted the role through the API.
+    pub async fn bootstrap_admin(&self, input: NewAdminInput) -> AppResult<Uuid> {
+        self.password_policy.validate(&input.password)?;
+        if self.repo.active_admin_exists().await? {
+            return Err(AppError::Conflict("an admin account already exists".into()));
+        }
+        self.repo.create_admin(input).await
+    }
+}
+
+pub trait AdminRepo: Send + Sync {
+    /// One page of matching users and the total number of matches.
+    #[expect(async_fn_in_trait)]
+    async fn search_users(&self, search: &UserSearch) -> AppResult<(Vec<AdminUserSummary>, i64)>;
+    #[expect(async_fn_in_trait)]
+    async fn user_detail(&self, user_id: Uuid) -> AppResult<Option<AdminUserDetail>>;
+    /// Idempotent; `NotFound` if the user does not exist.
+    #[expect(async_fn_in_trait)]
+    async fn grant_role(&self, user_id: Uuid, role: Role) -> AppResult<()>;
+    /// `NotFound` if the user does not hold the role.
+    #[expect(async_fn_in_trait)]
+    async fn revoke_role(&self, user_id: Uuid, role: Role) -> AppResult<()>;
+    /// Set or clear `deactivated_at`; deactivating also revokes all sessions.
+    #[expect(async_fn_in_trait)]
+    async fn set_deactivated(&self, user_id: Uuid, deactivated: bool) -> AppResult<()>;
+    #[expect(async_fn_in_trait)]
+    async fn active_admin_exists(&self) -> AppResult<bool>;
+    #[expect(async_fn_in_trait)]
+    async fn create_admin(&self, input: NewAdminInput) -> AppResult<Uuid>;
+}
+
+/// Trim the policy number (blank clears it) and check the validity window.
+fn validate_medical_right_terms(mut terms: MedicalRightTerms) -> AppResult<MedicalRightTerms> {
+    terms.policy_number = terms
+        .policy_number
+        .map(|p| p.trim().to_owned())
+        .filter(|p| !p.is_empty());
+    if let (Some(from), Some(until)) = (terms.valid_from, terms.valid_until)
+        && until < from
+    {
+        return Err(AppError::BadRequest(
+            "valid_until must not be before valid_from".into(),
+        ));
+    }
+    Ok(terms)
+}
+
+/// Trim every field and reject blank names, phone numbers and malformed
+/// emails. Blank `department`/`position` clear the value.
+fn validate_profile_update(update: ProfileUpdate) -> AppResult<ProfileUpdate> {
+    fn required(field: &str, value: Option<String>) -> AppResult<Option<String>> {
+        match value.map(|v| v.trim().to_owned()) {
+            Some(v) if v.is_empty() => {
+                Err(AppError::BadRequest(format!("{field} must not be empty")))
+            }
+            other => Ok(other),
+        }
+    }
+    let email = required("email", update.email)?;
+    if let Some(email) = &email
+        && !email.contains('@')
+    {
+        return Err(AppError::BadRequest("email is not a valid address".into()));
+    }
+    Ok(ProfileUpdate {
+        first_name: required("first_name", update.first_name)?,
+        last_name: required("last_name", update.last_name)?,
+        email,
+        phone: required("phone", update.phone)?,
+        department: update.department.map(|v| v.trim().to_owned()),
+        position: update.position.map(|v| v.trim().to_owned()),
+    })
+}
+
+/// Fresh recovery codes together with their hashes for storage.
+fn new_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| totp::generate_recovery_code())
+        .collect();
+    let hashes = codes.iter().map(|code| hash_token(code)).collect();
+    (codes, hashes)
+}
+
+fn seconds_until(instant: OffsetDateTime, now: OffsetDateTime) -> u64 {
+    // Round up so clients never retry a moment too early.
+    let millis = (instant - now).whole_milliseconds().max(0) as u64;
+    millis.div_ceil(1000)
 }
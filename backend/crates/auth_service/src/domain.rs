@@ -1,6 +1,10 @@
+use common::auth::Role;
 use serde::{Deserialize, Serialize};
-use time::OffsetDateTime;
+use time::{Date, OffsetDateTime};
 use utoipa::ToSchema;
+use uuid::Uuid;
+
+time::serde::format_description!(iso_date, Date, "[year]-[month]-[day]");
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PatientSignupReq {
@@ -20,6 +24,15 @@ pub struct LoginPatientReq {
     pub password: String,
 }
 
+/// Partial update of the signed-in patient's profile; omitted fields are kept.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct UpdatePatientProfileReq {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct PatientLoginInput {
     pub hn: i32,
@@ -43,6 +56,128 @@ pub struct MedicalRightUpsert {
     pub image_url: String,
 }
 
+/// Coverage rules of a medical right. Covered medicines are paid for except
+/// for `copay_percent` of their price, up to `order_cap` per order.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MedicalRightCoverage {
+    #[schema(example = 30.0)]
+    pub copay_percent: f64,
+    #[schema(nullable = true, example = 1000.0)]
+    pub order_cap: Option<f64>,
+    pub covered_medicine_ids: Vec<i32>,
+}
+
+/// Review state of a patient's medical right. `Expired` is derived from
+/// `valid_until` when read and never stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MedicalRightStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Expired,
+}
+
+impl MedicalRightStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MedicalRightStatus::Pending => "PENDING",
+            MedicalRightStatus::Approved => "APPROVED",
+            MedicalRightStatus::Rejected => "REJECTED",
+            MedicalRightStatus::Expired => "EXPIRED",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "PENDING" => Some(MedicalRightStatus::Pending),
+            "APPROVED" => Some(MedicalRightStatus::Approved),
+            "REJECTED" => Some(MedicalRightStatus::Rejected),
+            "EXPIRED" => Some(MedicalRightStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+/// A medical right attached to a patient, in any state.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PatientMedicalRight {
+    pub mr_id: i32,
+    pub name: String,
+    pub policy_number: Option<String>,
+    #[serde(with = "iso_date::option")]
+    #[schema(value_type = Option<String>, format = Date, example = "2025-01-01")]
+    pub valid_from: Option<Date>,
+    #[serde(with = "iso_date::option")]
+    #[schema(value_type = Option<String>, format = Date, example = "2025-12-31")]
+    pub valid_until: Option<Date>,
+    pub status: MedicalRightStatus,
+    /// Approved and within its validity window today.
+    pub active: bool,
+    pub rejection_reason: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub requested_at: OffsetDateTime,
+}
+
+/// A patient's request awaiting review, as listed for admins.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MedicalRightRequestView {
+    pub patient_id: Uuid,
+    pub patient_name: String,
+    pub hn: i32,
+    pub mr_id: i32,
+    pub name: String,
+    pub policy_number: Option<String>,
+    #[serde(with = "iso_date::option")]
+    #[schema(value_type = Option<String>, format = Date, example = "2025-01-01")]
+    pub valid_from: Option<Date>,
+    #[serde(with = "iso_date::option")]
+    #[schema(value_type = Option<String>, format = Date, example = "2025-12-31")]
+    pub valid_until: Option<Date>,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub requested_at: OffsetDateTime,
+}
+
+/// Admin assignment of a medical right. Missing dates leave that end of the
+/// validity window open.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AssignMedicalRightReq {
+    pub policy_number: Option<String>,
+    #[serde(default, with = "iso_date::option")]
+    #[schema(value_type = Option<String>, format = Date, example = "2025-01-01")]
+    pub valid_from: Option<Date>,
+    #[serde(default, with = "iso_date::option")]
+    #[schema(value_type = Option<String>, format = Date, example = "2025-12-31")]
+    pub valid_until: Option<Date>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RequestMedicalRightReq {
+    pub mr_id: i32,
+    pub policy_number: Option<String>,
+    #[serde(default, with = "iso_date::option")]
+    #[schema(value_type = Option<String>, format = Date, example = "2025-01-01")]
+    pub valid_from: Option<Date>,
+    #[serde(default, with = "iso_date::option")]
+    #[schema(value_type = Option<String>, format = Date, example = "2025-12-31")]
+    pub valid_until: Option<Date>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RejectMedicalRightReq {
+    pub reason: Option<String>,
+}
+
+/// Policy number and validity window of an assignment.
+#[derive(Debug, Clone, Default)]
+pub struct MedicalRightTerms {
+    pub policy_number: Option<String>,
+    pub valid_from: Option<Date>,
+    pub valid_until: Option<Date>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DoctorSignupReq {
     pub mln: String,
@@ -65,6 +200,53 @@ pub struct DoctorSignupInput {
     pub email: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LoginAdminReq {
+    pub citizen_id: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AdminLoginInput {
+    pub citizen_id: String,
+    pub password: String,
+}
+
+/// Partial update of the signed-in doctor's profile; omitted fields are kept.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct UpdateDoctorProfileReq {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    /// Name of a catalogued department, matched ignoring case.
+    pub department: Option<String>,
+    pub position: Option<String>,
+}
+
+/// Admin edit of any user. `department` and `position` only apply to doctors.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct AdminUpdateProfileReq {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    /// Name of a catalogued department, matched ignoring case.
+    pub department: Option<String>,
+    pub position: Option<String>,
+}
+
+/// Fields to change on a user and, for doctors, their doctor profile.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileUpdate {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub department: Option<String>,
+    pub position: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct LoginDoctorReq {
     pub mln: String,
@@ -82,6 +264,179 @@ pub struct DoctorLoginInput {
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AccessTokenResp {
     pub access_token: String,
+    pub refresh_token: String,
+    /// Lifetime of `access_token` in seconds.
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RefreshTokenReq {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChangePasswordReq {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+/// Identify the account by exactly one of `email` or `phone`; the reset token
+/// is sent to that address.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ForgotPasswordReq {
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ResetPasswordReq {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// A freshly issued refresh token together with the session it belongs to.
+#[derive(Debug, Clone)]
+pub struct SessionTokens {
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    pub refresh_token: String,
+}
+
+/// Result of presenting a refresh token for rotation.
+#[derive(Debug, Clone, Copy)]
+pub enum RefreshRotation {
+    Rotated {
+        user_id: Uuid,
+        session_id: Uuid,
+    },
+    /// The token had already been rotated; its session has been revoked.
+    Reused {
+        session_id: Uuid,
+    },
+    Invalid,
+}
+
+/// Returned by the first login step instead of tokens when the account has
+/// two-factor authentication enabled.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TwoFactorChallengeResp {
+    /// Always `true`; lets clients tell this apart from [`AccessTokenResp`].
+    pub two_factor_required: bool,
+    /// Pass to `/users/login/2fa` together with a TOTP or recovery code.
+    pub challenge_token: String,
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResp {
+    Tokens(AccessTokenResp),
+    TwoFactorChallenge(TwoFactorChallengeResp),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TwoFactorLoginReq {
+    pub challenge_token: String,
+    /// Current TOTP code or an unused recovery code.
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TotpEnrollmentResp {
+    /// Base32 shared secret, for manual entry.
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TotpCodeReq {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RecoveryCodesResp {
+    /// Shown once; each code can replace a TOTP code a single time.
+    pub recovery_codes: Vec<String>,
+}
+
+/// Result of a successful password check.
+#[derive(Debug, Clone)]
+pub enum LoginOutcome {
+    Authenticated(Uuid),
+    /// A second factor is needed; the challenge is valid for `expires_in` seconds.
+    ChallengeIssued {
+        challenge_token: String,
+        expires_in: i64,
+    },
+}
+
+/// A user's TOTP enrollment, confirmed or pending.
+#[derive(Debug, Clone)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub confirmed: bool,
+}
+
+/// Contact detail that can be verified with a one-time code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContactKind {
+    Email,
+    Phone,
+}
+
+impl ContactKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContactKind::Email => "email",
+            ContactKind::Phone => "phone",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VerificationCodeReq {
+    pub code: String,
+}
+
+/// A user's current email or phone and whether it is verified.
+#[derive(Debug, Clone)]
+pub struct ContactAddress {
+    pub address: Option<String>,
+    pub verified: bool,
+}
+
+/// Outstanding verification code for one contact kind.
+#[derive(Debug, Clone)]
+pub struct PendingVerification {
+    pub target: String,
+    pub code_hash: String,
+    pub created_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub attempts: i32,
+}
+
+/// Stored credentials for the account matching a login identifier.
+#[derive(Debug, Clone)]
+pub struct StoredCredentials {
+    pub user_id: Uuid,
+    pub password_hash: String,
+    pub deactivated: bool,
+}
+
+/// What failed logins are counted against.
+#[derive(Debug, Clone)]
+pub enum ThrottleKey {
+    Account(Uuid),
+    Ip(String),
+}
+
+/// Failed login counters for one [`ThrottleKey`].
+#[derive(Debug, Clone, Copy)]
+pub struct LoginFailures {
+    pub failed_count: i32,
+    pub last_failed_at: OffsetDateTime,
+    pub locked_until: Option<OffsetDateTime>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -92,6 +447,8 @@ pub struct DoctorProfileResp {
     pub phone: String,
     pub departments: String,
     pub position: String,
+    pub email_verified: bool,
+    pub phone_verified: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -100,6 +457,8 @@ pub struct PatientProfileResp {
     pub last_name: String,
     pub email: String,
     pub phone: String,
+    pub email_verified: bool,
+    pub phone_verified: bool,
     #[serde(with = "time::serde::rfc3339")]
     #[schema(value_type = String, format = DateTime)]
     pub updated_at: OffsetDateTime,
@@ -151,6 +510,26 @@ impl From<MedicalRightItem> for MedicalRightUpsert {
     }
 }
 
+impl From<AssignMedicalRightReq> for MedicalRightTerms {
+    fn from(value: AssignMedicalRightReq) -> Self {
+        Self {
+            policy_number: value.policy_number,
+            valid_from: value.valid_from,
+            valid_until: value.valid_until,
+        }
+    }
+}
+
+impl From<RequestMedicalRightReq> for MedicalRightTerms {
+    fn from(value: RequestMedicalRightReq) -> Self {
+        Self {
+            policy_number: value.policy_number,
+            valid_from: value.valid_from,
+            valid_until: value.valid_until,
+        }
+    }
+}
+
 impl From<DoctorSignupReq> for DoctorSignupInput {
     fn from(value: DoctorSignupReq) -> Self {
         Self {
@@ -174,3 +553,140 @@ impl From<LoginDoctorReq> for DoctorLoginInput {
         }
     }
 }
+
+impl From<UpdatePatientProfileReq> for ProfileUpdate {
+    fn from(value: UpdatePatientProfileReq) -> Self {
+        Self {
+            first_name: value.first_name,
+            last_name: value.last_name,
+            email: value.email,
+            phone: value.phone,
+            department: None,
+            position: None,
+        }
+    }
+}
+
+impl From<UpdateDoctorProfileReq> for ProfileUpdate {
+    fn from(value: UpdateDoctorProfileReq) -> Self {
+        Self {
+            first_name: value.first_name,
+            last_name: value.last_name,
+            email: value.email,
+            phone: value.phone,
+            department: value.department,
+            position: value.position,
+        }
+    }
+}
+
+impl From<AdminUpdateProfileReq> for ProfileUpdate {
+    fn from(value: AdminUpdateProfileReq) -> Self {
+        Self {
+            first_name: value.first_name,
+            last_name: value.last_name,
+            email: value.email,
+            phone: value.phone,
+            department: value.department,
+            position: value.position,
+        }
+    }
+}
+
+impl From<LoginAdminReq> for AdminLoginInput {
+    fn from(value: LoginAdminReq) -> Self {
+        Self {
+            citizen_id: value.citizen_id,
+            password: value.password,
+        }
+    }
+}
+
+/// Filters for the admin user listing.
+#[derive(Debug, Clone, Default)]
+pub struct UserSearch {
+    /// Matched case-insensitively against names, email, phone, citizen ID,
+    /// HN and MLN.
+    pub q: Option<String>,
+    pub role: Option<Role>,
+    /// `Some(true)` for deactivated accounts only, `Some(false)` for active.
+    pub deactivated: Option<bool>,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AdminUserSummary {
+    pub user_id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: Option<String>,
+    pub phone: String,
+    pub citizen_id: String,
+    #[schema(value_type = Vec<String>)]
+    pub roles: Vec<Role>,
+    pub deactivated: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AdminUserPage {
+    pub items: Vec<AdminUserSummary>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PatientProfileInfo {
+    pub hn: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DoctorProfileInfo {
+    pub mln: String,
+    pub department: Option<String>,
+    pub position: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AdminUserDetail {
+    pub user_id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub phone: String,
+    pub phone_verified: bool,
+    pub citizen_id: String,
+    #[schema(value_type = Vec<String>)]
+    pub roles: Vec<Role>,
+    pub two_factor_enabled: bool,
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub deactivated_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: OffsetDateTime,
+    pub patient_profile: Option<PatientProfileInfo>,
+    pub doctor_profile: Option<DoctorProfileInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoleReq {
+    #[schema(value_type = String, example = "DOCTOR")]
+    pub role: Role,
+}
+
+/// Details for the bootstrap admin account.
+#[derive(Debug, Clone)]
+pub struct NewAdminInput {
+    pub citizen_id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub phone: String,
+    pub email: Option<String>,
+    pub password: String,
+}
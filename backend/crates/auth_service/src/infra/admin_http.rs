@@ -0,0 +1,264 @@
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+};
+use common::{
+    auth::{Admin, AuthUser, RequireRole, Role, Strict, jwt_keys_from_config},
+    config::AppConfig,
+    error::{AppError, AppResult},
+};
+use sqlx::PgPool;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use super::{http::SecurityAddon, repo_sqlx::SqlxAuthRepo};
+use crate::{
+    app::AdminService,
+    domain::{
+        AdminUserDetail, AdminUserPage, AdminUserSummary, DoctorProfileInfo, NewAdminInput,
+        PatientProfileInfo, RoleReq, UserSearch,
+    },
+};
+
+const DEFAULT_PER_PAGE: i64 = 20;
+
+#[derive(Clone)]
+pub struct Ctx {
+    svc: AdminService<SqlxAuthRepo>,
+}
+impl Ctx {
+    pub fn new(pool: PgPool, cfg: &AppConfig) -> Self {
+        Self {
+            svc: AdminService::new(SqlxAuthRepo::new(pool), cfg.password_policy.clone()),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct UserListQuery {
+    q: Option<String>,
+    role: Option<String>,
+    status: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+impl TryFrom<UserListQuery> for UserSearch {
+    type Error = AppError;
+
+    fn try_from(query: UserListQuery) -> AppResult<Self> {
+        let role = query.role.as_deref().map(parse_role).transpose()?;
+        let deactivated = match query.status.as_deref() {
+            None => None,
+            Some("active") => Some(false),
+            Some("deactivated") => Some(true),
+            Some(other) => {
+                return Err(AppError::BadRequest(format!(
+                    "unknown status `{other}`, expected `active` or `deactivated`"
+                )));
+            }
+        };
+        Ok(UserSearch {
+            q: query.q,
+            role,
+            deactivated,
+            page: query.page.unwrap_or(1),
+            per_page: query.per_page.unwrap_or(DEFAULT_PER_PAGE),
+        })
+    }
+}
+
+fn parse_role(value: &str) -> AppResult<Role> {
+    Role::parse(&value.to_ascii_uppercase())
+        .ok_or_else(|| AppError::BadRequest(format!("unknown role `{value}`")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/users",
+    params(
+        ("q" = Option<String>, Query, description = "Search names, email, phone, citizen ID, HN or MLN"),
+        ("role" = Option<String>, Query, description = "PATIENT, DOCTOR or ADMIN"),
+        ("status" = Option<String>, Query, description = "`active` or `deactivated`"),
+        ("page" = Option<i64>, Query, description = "1-based page number"),
+        ("per_page" = Option<i64>, Query, description = "Page size, at most 100 (default 20)")
+    ),
+    responses((status = 200, description = "Matching users, newest first", body = AdminUserPage)),
+    tag = "admin",
+    security(("bearerAuth" = []))
+)]
+async fn list_users(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Query(query): Query<UserListQuery>,
+) -> AppResult<Json<AdminUserPage>> {
+    let page = ctx.svc.list_users(query.try_into()?).await?;
+    Ok(Json(page))
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/{user_id}",
+    params(("user_id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 200, description = "User with roles and profiles", body = AdminUserDetail),
+        (status = 404, description = "Not found")
+    ),
+    tag = "admin",
+    security(("bearerAuth" = []))
+)]
+async fn get_user(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(user_id): Path<Uuid>,
+) -> AppResult<Json<AdminUserDetail>> {
+    Ok(Json(ctx.svc.user(user_id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{user_id}/roles",
+    params(("user_id" = Uuid, Path, description = "User ID")),
+    request_body = RoleReq,
+    responses(
+        (status = 204, description = "Role granted"),
+        (status = 404, description = "Not found")
+    ),
+    tag = "admin",
+    security(("bearerAuth" = []))
+)]
+async fn grant_role(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<RoleReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc.grant_role(user_id, req.role).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/{user_id}/roles/{role}",
+    params(
+        ("user_id" = Uuid, Path, description = "User ID"),
+        ("role" = String, Path, description = "PATIENT, DOCTOR or ADMIN")
+    ),
+    responses(
+        (status = 204, description = "Role revoked"),
+        (status = 400, description = "Cannot revoke your own admin role"),
+        (status = 404, description = "User does not hold the role")
+    ),
+    tag = "admin",
+    security(("bearerAuth" = []))
+)]
+async fn revoke_role(
+    RequireRole(
+        AuthUser {
+            user_id: actor_id, ..
+        },
+        _,
+    ): RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path((user_id, role)): Path<(Uuid, String)>,
+) -> AppResult<StatusCode> {
+    ctx.svc
+        .revoke_role(actor_id, user_id, parse_role(&role)?)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{user_id}/deactivate",
+    params(("user_id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 204, description = "Deactivated; all sessions revoked"),
+        (status = 400, description = "Cannot deactivate your own account"),
+        (status = 404, description = "Not found")
+    ),
+    tag = "admin",
+    security(("bearerAuth" = []))
+)]
+async fn deactivate_user(
+    RequireRole(
+        AuthUser {
+            user_id: actor_id, ..
+        },
+        _,
+    ): RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(user_id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    ctx.svc.deactivate(actor_id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{user_id}/reactivate",
+    params(("user_id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 204, description = "Reactivated"),
+        (status = 404, description = "Not found")
+    ),
+    tag = "admin",
+    security(("bearerAuth" = []))
+)]
+async fn reactivate_user(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(user_id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    ctx.svc.reactivate(user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn admin_router(pool: PgPool) -> Router {
+    let cfg = AppConfig::from_env();
+    let jwt = jwt_keys_from_config(&cfg);
+    let ctx = Ctx::new(pool.clone(), &cfg);
+    Router::new()
+        .route("/admin/users", get(list_users))
+        .route("/admin/users/{user_id}", get(get_user))
+        .route("/admin/users/{user_id}/roles", post(grant_role))
+        .route("/admin/users/{user_id}/roles/{role}", delete(revoke_role))
+        .route("/admin/users/{user_id}/deactivate", post(deactivate_user))
+        .route("/admin/users/{user_id}/reactivate", post(reactivate_user))
+        .with_state(ctx)
+        .layer(Extension(jwt))
+        .layer(Extension(pool))
+}
+
+/// Create the first admin account from the command line.
+pub async fn bootstrap_admin(pool: PgPool, input: NewAdminInput) -> AppResult<Uuid> {
+    let cfg = AppConfig::from_env();
+    Ctx::new(pool, &cfg).svc.bootstrap_admin(input).await
+}
+
+#[derive(OpenApi, Default)]
+#[openapi(
+    paths(
+        list_users,
+        get_user,
+        grant_role,
+        revoke_role,
+        deactivate_user,
+        reactivate_user,
+    ),
+    components(
+        schemas(
+            AdminUserPage,
+            AdminUserSummary,
+            AdminUserDetail,
+            PatientProfileInfo,
+            DoctorProfileInfo,
+            RoleReq
+        )
+    ),
+    modifiers(&SecurityAddon),
+    tags((name = "admin", description = "User administration APIs"))
+)]
+pub struct AdminApiDoc;
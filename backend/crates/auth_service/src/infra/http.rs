@@ -1,41 +1,112 @@
 use axum::{
     Extension, Json, Router,
-    extract::State,
-    http::StatusCode,
-    routing::{get, post},
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{delete, get, patch, post, put},
 };
 use common::{
-    auth::{AuthUser, JwtKeys, Role, ensure_user_role, issue_jwt},
+    auth::{
+        Admin, AuthUser, Doctor, JwtKeys, Patient, RequireRole, Strict, issue_jwt,
+        jwt_keys_from_config,
+    },
     config::AppConfig,
     error::AppResult,
+    notify::notifier_from_config,
 };
+use jsonwebtoken::jwk::JwkSet;
 use sqlx::PgPool;
+use std::net::{IpAddr, SocketAddr};
+use time::Duration;
 use utoipa::OpenApi;
+use uuid::Uuid;
 
 use super::repo_sqlx::SqlxAuthRepo;
 use crate::{
     app::AuthService,
     domain::{
-        AccessTokenResp, DoctorProfileResp, DoctorSignupReq, LoginDoctorReq, LoginPatientReq,
-        MedicalRightItem, PatientProfileResp, PatientSignupReq,
+        AccessTokenResp, AdminUpdateProfileReq, AssignMedicalRightReq, ChangePasswordReq,
+        ContactKind, DoctorProfileResp, DoctorSignupReq, ForgotPasswordReq, LoginAdminReq,
+        LoginDoctorReq, LoginOutcome, LoginPatientReq, LoginResp, MedicalRightCoverage,
+        MedicalRightItem, MedicalRightRequestView, MedicalRightStatus, PatientMedicalRight,
+        PatientProfileResp, PatientSignupReq, RecoveryCodesResp, RefreshTokenReq,
+        RejectMedicalRightReq, RequestMedicalRightReq, ResetPasswordReq, SessionTokens,
+        TotpCodeReq, TotpEnrollmentResp, TwoFactorChallengeResp, TwoFactorLoginReq,
+        UpdateDoctorProfileReq, UpdatePatientProfileReq, VerificationCodeReq,
     },
 };
 
-const ACCESS_TOKEN_TTL_MINUTES: i64 = 60 * 24;
-
 #[derive(Clone)]
 pub struct Ctx {
     svc: AuthService<SqlxAuthRepo>,
     jwt: JwtKeys,
-    pool: PgPool,
+    access_token_ttl_minutes: i64,
+    refresh_token_ttl: Duration,
+    trust_proxy_headers: bool,
 }
 impl Ctx {
-    pub fn new(pool: PgPool, jwt: JwtKeys) -> Self {
+    pub fn new(pool: PgPool, jwt: JwtKeys, cfg: &AppConfig) -> Self {
         Self {
-            svc: AuthService::new(SqlxAuthRepo::new(pool.clone())),
+            svc: AuthService::new(SqlxAuthRepo::new(pool), notifier_from_config(cfg), cfg),
             jwt,
-            pool,
+            access_token_ttl_minutes: cfg.access_token_ttl_minutes,
+            refresh_token_ttl: Duration::days(cfg.refresh_token_ttl_days),
+            trust_proxy_headers: cfg.trust_proxy_headers,
+        }
+    }
+
+    /// Client address used for login throttling: the first `X-Forwarded-For`
+    /// hop when proxy headers are trusted, otherwise the socket peer.
+    fn client_ip(&self, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+        if self.trust_proxy_headers
+            && let Some(ip) = headers
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .and_then(|v| v.trim().parse().ok())
+        {
+            return ip;
         }
+        peer.ip()
+    }
+
+    async fn start_session(&self, user_id: Uuid) -> AppResult<AccessTokenResp> {
+        let tokens = self
+            .svc
+            .start_session(user_id, self.refresh_token_ttl)
+            .await?;
+        self.token_resp(tokens).await
+    }
+
+    async fn login_resp(&self, outcome: LoginOutcome) -> AppResult<LoginResp> {
+        Ok(match outcome {
+            LoginOutcome::Authenticated(user_id) => {
+                LoginResp::Tokens(self.start_session(user_id).await?)
+            }
+            LoginOutcome::ChallengeIssued {
+                challenge_token,
+                expires_in,
+            } => LoginResp::TwoFactorChallenge(TwoFactorChallengeResp {
+                two_factor_required: true,
+                challenge_token,
+                expires_in,
+            }),
+        })
+    }
+
+    async fn token_resp(&self, tokens: SessionTokens) -> AppResult<AccessTokenResp> {
+        let roles = self.svc.access_token_roles(tokens.user_id).await?;
+        let access_token = issue_jwt(
+            tokens.user_id,
+            tokens.session_id,
+            roles,
+            &self.jwt,
+            self.access_token_ttl_minutes,
+        )?;
+        Ok(AccessTokenResp {
+            access_token,
+            refresh_token: tokens.refresh_token,
+            expires_in: self.access_token_ttl_minutes * 60,
+        })
     }
 }
 
@@ -51,31 +122,31 @@ async fn create_patient(
     Json(req): Json<PatientSignupReq>,
 ) -> AppResult<(StatusCode, Json<AccessTokenResp>)> {
     let user_id = ctx.svc.create_patient(req.into()).await?;
-    let token = issue_jwt(user_id, &ctx.jwt, ACCESS_TOKEN_TTL_MINUTES)?;
-    Ok((
-        StatusCode::CREATED,
-        Json(AccessTokenResp {
-            access_token: token,
-        }),
-    ))
+    let tokens = ctx.start_session(user_id).await?;
+    Ok((StatusCode::CREATED, Json(tokens)))
 }
 
 #[utoipa::path(
     post,
     path = "/login/patients",
     request_body = LoginPatientReq,
-    responses((status = 200, description = "OK", body = AccessTokenResp)),
+    responses(
+        (status = 200, description = "Tokens, or a challenge when two-factor authentication is enabled", body = LoginResp),
+        (status = 401, description = "Invalid credentials"),
+        (status = 423, description = "Account temporarily locked"),
+        (status = 429, description = "Too many attempts; see Retry-After")
+    ),
     tag = "auth"
 )]
 async fn login_patient(
     State(ctx): State<Ctx>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(req): Json<LoginPatientReq>,
-) -> AppResult<Json<AccessTokenResp>> {
-    let user_id = ctx.svc.login_patient(req.into()).await?;
-    let token = issue_jwt(user_id, &ctx.jwt, ACCESS_TOKEN_TTL_MINUTES)?;
-    Ok(Json(AccessTokenResp {
-        access_token: token,
-    }))
+) -> AppResult<Json<LoginResp>> {
+    let client_ip = ctx.client_ip(&headers, peer);
+    let outcome = ctx.svc.login_patient(req.into(), client_ip).await?;
+    Ok(Json(ctx.login_resp(outcome).await?))
 }
 
 #[utoipa::path(
@@ -87,11 +158,10 @@ async fn login_patient(
     security(("bearerAuth" = []))
 )]
 async fn upsert_medical_rights(
-    AuthUser { user_id, .. }: AuthUser,
+    _: RequireRole<Admin, Strict>,
     State(ctx): State<Ctx>,
     Json(items): Json<Vec<MedicalRightItem>>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Admin).await?;
     let upserts = items.into_iter().map(Into::into).collect();
     ctx.svc.upsert_medical_rights(upserts).await?;
     Ok(StatusCode::NO_CONTENT)
@@ -99,21 +169,268 @@ async fn upsert_medical_rights(
 
 #[utoipa::path(
     get,
-    path = "/medical-rights",
-    responses((status = 200, description = "User medical rights", body = [MedicalRightItem])),
+    path = "/medical-rights/{mr_id}/coverage",
+    params(("mr_id" = i32, Path, description = "Medical right")),
+    responses(
+        (status = 200, description = "Coverage rules", body = MedicalRightCoverage),
+        (status = 404, description = "Not found")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn get_medical_right_coverage(
+    _: AuthUser,
+    State(ctx): State<Ctx>,
+    Path(mr_id): Path<i32>,
+) -> AppResult<Json<MedicalRightCoverage>> {
+    Ok(Json(ctx.svc.medical_right_coverage(mr_id).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/medical-rights/{mr_id}/coverage",
+    params(("mr_id" = i32, Path, description = "Medical right")),
+    request_body = MedicalRightCoverage,
+    responses(
+        (status = 204, description = "Coverage rules replaced"),
+        (status = 400, description = "Invalid percentage, cap or medicine"),
+        (status = 404, description = "Not found")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn set_medical_right_coverage(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(mr_id): Path<i32>,
+    Json(req): Json<MedicalRightCoverage>,
+) -> AppResult<StatusCode> {
+    ctx.svc.set_medical_right_coverage(mr_id, req).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/medical-rights",
+    responses((status = 200, description = "Approved medical rights valid today", body = [MedicalRightItem])),
     tag = "auth",
     security(("bearerAuth" = []))
 )]
 async fn get_my_medical_rights(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<Vec<MedicalRightItem>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
-
     let items = ctx.svc.user_medical_rights(user_id).await?;
     Ok(Json(items))
 }
 
+#[utoipa::path(
+    get,
+    path = "/me/medical-rights/requests",
+    responses((status = 200, description = "Own medical rights in every state", body = [PatientMedicalRight])),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn list_my_medical_right_requests(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+) -> AppResult<Json<Vec<PatientMedicalRight>>> {
+    Ok(Json(ctx.svc.patient_medical_rights(user_id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/medical-rights/requests",
+    request_body = RequestMedicalRightReq,
+    responses(
+        (status = 201, description = "Requested; pending approval"),
+        (status = 404, description = "Unknown medical right"),
+        (status = 409, description = "Already assigned or pending")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn request_medical_right(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<RequestMedicalRightReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc
+        .request_medical_right(user_id, req.mr_id, req.into())
+        .await?;
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/me/medical-rights/requests/{mr_id}",
+    params(("mr_id" = i32, Path, description = "Medical right")),
+    responses(
+        (status = 204, description = "Request withdrawn"),
+        (status = 404, description = "No pending request")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn withdraw_medical_right_request(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+    Path(mr_id): Path<i32>,
+) -> AppResult<StatusCode> {
+    ctx.svc
+        .withdraw_medical_right_request(user_id, mr_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/medical-rights/requests",
+    responses((status = 200, description = "Requests awaiting review, oldest first", body = [MedicalRightRequestView])),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn list_pending_medical_right_requests(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+) -> AppResult<Json<Vec<MedicalRightRequestView>>> {
+    Ok(Json(ctx.svc.pending_medical_right_requests().await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{user_id}/medical-rights",
+    params(("user_id" = Uuid, Path, description = "Patient")),
+    responses((status = 200, description = "Patient's medical rights in every state", body = [PatientMedicalRight])),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn list_patient_medical_rights(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(user_id): Path<Uuid>,
+) -> AppResult<Json<Vec<PatientMedicalRight>>> {
+    Ok(Json(ctx.svc.patient_medical_rights(user_id).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/{user_id}/medical-rights/{mr_id}",
+    params(
+        ("user_id" = Uuid, Path, description = "Patient"),
+        ("mr_id" = i32, Path, description = "Medical right")
+    ),
+    request_body = AssignMedicalRightReq,
+    responses(
+        (status = 204, description = "Assigned and approved"),
+        (status = 400, description = "Invalid validity window"),
+        (status = 404, description = "Unknown patient or medical right")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn assign_medical_right(
+    RequireRole(
+        AuthUser {
+            user_id: admin_id, ..
+        },
+        _,
+    ): RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path((user_id, mr_id)): Path<(Uuid, i32)>,
+    Json(req): Json<AssignMedicalRightReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc
+        .assign_medical_right(admin_id, user_id, mr_id, req.into())
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{user_id}/medical-rights/{mr_id}",
+    params(
+        ("user_id" = Uuid, Path, description = "Patient"),
+        ("mr_id" = i32, Path, description = "Medical right")
+    ),
+    responses(
+        (status = 204, description = "Detached"),
+        (status = 404, description = "Not assigned")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn unassign_medical_right(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path((user_id, mr_id)): Path<(Uuid, i32)>,
+) -> AppResult<StatusCode> {
+    ctx.svc.unassign_medical_right(user_id, mr_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/{user_id}/medical-rights/{mr_id}/approve",
+    params(
+        ("user_id" = Uuid, Path, description = "Patient"),
+        ("mr_id" = i32, Path, description = "Medical right")
+    ),
+    responses(
+        (status = 204, description = "Approved"),
+        (status = 404, description = "No pending request")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn approve_medical_right_request(
+    RequireRole(
+        AuthUser {
+            user_id: admin_id, ..
+        },
+        _,
+    ): RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path((user_id, mr_id)): Path<(Uuid, i32)>,
+) -> AppResult<StatusCode> {
+    ctx.svc
+        .review_medical_right_request(admin_id, user_id, mr_id, true, None)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/{user_id}/medical-rights/{mr_id}/reject",
+    params(
+        ("user_id" = Uuid, Path, description = "Patient"),
+        ("mr_id" = i32, Path, description = "Medical right")
+    ),
+    request_body = RejectMedicalRightReq,
+    responses(
+        (status = 204, description = "Rejected"),
+        (status = 404, description = "No pending request")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn reject_medical_right_request(
+    RequireRole(
+        AuthUser {
+            user_id: admin_id, ..
+        },
+        _,
+    ): RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path((user_id, mr_id)): Path<(Uuid, i32)>,
+    Json(req): Json<RejectMedicalRightReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc
+        .review_medical_right_request(admin_id, user_id, mr_id, false, req.reason)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[utoipa::path(
     post,
     path = "/doctors",
@@ -126,48 +443,296 @@ async fn create_doctor(
     Json(req): Json<DoctorSignupReq>,
 ) -> AppResult<(StatusCode, Json<AccessTokenResp>)> {
     let user_id = ctx.svc.create_doctor(req.into()).await?;
-    let token = issue_jwt(user_id, &ctx.jwt, ACCESS_TOKEN_TTL_MINUTES)?;
-    Ok((
-        StatusCode::CREATED,
-        Json(AccessTokenResp {
-            access_token: token,
-        }),
-    ))
+    let tokens = ctx.start_session(user_id).await?;
+    Ok((StatusCode::CREATED, Json(tokens)))
 }
 
 #[utoipa::path(
     post,
     path = "/login/doctors",
     request_body = LoginDoctorReq,
-    responses((status = 200, description = "OK", body = AccessTokenResp)),
+    responses(
+        (status = 200, description = "Tokens, or a challenge when two-factor authentication is enabled", body = LoginResp),
+        (status = 401, description = "Invalid credentials"),
+        (status = 423, description = "Account temporarily locked"),
+        (status = 429, description = "Too many attempts; see Retry-After")
+    ),
     tag = "auth"
 )]
 async fn login_doctor(
     State(ctx): State<Ctx>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(req): Json<LoginDoctorReq>,
+) -> AppResult<Json<LoginResp>> {
+    let client_ip = ctx.client_ip(&headers, peer);
+    let outcome = ctx.svc.login_doctor(req.into(), client_ip).await?;
+    Ok(Json(ctx.login_resp(outcome).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login/admins",
+    request_body = LoginAdminReq,
+    responses(
+        (status = 200, description = "Tokens, or a challenge when two-factor authentication is enabled", body = LoginResp),
+        (status = 401, description = "Invalid credentials"),
+        (status = 403, description = "Account deactivated"),
+        (status = 423, description = "Account temporarily locked"),
+        (status = 429, description = "Too many attempts; see Retry-After")
+    ),
+    tag = "auth"
+)]
+async fn login_admin(
+    State(ctx): State<Ctx>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<LoginAdminReq>,
+) -> AppResult<Json<LoginResp>> {
+    let client_ip = ctx.client_ip(&headers, peer);
+    let outcome = ctx.svc.login_admin(req.into(), client_ip).await?;
+    Ok(Json(ctx.login_resp(outcome).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login/2fa",
+    request_body = TwoFactorLoginReq,
+    responses(
+        (status = 200, description = "OK", body = AccessTokenResp),
+        (status = 401, description = "Invalid or expired challenge, or wrong code"),
+        (status = 423, description = "Account temporarily locked")
+    ),
+    tag = "auth"
+)]
+async fn login_two_factor(
+    State(ctx): State<Ctx>,
+    Json(req): Json<TwoFactorLoginReq>,
 ) -> AppResult<Json<AccessTokenResp>> {
-    let user_id = ctx.svc.login_doctor(req.into()).await?;
-    let token = issue_jwt(user_id, &ctx.jwt, ACCESS_TOKEN_TTL_MINUTES)?;
-    Ok(Json(AccessTokenResp {
-        access_token: token,
-    }))
+    let user_id = ctx
+        .svc
+        .complete_two_factor_login(&req.challenge_token, &req.code)
+        .await?;
+    let tokens = ctx.start_session(user_id).await?;
+    Ok(Json(tokens))
 }
 
 #[utoipa::path(
     post,
-    path = "/refresh",
-    responses((status = 200, description = "Refreshed access token", body = AccessTokenResp)),
+    path = "/2fa/enroll",
+    responses(
+        (status = 200, description = "Pending enrollment; confirm with a code", body = TotpEnrollmentResp),
+        (status = 403, description = "Only doctors and admins can enroll"),
+        (status = 409, description = "Two-factor authentication already enabled")
+    ),
     tag = "auth",
     security(("bearerAuth" = []))
 )]
-async fn refresh_access_token(
+async fn enroll_totp(
+    AuthUser { user_id, .. }: AuthUser,
+    State(ctx): State<Ctx>,
+) -> AppResult<Json<TotpEnrollmentResp>> {
+    Ok(Json(ctx.svc.enroll_totp(user_id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/2fa/confirm",
+    request_body = TotpCodeReq,
+    responses(
+        (status = 200, description = "Enabled; refresh to pick up 2FA-gated roles", body = RecoveryCodesResp),
+        (status = 400, description = "No pending enrollment or invalid code")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn confirm_totp(
     AuthUser { user_id, .. }: AuthUser,
     State(ctx): State<Ctx>,
+    Json(req): Json<TotpCodeReq>,
+) -> AppResult<Json<RecoveryCodesResp>> {
+    let recovery_codes = ctx.svc.confirm_totp(user_id, &req.code).await?;
+    Ok(Json(RecoveryCodesResp { recovery_codes }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/2fa/recovery-codes",
+    request_body = TotpCodeReq,
+    responses(
+        (status = 200, description = "Previous recovery codes replaced", body = RecoveryCodesResp),
+        (status = 400, description = "Invalid code")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn regenerate_recovery_codes(
+    AuthUser { user_id, .. }: AuthUser,
+    State(ctx): State<Ctx>,
+    Json(req): Json<TotpCodeReq>,
+) -> AppResult<Json<RecoveryCodesResp>> {
+    let recovery_codes = ctx
+        .svc
+        .regenerate_recovery_codes(user_id, &req.code)
+        .await?;
+    Ok(Json(RecoveryCodesResp { recovery_codes }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/2fa/disable",
+    request_body = TotpCodeReq,
+    responses(
+        (status = 204, description = "Two-factor authentication disabled"),
+        (status = 400, description = "Invalid code"),
+        (status = 403, description = "Required for one of the user's roles")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn disable_totp(
+    AuthUser { user_id, .. }: AuthUser,
+    State(ctx): State<Ctx>,
+    Json(req): Json<TotpCodeReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc.disable_totp(user_id, &req.code).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/{user_id}/unlock",
+    params(("user_id" = Uuid, Path, description = "Account to unlock")),
+    responses((status = 204, description = "Lockout and failed attempts cleared")),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn unlock_account(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(user_id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    ctx.svc.unlock_account(user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/password/change",
+    request_body = ChangePasswordReq,
+    responses(
+        (status = 204, description = "Password changed; other sessions revoked"),
+        (status = 400, description = "Wrong current password or weak new password"),
+        (status = 423, description = "Account temporarily locked"),
+        (status = 429, description = "Too many attempts; see Retry-After")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn change_password(
+    user: AuthUser,
+    State(ctx): State<Ctx>,
+    Json(req): Json<ChangePasswordReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc
+        .change_password(
+            user.user_id,
+            user.session_id,
+            &req.old_password,
+            &req.new_password,
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/password/forgot",
+    request_body = ForgotPasswordReq,
+    responses((status = 202, description = "Reset token sent if the account exists")),
+    tag = "auth"
+)]
+async fn forgot_password(
+    State(ctx): State<Ctx>,
+    Json(req): Json<ForgotPasswordReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc.request_password_reset(req.email, req.phone).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/password/reset",
+    request_body = ResetPasswordReq,
+    responses(
+        (status = 204, description = "Password reset; all sessions revoked"),
+        (status = 400, description = "Invalid or expired token, or weak password")
+    ),
+    tag = "auth"
+)]
+async fn reset_password(
+    State(ctx): State<Ctx>,
+    Json(req): Json<ResetPasswordReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc
+        .reset_password(&req.token, &req.new_password)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/refresh",
+    request_body = RefreshTokenReq,
+    responses(
+        (status = 200, description = "Rotated token pair", body = AccessTokenResp),
+        (status = 401, description = "Refresh token invalid, expired or reused"),
+    ),
+    tag = "auth"
+)]
+async fn refresh_access_token(
+    State(ctx): State<Ctx>,
+    Json(req): Json<RefreshTokenReq>,
 ) -> AppResult<Json<AccessTokenResp>> {
-    let token = issue_jwt(user_id, &ctx.jwt, ACCESS_TOKEN_TTL_MINUTES)?;
-    Ok(Json(AccessTokenResp {
-        access_token: token,
-    }))
+    let tokens = ctx
+        .svc
+        .refresh_session(&req.refresh_token, ctx.refresh_token_ttl)
+        .await?;
+    Ok(Json(ctx.token_resp(tokens).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/logout",
+    responses((status = 204, description = "Current session revoked")),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn logout(
+    AuthUser {
+        user_id,
+        session_id,
+        ..
+    }: AuthUser,
+    State(ctx): State<Ctx>,
+) -> AppResult<StatusCode> {
+    ctx.svc.logout(user_id, session_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/logout/all",
+    responses((status = 204, description = "All sessions of the user revoked")),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn logout_all(
+    AuthUser { user_id, .. }: AuthUser,
+    State(ctx): State<Ctx>,
+) -> AppResult<StatusCode> {
+    ctx.svc.logout_all(user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[utoipa::path(
@@ -178,11 +743,9 @@ async fn refresh_access_token(
     security(("bearerAuth" = []))
 )]
 async fn get_doctor_profile(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<DoctorProfileResp>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-
     let profile = ctx.svc.doctor_profile(user_id).await?;
     Ok(Json(profile))
 }
@@ -195,31 +758,215 @@ async fn get_doctor_profile(
     security(("bearerAuth" = []))
 )]
 async fn get_patient_profile(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<PatientProfileResp>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
-
     let profile = ctx.svc.patient_profile(user_id).await?;
     Ok(Json(profile))
 }
 
+#[utoipa::path(
+    patch,
+    path = "/patient/profiles",
+    request_body = UpdatePatientProfileReq,
+    responses(
+        (status = 200, description = "Updated profile", body = PatientProfileResp),
+        (status = 400, description = "Invalid field value"),
+        (status = 409, description = "Email or phone already in use")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn update_patient_profile(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<UpdatePatientProfileReq>,
+) -> AppResult<Json<PatientProfileResp>> {
+    let profile = ctx.svc.update_patient_profile(user_id, req.into()).await?;
+    Ok(Json(profile))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/doctor/profiles",
+    request_body = UpdateDoctorProfileReq,
+    responses(
+        (status = 200, description = "Updated profile", body = DoctorProfileResp),
+        (status = 400, description = "Invalid field value"),
+        (status = 409, description = "Email or phone already in use")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn update_doctor_profile(
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor>,
+    State(ctx): State<Ctx>,
+    Json(req): Json<UpdateDoctorProfileReq>,
+) -> AppResult<Json<DoctorProfileResp>> {
+    let profile = ctx.svc.update_doctor_profile(user_id, req.into()).await?;
+    Ok(Json(profile))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/{user_id}/profile",
+    params(("user_id" = Uuid, Path, description = "User to edit")),
+    request_body = AdminUpdateProfileReq,
+    responses(
+        (status = 204, description = "Updated"),
+        (status = 400, description = "Invalid field value, or doctor fields for a non-doctor"),
+        (status = 404, description = "Unknown user"),
+        (status = 409, description = "Email or phone already in use")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn admin_update_profile(
+    _: RequireRole<Admin, Strict>,
+    State(ctx): State<Ctx>,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<AdminUpdateProfileReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc.update_profile(user_id, req.into()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/verify/{kind}",
+    params(("kind" = ContactKind, Path, description = "`email` or `phone`")),
+    responses(
+        (status = 202, description = "Code sent"),
+        (status = 400, description = "No such contact detail on file"),
+        (status = 409, description = "Already verified"),
+        (status = 429, description = "A code was sent moments ago; see Retry-After")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn request_contact_verification(
+    AuthUser { user_id, .. }: AuthUser,
+    State(ctx): State<Ctx>,
+    Path(kind): Path<ContactKind>,
+) -> AppResult<StatusCode> {
+    ctx.svc.request_contact_verification(user_id, kind).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/verify/{kind}/confirm",
+    params(("kind" = ContactKind, Path, description = "`email` or `phone`")),
+    request_body = VerificationCodeReq,
+    responses(
+        (status = 204, description = "Verified"),
+        (status = 400, description = "Wrong, expired or exhausted code")
+    ),
+    tag = "auth",
+    security(("bearerAuth" = []))
+)]
+async fn confirm_contact_verification(
+    AuthUser { user_id, .. }: AuthUser,
+    State(ctx): State<Ctx>,
+    Path(kind): Path<ContactKind>,
+    Json(req): Json<VerificationCodeReq>,
+) -> AppResult<StatusCode> {
+    ctx.svc
+        .confirm_contact_verification(user_id, kind, &req.code)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub fn router(pool: PgPool) -> Router {
     let cfg = AppConfig::from_env();
-    let jwt = common::auth::JwtKeys::from_secret(&cfg.jwt_secret);
-    let ctx = Ctx::new(pool, jwt.clone());
+    let jwt = jwt_keys_from_config(&cfg);
+    let ctx = Ctx::new(pool.clone(), jwt.clone(), &cfg);
     Router::new()
         .route("/users/patients", post(create_patient))
         .route("/users/login/patients", post(login_patient))
-        .route("/users/patient/profiles", get(get_patient_profile))
+        .route(
+            "/users/patient/profiles",
+            get(get_patient_profile).patch(update_patient_profile),
+        )
         .route("/users/me/medical-rights", get(get_my_medical_rights))
+        .route(
+            "/users/me/medical-rights/requests",
+            get(list_my_medical_right_requests).post(request_medical_right),
+        )
+        .route(
+            "/users/me/medical-rights/requests/{mr_id}",
+            delete(withdraw_medical_right_request),
+        )
+        .route(
+            "/users/me/verify/{kind}",
+            post(request_contact_verification),
+        )
+        .route(
+            "/users/me/verify/{kind}/confirm",
+            post(confirm_contact_verification),
+        )
         .route("/users/doctors", post(create_doctor))
         .route("/users/login/doctors", post(login_doctor))
-        .route("/users/doctor/profiles", get(get_doctor_profile))
+        .route(
+            "/users/doctor/profiles",
+            get(get_doctor_profile).patch(update_doctor_profile),
+        )
         .route("/users/medical-rights", post(upsert_medical_rights))
+        .route(
+            "/users/medical-rights/requests",
+            get(list_pending_medical_right_requests),
+        )
+        .route(
+            "/users/medical-rights/{mr_id}/coverage",
+            get(get_medical_right_coverage).put(set_medical_right_coverage),
+        )
+        .route(
+            "/users/{user_id}/medical-rights",
+            get(list_patient_medical_rights),
+        )
+        .route(
+            "/users/{user_id}/medical-rights/{mr_id}",
+            put(assign_medical_right).delete(unassign_medical_right),
+        )
+        .route(
+            "/users/{user_id}/medical-rights/{mr_id}/approve",
+            post(approve_medical_right_request),
+        )
+        .route(
+            "/users/{user_id}/medical-rights/{mr_id}/reject",
+            post(reject_medical_right_request),
+        )
+        .route("/users/login/admins", post(login_admin))
+        .route("/users/login/2fa", post(login_two_factor))
+        .route("/users/2fa/enroll", post(enroll_totp))
+        .route("/users/2fa/confirm", post(confirm_totp))
+        .route("/users/2fa/recovery-codes", post(regenerate_recovery_codes))
+        .route("/users/2fa/disable", post(disable_totp))
+        .route("/users/{user_id}/profile", patch(admin_update_profile))
+        .route("/users/{user_id}/unlock", post(unlock_account))
+        .route("/users/password/change", post(change_password))
+        .route("/users/password/forgot", post(forgot_password))
+        .route("/users/password/reset", post(reset_password))
         .route("/users/refresh", post(refresh_access_token))
+        .route("/users/logout", post(logout))
+        .route("/users/logout/all", post(logout_all))
         .with_state(ctx)
         .layer(Extension(jwt))
+        .layer(Extension(pool))
+}
+
+/// `GET /.well-known/jwks.json`: public keys that currently verify access tokens.
+async fn jwks(Extension(jwt): Extension<JwtKeys>) -> Json<JwkSet> {
+    Json(jwt.jwks())
+}
+
+/// Routes served outside the `/api` prefix.
+pub fn well_known_router() -> Router {
+    let cfg = AppConfig::from_env();
+    let jwt = jwt_keys_from_config(&cfg);
+    Router::new()
+        .route("/.well-known/jwks.json", get(jwks))
+        .layer(Extension(jwt))
 }
 
 #[derive(OpenApi, Default)]
@@ -228,23 +975,74 @@ pub fn router(pool: PgPool) -> Router {
         create_patient,
         login_patient,
         get_patient_profile,
+        update_patient_profile,
         get_my_medical_rights,
+        list_my_medical_right_requests,
+        request_medical_right,
+        withdraw_medical_right_request,
+        request_contact_verification,
+        confirm_contact_verification,
         create_doctor,
         login_doctor,
         get_doctor_profile,
+        update_doctor_profile,
+        admin_update_profile,
         upsert_medical_rights,
+        get_medical_right_coverage,
+        set_medical_right_coverage,
+        list_pending_medical_right_requests,
+        list_patient_medical_rights,
+        assign_medical_right,
+        unassign_medical_right,
+        approve_medical_right_request,
+        reject_medical_right_request,
+        login_admin,
+        login_two_factor,
+        enroll_totp,
+        confirm_totp,
+        regenerate_recovery_codes,
+        disable_totp,
+        unlock_account,
+        change_password,
+        forgot_password,
+        reset_password,
         refresh_access_token,
+        logout,
+        logout_all,
     ),
     components(
         schemas(
             PatientSignupReq,
             LoginPatientReq,
             MedicalRightItem,
+            MedicalRightCoverage,
+            MedicalRightStatus,
+            PatientMedicalRight,
+            MedicalRightRequestView,
+            AssignMedicalRightReq,
+            RequestMedicalRightReq,
+            RejectMedicalRightReq,
             DoctorSignupReq,
             LoginDoctorReq,
+            LoginAdminReq,
             AccessTokenResp,
+            RefreshTokenReq,
+            ChangePasswordReq,
+            ForgotPasswordReq,
+            ResetPasswordReq,
+            LoginResp,
+            TwoFactorChallengeResp,
+            TwoFactorLoginReq,
+            TotpEnrollmentResp,
+            TotpCodeReq,
+            RecoveryCodesResp,
             DoctorProfileResp,
-            PatientProfileResp
+            PatientProfileResp,
+            UpdatePatientProfileReq,
+            UpdateDoctorProfileReq,
+            AdminUpdateProfileReq,
+            ContactKind,
+            VerificationCodeReq
         )
     ),
     modifiers(&SecurityAddon),
@@ -1,2 +1,3 @@
+pub mod admin_http;
 pub mod http;
 pub mod repo_sqlx;
@@ -1,17 +1,33 @@
 use crate::{
-    app::AuthRepo,
+    app::{AdminRepo, AuthRepo},
     domain::{
-        DoctorLoginInput, DoctorProfileResp, DoctorSignupInput, MedicalRightItem,
-        MedicalRightUpsert, PatientLoginInput, PatientProfileResp, PatientSignupInput,
+        AdminUserDetail, AdminUserSummary, ContactAddress, ContactKind, DoctorProfileInfo,
+        DoctorProfileResp, DoctorSignupInput, LoginFailures, MedicalRightCoverage,
+        MedicalRightItem, MedicalRightRequestView, MedicalRightStatus, MedicalRightTerms,
+        MedicalRightUpsert, NewAdminInput, PatientMedicalRight, PatientProfileInfo,
+        PatientProfileResp, PatientSignupInput, PendingVerification, ProfileUpdate,
+        RefreshRotation, StoredCredentials, ThrottleKey, TotpEnrollment, UserSearch,
     },
 };
 use common::{
+    auth::{Role, user_roles},
     error::{AppError, AppResult},
-    password::{hash_password, verify_password},
+    password::hash_password,
 };
+use db::PgTx;
 use sqlx::PgPool;
+use time::{Date, OffsetDateTime};
 use uuid::Uuid;
 
+/// Unique constraints on `users` reported to clients by field name.
+const USER_UNIQUE_FIELDS: &[(&str, &str)] = &[
+    ("users_email_key", "email"),
+    ("users_phone_key", "phone"),
+    ("users_citizen_id_key", "citizen_id"),
+    ("patient_profile_hn_key", "hn"),
+    ("doctor_profile_mln_key", "mln"),
+];
+
 #[derive(Clone)]
 pub struct SqlxAuthRepo {
     pool: PgPool,
@@ -46,7 +62,8 @@ impl AuthRepo for SqlxAuthRepo {
             email,
         )
         .fetch_one(&mut *tx)
-        .await?;
+        .await
+        .map_err(|e| AppError::on_unique_violation(e, USER_UNIQUE_FIELDS))?;
         sqlx::query!(
             r#"INSERT INTO patient_profile (user_id, hn) VALUES ($1,$2)
                ON CONFLICT (user_id) DO UPDATE SET hn = EXCLUDED.hn"#,
@@ -54,7 +71,8 @@ impl AuthRepo for SqlxAuthRepo {
             hn
         )
         .execute(&mut *tx)
-        .await?;
+        .await
+        .map_err(|e| AppError::on_unique_violation(e, USER_UNIQUE_FIELDS))?;
         sqlx::query!(
             r#"INSERT INTO user_roles (user_id, role) VALUES ($1,'PATIENT') ON CONFLICT DO NOTHING"#,
             user_id
@@ -63,14 +81,15 @@ impl AuthRepo for SqlxAuthRepo {
         Ok(user_id)
     }
 
-    async fn login_patient(&self, input: PatientLoginInput) -> AppResult<Uuid> {
-        let PatientLoginInput {
-            hn,
-            citizen_id,
-            password,
-        } = input;
-        let row = sqlx::query!(
-            r#"SELECT u.user_id, u.password
+    async fn patient_credentials(
+        &self,
+        hn: i32,
+        citizen_id: &str,
+    ) -> AppResult<Option<StoredCredentials>> {
+        let row = sqlx::query_as!(
+            StoredCredentials,
+            r#"SELECT u.user_id, u.password AS password_hash,
+                      u.deactivated_at IS NOT NULL AS "deactivated!"
                FROM users u JOIN patient_profile p ON p.user_id = u.user_id
                WHERE p.hn=$1 AND u.citizen_id=$2"#,
             hn,
@@ -78,14 +97,7 @@ impl AuthRepo for SqlxAuthRepo {
         )
         .fetch_optional(&self.pool)
         .await?;
-        let Some(r) = row else {
-            return Err(AppError::Unauthorized);
-        };
-        // NOTE: password is stored as plaintext in migrations; in real code hash+verify
-        if !verify_password(&password, &r.password)? {
-            return Err(AppError::Unauthorized);
-        }
-        Ok(r.user_id)
+        Ok(row)
     }
 
     async fn upsert_medical_rights(&self, items: Vec<MedicalRightUpsert>) -> AppResult<()> {
@@ -108,6 +120,68 @@ impl AuthRepo for SqlxAuthRepo {
         Ok(())
     }
 
+    async fn medical_right_coverage(&self, mr_id: i32) -> AppResult<Option<MedicalRightCoverage>> {
+        let row = sqlx::query_as!(
+            MedicalRightCoverage,
+            r#"
+                SELECT
+                    mr.copay_percent::float8 AS "copay_percent!",
+                    mr.order_cap::float8 AS order_cap,
+                    ARRAY(
+                        SELECT mrm.medicine_id FROM medical_right_medicines mrm
+                        WHERE mrm.mr_id = mr.mr_id ORDER BY mrm.medicine_id
+                    ) AS "covered_medicine_ids!"
+                FROM medical_rights mr
+                WHERE mr.mr_id = $1
+            "#,
+            mr_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    async fn set_medical_right_coverage(
+        &self,
+        mr_id: i32,
+        coverage: &MedicalRightCoverage,
+    ) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            r#"UPDATE medical_rights SET copay_percent = $2::float8, order_cap = $3::float8
+               WHERE mr_id = $1"#,
+            mr_id,
+            coverage.copay_percent,
+            coverage.order_cap
+        )
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        sqlx::query!(
+            "DELETE FROM medical_right_medicines WHERE mr_id = $1",
+            mr_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        let inserted = sqlx::query!(
+            r#"INSERT INTO medical_right_medicines (mr_id, medicine_id)
+               SELECT $1, m.medicine_id FROM medicines m WHERE m.medicine_id = ANY($2)"#,
+            mr_id,
+            &coverage.covered_medicine_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+        if inserted.rows_affected() != coverage.covered_medicine_ids.len() as u64 {
+            return Err(AppError::BadRequest(
+                "covered_medicine_ids contains an unknown medicine".into(),
+            ));
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
     async fn user_medical_rights(&self, user_id: Uuid) -> AppResult<Vec<MedicalRightItem>> {
         let rows = sqlx::query!(
             r#"
@@ -115,6 +189,9 @@ impl AuthRepo for SqlxAuthRepo {
                 FROM user_mr um
                 JOIN medical_rights mr ON mr.mr_id = um.mr_id
                 WHERE um.patient_id = $1
+                  AND um.status = 'APPROVED'
+                  AND (um.valid_from IS NULL OR um.valid_from <= current_date)
+                  AND (um.valid_until IS NULL OR um.valid_until >= current_date)
                 ORDER BY mr.name
             "#,
             user_id
@@ -133,6 +210,184 @@ impl AuthRepo for SqlxAuthRepo {
             .collect())
     }
 
+    async fn patient_medical_rights(
+        &self,
+        patient_id: Uuid,
+    ) -> AppResult<Vec<PatientMedicalRight>> {
+        let rows = sqlx::query_as!(
+            PatientMedicalRightRow,
+            r#"
+                SELECT
+                    um.mr_id,
+                    mr.name,
+                    um.policy_number,
+                    um.valid_from,
+                    um.valid_until,
+                    um.status,
+                    um.rejection_reason,
+                    um.requested_at
+                FROM user_mr um
+                JOIN medical_rights mr ON mr.mr_id = um.mr_id
+                WHERE um.patient_id = $1
+                ORDER BY um.requested_at DESC, mr.name
+            "#,
+            patient_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn patient_medical_right(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+    ) -> AppResult<Option<PatientMedicalRight>> {
+        let row = sqlx::query_as!(
+            PatientMedicalRightRow,
+            r#"
+                SELECT
+                    um.mr_id,
+                    mr.name,
+                    um.policy_number,
+                    um.valid_from,
+                    um.valid_until,
+                    um.status,
+                    um.rejection_reason,
+                    um.requested_at
+                FROM user_mr um
+                JOIN medical_rights mr ON mr.mr_id = um.mr_id
+                WHERE um.patient_id = $1 AND um.mr_id = $2
+            "#,
+            patient_id,
+            mr_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(Into::into))
+    }
+
+    async fn save_medical_right(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+        terms: &MedicalRightTerms,
+        status: MedicalRightStatus,
+        decided_by: Option<Uuid>,
+    ) -> AppResult<()> {
+        let res = sqlx::query!(
+            r#"
+                INSERT INTO user_mr (patient_id, mr_id, policy_number, valid_from, valid_until,
+                                     status, requested_at, decided_at, decided_by)
+                SELECT p.user_id, mr.mr_id, $3, $4, $5, $6, now(),
+                       CASE WHEN $7::uuid IS NULL THEN NULL ELSE now() END, $7
+                FROM patient_profile p, medical_rights mr
+                WHERE p.user_id = $1 AND mr.mr_id = $2
+                ON CONFLICT (patient_id, mr_id) DO UPDATE SET
+                    policy_number = EXCLUDED.policy_number,
+                    valid_from = EXCLUDED.valid_from,
+                    valid_until = EXCLUDED.valid_until,
+                    status = EXCLUDED.status,
+                    requested_at = EXCLUDED.requested_at,
+                    decided_at = EXCLUDED.decided_at,
+                    decided_by = EXCLUDED.decided_by,
+                    rejection_reason = NULL
+            "#,
+            patient_id,
+            mr_id,
+            terms.policy_number,
+            terms.valid_from,
+            terms.valid_until,
+            status.as_str(),
+            decided_by
+        )
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete_medical_right(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+        status: Option<MedicalRightStatus>,
+    ) -> AppResult<()> {
+        let res = sqlx::query!(
+            r#"DELETE FROM user_mr
+               WHERE patient_id = $1 AND mr_id = $2 AND ($3::text IS NULL OR status = $3)"#,
+            patient_id,
+            mr_id,
+            status.map(MedicalRightStatus::as_str)
+        )
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn pending_medical_right_requests(&self) -> AppResult<Vec<MedicalRightRequestView>> {
+        let rows = sqlx::query_as!(
+            MedicalRightRequestView,
+            r#"
+                SELECT
+                    um.patient_id,
+                    u.first_name || ' ' || u.last_name AS "patient_name!",
+                    p.hn,
+                    um.mr_id,
+                    mr.name,
+                    um.policy_number,
+                    um.valid_from,
+                    um.valid_until,
+                    um.requested_at
+                FROM user_mr um
+                JOIN medical_rights mr ON mr.mr_id = um.mr_id
+                JOIN users u ON u.user_id = um.patient_id
+                JOIN patient_profile p ON p.user_id = um.patient_id
+                WHERE um.status = 'PENDING'
+                ORDER BY um.requested_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    async fn review_medical_right(
+        &self,
+        patient_id: Uuid,
+        mr_id: i32,
+        approve: bool,
+        decided_by: Uuid,
+        reason: Option<String>,
+    ) -> AppResult<()> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_mr
+                SET status = CASE WHEN $3 THEN 'APPROVED' ELSE 'REJECTED' END,
+                    decided_at = now(),
+                    decided_by = $4,
+                    rejection_reason = CASE WHEN $3 THEN NULL ELSE $5 END
+                WHERE patient_id = $1 AND mr_id = $2 AND status = 'PENDING'
+            "#,
+            patient_id,
+            mr_id,
+            approve,
+            decided_by,
+            reason
+        )
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
     async fn create_doctor(&self, input: DoctorSignupInput) -> AppResult<Uuid> {
         let DoctorSignupInput {
             mln,
@@ -156,7 +411,8 @@ impl AuthRepo for SqlxAuthRepo {
             email,
         )
         .fetch_one(&mut *tx)
-        .await?;
+        .await
+        .map_err(|e| AppError::on_unique_violation(e, USER_UNIQUE_FIELDS))?;
         sqlx::query!(
             r#"INSERT INTO doctor_profile (user_id, mln) VALUES ($1,$2)
                ON CONFLICT (user_id) DO UPDATE SET mln = EXCLUDED.mln"#,
@@ -164,7 +420,8 @@ impl AuthRepo for SqlxAuthRepo {
             mln
         )
         .execute(&mut *tx)
-        .await?;
+        .await
+        .map_err(|e| AppError::on_unique_violation(e, USER_UNIQUE_FIELDS))?;
         sqlx::query!(
             r#"INSERT INTO user_roles (user_id, role) VALUES ($1,'DOCTOR') ON CONFLICT DO NOTHING"#,
             user_id
@@ -175,14 +432,15 @@ impl AuthRepo for SqlxAuthRepo {
         Ok(user_id)
     }
 
-    async fn login_doctor(&self, input: DoctorLoginInput) -> AppResult<Uuid> {
-        let DoctorLoginInput {
-            mln,
-            citizen_id,
-            password,
-        } = input;
-        let row = sqlx::query!(
-            r#"SELECT u.user_id, u.password
+    async fn doctor_credentials(
+        &self,
+        mln: &str,
+        citizen_id: &str,
+    ) -> AppResult<Option<StoredCredentials>> {
+        let row = sqlx::query_as!(
+            StoredCredentials,
+            r#"SELECT u.user_id, u.password AS password_hash,
+                      u.deactivated_at IS NOT NULL AS "deactivated!"
                FROM users u JOIN doctor_profile d ON d.user_id = u.user_id
                WHERE d.mln=$1 AND u.citizen_id=$2"#,
             mln,
@@ -190,13 +448,518 @@ impl AuthRepo for SqlxAuthRepo {
         )
         .fetch_optional(&self.pool)
         .await?;
-        let Some(r) = row else {
-            return Err(AppError::Unauthorized);
+        Ok(row)
+    }
+
+    async fn admin_credentials(&self, citizen_id: &str) -> AppResult<Option<StoredCredentials>> {
+        let row = sqlx::query_as!(
+            StoredCredentials,
+            r#"SELECT u.user_id, u.password AS password_hash,
+                      u.deactivated_at IS NOT NULL AS "deactivated!"
+               FROM users u JOIN user_roles r ON r.user_id = u.user_id AND r.role = 'ADMIN'
+               WHERE u.citizen_id = $1"#,
+            citizen_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    async fn user_credentials(&self, user_id: Uuid) -> AppResult<Option<StoredCredentials>> {
+        let row = sqlx::query_as!(
+            StoredCredentials,
+            r#"SELECT user_id, password AS password_hash, deactivated_at IS NOT NULL AS "deactivated!"
+               FROM users WHERE user_id = $1"#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    async fn user_id_by_email(&self, email: &str) -> AppResult<Option<Uuid>> {
+        let user_id = sqlx::query_scalar!(r#"SELECT user_id FROM users WHERE email = $1"#, email)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(user_id)
+    }
+
+    async fn user_id_by_phone(&self, phone: &str) -> AppResult<Option<Uuid>> {
+        let user_id = sqlx::query_scalar!(r#"SELECT user_id FROM users WHERE phone = $1"#, phone)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(user_id)
+    }
+
+    async fn change_password(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+        keep_session: Uuid,
+    ) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"UPDATE users SET password = $2 WHERE user_id = $1"#,
+            user_id,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"UPDATE sessions SET revoked_at = now(), revoked_reason = 'password_change'
+               WHERE user_id = $1 AND session_id <> $2 AND revoked_at IS NULL"#,
+            user_id,
+            keep_session
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn create_password_reset(
+        &self,
+        user_id: Uuid,
+        token_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL"#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"INSERT INTO password_reset_tokens (token_hash, user_id, expires_at)
+               VALUES ($1, $2, $3)"#,
+            token_hash,
+            user_id,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn reset_password(&self, token_hash: String, password_hash: &str) -> AppResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let user_id = sqlx::query_scalar!(
+            r#"UPDATE password_reset_tokens SET used_at = now()
+               WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+               RETURNING user_id"#,
+            token_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(user_id) = user_id else {
+            return Ok(false);
         };
-        if !verify_password(&password, &r.password)? {
-            return Err(AppError::Unauthorized);
+        sqlx::query!(
+            r#"UPDATE users SET password = $2 WHERE user_id = $1"#,
+            user_id,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"UPDATE sessions SET revoked_at = now(), revoked_reason = 'password_reset'
+               WHERE user_id = $1 AND revoked_at IS NULL"#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"DELETE FROM account_login_failures WHERE user_id = $1"#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn update_profile(&self, user_id: Uuid, update: ProfileUpdate) -> AppResult<()> {
+        let ProfileUpdate {
+            first_name,
+            last_name,
+            email,
+            phone,
+            department,
+            position,
+        } = update;
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            r#"UPDATE users SET
+                 first_name = COALESCE($2, first_name),
+                 last_name = COALESCE($3, last_name),
+                 email = COALESCE($4, email),
+                 phone = COALESCE($5, phone),
+                 email_verified_at = CASE WHEN $4 IS DISTINCT FROM email AND $4 IS NOT NULL
+                                          THEN NULL ELSE email_verified_at END,
+                 phone_verified_at = CASE WHEN $5 IS DISTINCT FROM phone AND $5 IS NOT NULL
+                                          THEN NULL ELSE phone_verified_at END
+               WHERE user_id = $1"#,
+            user_id,
+            first_name,
+            last_name,
+            email,
+            phone
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::on_unique_violation(e, USER_UNIQUE_FIELDS))?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        if department.is_some() || position.is_some() {
+            // Departments come from the catalogue; the profile keeps its
+            // spelling of the name alongside the reference.
+            let department = match department.as_deref() {
+                Some(name) if !name.is_empty() => {
+                    let found = sqlx::query!(
+                        r#"SELECT department_id, name FROM departments WHERE lower(name) = lower($1)"#,
+                        name
+                    )
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .ok_or_else(|| AppError::BadRequest(format!("unknown department `{name}`")))?;
+                    Some((Some(found.department_id), found.name))
+                }
+                Some(_) => Some((None, String::new())),
+                None => None,
+            };
+            let (department_id, department) = department.unzip();
+            let res = sqlx::query!(
+                r#"UPDATE doctor_profile SET
+                     department = CASE WHEN $2::text IS NULL THEN department ELSE NULLIF($2, '') END,
+                     department_id = CASE WHEN $2::text IS NULL THEN department_id ELSE $4 END,
+                     position = CASE WHEN $3::text IS NULL THEN position ELSE NULLIF($3, '') END
+                   WHERE user_id = $1"#,
+                user_id,
+                department,
+                position,
+                department_id.flatten()
+            )
+            .execute(&mut *tx)
+            .await?;
+            if res.rows_affected() == 0 {
+                return Err(AppError::BadRequest(
+                    "department and position only apply to doctors".into(),
+                ));
+            }
         }
-        Ok(r.user_id)
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn contact_address(&self, user_id: Uuid, kind: ContactKind) -> AppResult<ContactAddress> {
+        let row = match kind {
+            ContactKind::Email => {
+                sqlx::query_as!(
+                    ContactAddress,
+                    r#"SELECT email AS address, email_verified_at IS NOT NULL AS "verified!"
+                       FROM users WHERE user_id = $1"#,
+                    user_id
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            ContactKind::Phone => {
+                sqlx::query_as!(
+                    ContactAddress,
+                    r#"SELECT phone AS "address?", phone_verified_at IS NOT NULL AS "verified!"
+                       FROM users WHERE user_id = $1"#,
+                    user_id
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+        };
+        row.ok_or(AppError::NotFound)
+    }
+
+    async fn pending_verification(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+    ) -> AppResult<Option<PendingVerification>> {
+        let row = sqlx::query_as!(
+            PendingVerification,
+            r#"SELECT target, code_hash, created_at, expires_at, attempts
+               FROM contact_verifications WHERE user_id = $1 AND channel = $2"#,
+            user_id,
+            kind.as_str()
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    async fn store_pending_verification(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+        target: &str,
+        code_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"INSERT INTO contact_verifications (user_id, channel, target, code_hash, expires_at)
+               VALUES ($1, $2, $3, $4, $5)
+               ON CONFLICT (user_id, channel) DO UPDATE SET
+                 target = EXCLUDED.target,
+                 code_hash = EXCLUDED.code_hash,
+                 created_at = now(),
+                 expires_at = EXCLUDED.expires_at,
+                 attempts = 0"#,
+            user_id,
+            kind.as_str(),
+            target,
+            code_hash,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn count_verification_attempt(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+        max_attempts: i32,
+    ) -> AppResult<bool> {
+        let res = sqlx::query!(
+            r#"UPDATE contact_verifications SET attempts = attempts + 1
+               WHERE user_id = $1 AND channel = $2 AND attempts < $3"#,
+            user_id,
+            kind.as_str(),
+            max_attempts
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn mark_contact_verified(
+        &self,
+        user_id: Uuid,
+        kind: ContactKind,
+        target: &str,
+    ) -> AppResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let res = match kind {
+            ContactKind::Email => {
+                sqlx::query!(
+                    r#"UPDATE users SET email_verified_at = now()
+                       WHERE user_id = $1 AND email = $2"#,
+                    user_id,
+                    target
+                )
+                .execute(&mut *tx)
+                .await?
+            }
+            ContactKind::Phone => {
+                sqlx::query!(
+                    r#"UPDATE users SET phone_verified_at = now()
+                       WHERE user_id = $1 AND phone = $2"#,
+                    user_id,
+                    target
+                )
+                .execute(&mut *tx)
+                .await?
+            }
+        };
+        sqlx::query!(
+            r#"DELETE FROM contact_verifications WHERE user_id = $1 AND channel = $2"#,
+            user_id,
+            kind.as_str()
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn user_roles(&self, user_id: Uuid) -> AppResult<Vec<Role>> {
+        user_roles(&self.pool, user_id).await
+    }
+
+    async fn user_account_label(&self, user_id: Uuid) -> AppResult<String> {
+        let label = sqlx::query_scalar!(
+            r#"SELECT COALESCE(email, phone) AS "label!" FROM users WHERE user_id = $1"#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        label.ok_or(AppError::NotFound)
+    }
+
+    async fn totp_enrollment(&self, user_id: Uuid) -> AppResult<Option<TotpEnrollment>> {
+        let row = sqlx::query_as!(
+            TotpEnrollment,
+            r#"SELECT secret, confirmed_at IS NOT NULL AS "confirmed!"
+               FROM user_totp WHERE user_id = $1"#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    async fn start_totp_enrollment(&self, user_id: Uuid, secret: &str) -> AppResult<bool> {
+        let res = sqlx::query!(
+            r#"INSERT INTO user_totp (user_id, secret) VALUES ($1, $2)
+               ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret, created_at = now()
+               WHERE user_totp.confirmed_at IS NULL"#,
+            user_id,
+            secret
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn confirm_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            r#"UPDATE user_totp SET confirmed_at = now(), last_used_step = $2
+               WHERE user_id = $1 AND confirmed_at IS NULL"#,
+            user_id,
+            step
+        )
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::Conflict(
+                "two-factor authentication is already enabled".into(),
+            ));
+        }
+        store_recovery_codes(&mut tx, user_id, recovery_code_hashes).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        recovery_code_hashes: Vec<String>,
+    ) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        store_recovery_codes(&mut tx, user_id, recovery_code_hashes).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn disable_totp(&self, user_id: Uuid) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(r#"DELETE FROM user_totp WHERE user_id = $1"#, user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            r#"DELETE FROM totp_recovery_codes WHERE user_id = $1"#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn advance_totp_step(&self, user_id: Uuid, step: i64) -> AppResult<bool> {
+        let res = sqlx::query!(
+            r#"UPDATE user_totp SET last_used_step = $2
+               WHERE user_id = $1 AND confirmed_at IS NOT NULL
+                 AND (last_used_step IS NULL OR last_used_step < $2)"#,
+            user_id,
+            step
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> AppResult<bool> {
+        let res = sqlx::query!(
+            r#"UPDATE totp_recovery_codes SET used_at = now()
+               WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL"#,
+            user_id,
+            code_hash
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn create_login_challenge(
+        &self,
+        user_id: Uuid,
+        challenge_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"INSERT INTO login_challenges (challenge_hash, user_id, expires_at)
+               VALUES ($1, $2, $3)"#,
+            challenge_hash,
+            user_id,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn attempt_login_challenge(
+        &self,
+        challenge_hash: &str,
+        max_attempts: i32,
+    ) -> AppResult<Option<Uuid>> {
+        let user_id = sqlx::query_scalar!(
+            r#"UPDATE login_challenges SET attempts = attempts + 1
+               WHERE challenge_hash = $1 AND used_at IS NULL AND expires_at > now()
+                 AND attempts < $2
+               RETURNING user_id"#,
+            challenge_hash,
+            max_attempts
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(user_id)
+    }
+
+    async fn complete_login_challenge(&self, challenge_hash: &str) -> AppResult<bool> {
+        let res = sqlx::query!(
+            r#"UPDATE login_challenges SET used_at = now()
+               WHERE challenge_hash = $1 AND used_at IS NULL"#,
+            challenge_hash
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn replace_password_hash(
+        &self,
+        user_id: Uuid,
+        old_hash: &str,
+        new_hash: &str,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"UPDATE users SET password = $1 WHERE user_id = $2 AND password = $3"#,
+            new_hash,
+            user_id,
+            old_hash
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
     }
 
     async fn doctor_profile(&self, user_id: Uuid) -> AppResult<DoctorProfileResp> {
@@ -208,7 +971,9 @@ impl AuthRepo for SqlxAuthRepo {
                     COALESCE(u.email, '') AS email,
                     u.phone,
                     COALESCE(d.department, '') AS departments,
-                    COALESCE(d.position, '') AS position
+                    COALESCE(d.position, '') AS position,
+                    u.email_verified_at IS NOT NULL AS "email_verified!",
+                    u.phone_verified_at IS NOT NULL AS "phone_verified!"
                 FROM users u
                 JOIN doctor_profile d ON d.user_id = u.user_id
                 WHERE u.user_id = $1
@@ -229,6 +994,8 @@ impl AuthRepo for SqlxAuthRepo {
             phone: rec.phone,
             departments: rec.departments.unwrap_or_default(),
             position: rec.position.unwrap_or_default(),
+            email_verified: rec.email_verified,
+            phone_verified: rec.phone_verified,
         })
     }
 
@@ -240,6 +1007,8 @@ impl AuthRepo for SqlxAuthRepo {
                     last_name,
                     COALESCE(email, '') AS email,
                     phone,
+                    email_verified_at IS NOT NULL AS "email_verified!",
+                    phone_verified_at IS NOT NULL AS "phone_verified!",
                     updated_at
                 FROM users
                 WHERE user_id = $1
@@ -258,7 +1027,574 @@ impl AuthRepo for SqlxAuthRepo {
             last_name: rec.last_name,
             email: rec.email.unwrap_or_default(),
             phone: rec.phone,
+            email_verified: rec.email_verified,
+            phone_verified: rec.phone_verified,
             updated_at: rec.updated_at,
         })
     }
+
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        refresh_token_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<Uuid> {
+        let mut tx = self.pool.begin().await?;
+        let session_id = sqlx::query_scalar!(
+            r#"INSERT INTO sessions (user_id) VALUES ($1) RETURNING session_id"#,
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"INSERT INTO refresh_tokens (token_hash, session_id, expires_at) VALUES ($1,$2,$3)"#,
+            refresh_token_hash,
+            session_id,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(session_id)
+    }
+
+    async fn rotate_refresh_token(
+        &self,
+        presented_hash: String,
+        next_hash: String,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<RefreshRotation> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query!(
+            r#"
+                SELECT rt.session_id, rt.expires_at, rt.used_at, s.user_id, s.revoked_at
+                FROM refresh_tokens rt
+                JOIN sessions s ON s.session_id = rt.session_id
+                WHERE rt.token_hash = $1
+                FOR UPDATE OF rt, s
+            "#,
+            presented_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(row) = row else {
+            return Ok(RefreshRotation::Invalid);
+        };
+
+        if row.used_at.is_some() {
+            sqlx::query!(
+                r#"UPDATE sessions SET revoked_at = now(), revoked_reason = 'refresh_token_reuse'
+                   WHERE session_id = $1 AND revoked_at IS NULL"#,
+                row.session_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(RefreshRotation::Reused {
+                session_id: row.session_id,
+            });
+        }
+        if row.revoked_at.is_some() || row.expires_at <= OffsetDateTime::now_utc() {
+            return Ok(RefreshRotation::Invalid);
+        }
+
+        sqlx::query!(
+            r#"UPDATE refresh_tokens SET used_at = now() WHERE token_hash = $1"#,
+            presented_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"INSERT INTO refresh_tokens (token_hash, session_id, expires_at) VALUES ($1,$2,$3)"#,
+            next_hash,
+            row.session_id,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"UPDATE sessions SET last_used_at = now() WHERE session_id = $1"#,
+            row.session_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(RefreshRotation::Rotated {
+            user_id: row.user_id,
+            session_id: row.session_id,
+        })
+    }
+
+    async fn revoke_session(&self, user_id: Uuid, session_id: Uuid, reason: &str) -> AppResult<()> {
+        let rows = sqlx::query!(
+            r#"UPDATE sessions SET revoked_at = now(), revoked_reason = $3
+               WHERE session_id = $1 AND user_id = $2 AND revoked_at IS NULL"#,
+            session_id,
+            user_id,
+            reason
+        )
+        .execute(&self.pool)
+        .await?;
+        if rows.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn revoke_all_sessions(&self, user_id: Uuid, reason: &str) -> AppResult<()> {
+        sqlx::query!(
+            r#"UPDATE sessions SET revoked_at = now(), revoked_reason = $2
+               WHERE user_id = $1 AND revoked_at IS NULL"#,
+            user_id,
+            reason
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn login_failures(&self, key: &ThrottleKey) -> AppResult<Option<LoginFailures>> {
+        let row = match key {
+            ThrottleKey::Account(user_id) => {
+                sqlx::query_as!(
+                    LoginFailures,
+                    r#"SELECT failed_count, last_failed_at, locked_until
+                       FROM account_login_failures WHERE user_id = $1"#,
+                    user_id
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            ThrottleKey::Ip(ip) => {
+                sqlx::query_as!(
+                    LoginFailures,
+                    r#"SELECT failed_count, last_failed_at, locked_until
+                       FROM ip_login_failures WHERE ip_addr = $1"#,
+                    ip
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+        };
+        Ok(row)
+    }
+
+    async fn record_login_failure(
+        &self,
+        key: &ThrottleKey,
+        window_start: OffsetDateTime,
+    ) -> AppResult<LoginFailures> {
+        let row = match key {
+            ThrottleKey::Account(user_id) => {
+                sqlx::query_as!(
+                    LoginFailures,
+                    r#"INSERT INTO account_login_failures AS f (user_id, failed_count)
+                       VALUES ($1, 1)
+                       ON CONFLICT (user_id) DO UPDATE SET
+                         failed_count = CASE WHEN f.last_failed_at < $2 THEN 1
+                                             ELSE f.failed_count + 1 END,
+                         last_failed_at = now()
+                       RETURNING failed_count, last_failed_at, locked_until"#,
+                    user_id,
+                    window_start
+                )
+                .fetch_one(&self.pool)
+                .await?
+            }
+            ThrottleKey::Ip(ip) => {
+                sqlx::query_as!(
+                    LoginFailures,
+                    r#"INSERT INTO ip_login_failures AS f (ip_addr, failed_count)
+                       VALUES ($1, 1)
+                       ON CONFLICT (ip_addr) DO UPDATE SET
+                         failed_count = CASE WHEN f.last_failed_at < $2 THEN 1
+                                             ELSE f.failed_count + 1 END,
+                         last_failed_at = now()
+                       RETURNING failed_count, last_failed_at, locked_until"#,
+                    ip,
+                    window_start
+                )
+                .fetch_one(&self.pool)
+                .await?
+            }
+        };
+        Ok(row)
+    }
+
+    async fn lock_login(&self, key: &ThrottleKey, until: OffsetDateTime) -> AppResult<()> {
+        match key {
+            ThrottleKey::Account(user_id) => {
+                sqlx::query!(
+                    r#"UPDATE account_login_failures SET locked_until = $2, failed_count = 0
+                       WHERE user_id = $1"#,
+                    user_id,
+                    until
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            ThrottleKey::Ip(ip) => {
+                sqlx::query!(
+                    r#"UPDATE ip_login_failures SET locked_until = $2, failed_count = 0
+                       WHERE ip_addr = $1"#,
+                    ip,
+                    until
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn clear_login_failures(&self, key: &ThrottleKey) -> AppResult<()> {
+        match key {
+            ThrottleKey::Account(user_id) => {
+                sqlx::query!(
+                    r#"DELETE FROM account_login_failures WHERE user_id = $1"#,
+                    user_id
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            ThrottleKey::Ip(ip) => {
+                sqlx::query!(r#"DELETE FROM ip_login_failures WHERE ip_addr = $1"#, ip)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn store_recovery_codes(
+    tx: &mut PgTx<'_>,
+    user_id: Uuid,
+    code_hashes: Vec<String>,
+) -> AppResult<()> {
+    sqlx::query!(
+        r#"DELETE FROM totp_recovery_codes WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query!(
+        r#"INSERT INTO totp_recovery_codes (user_id, code_hash)
+           SELECT $1, UNNEST($2::text[])"#,
+        user_id,
+        &code_hashes
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+impl AdminRepo for SqlxAuthRepo {
+    async fn search_users(&self, search: &UserSearch) -> AppResult<(Vec<AdminUserSummary>, i64)> {
+        let pattern = search.q.as_deref().map(like_pattern);
+        let role = search.role.map(Role::as_str);
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    u.user_id,
+                    u.first_name,
+                    u.last_name,
+                    u.email,
+                    u.phone,
+                    u.citizen_id,
+                    u.deactivated_at IS NOT NULL AS "deactivated!",
+                    u.created_at,
+                    ARRAY(
+                        SELECT r.role::text FROM user_roles r
+                        WHERE r.user_id = u.user_id ORDER BY r.role
+                    ) AS "roles!"
+                FROM users u
+                LEFT JOIN patient_profile p ON p.user_id = u.user_id
+                LEFT JOIN doctor_profile d ON d.user_id = u.user_id
+                WHERE ($1::text IS NULL
+                       OR u.first_name || ' ' || u.last_name ILIKE $1
+                       OR u.email ILIKE $1
+                       OR u.phone ILIKE $1
+                       OR u.citizen_id ILIKE $1
+                       OR p.hn::text ILIKE $1
+                       OR d.mln ILIKE $1)
+                  AND ($2::text IS NULL OR EXISTS (
+                        SELECT 1 FROM user_roles r
+                        WHERE r.user_id = u.user_id AND r.role::text = $2))
+                  AND ($3::bool IS NULL OR (u.deactivated_at IS NOT NULL) = $3)
+                ORDER BY u.created_at DESC, u.user_id
+                LIMIT $4 OFFSET $5
+            "#,
+            pattern,
+            role,
+            search.deactivated,
+            search.per_page,
+            (search.page - 1) * search.per_page
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let total = sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*) AS "count!"
+                FROM users u
+                LEFT JOIN patient_profile p ON p.user_id = u.user_id
+                LEFT JOIN doctor_profile d ON d.user_id = u.user_id
+                WHERE ($1::text IS NULL
+                       OR u.first_name || ' ' || u.last_name ILIKE $1
+                       OR u.email ILIKE $1
+                       OR u.phone ILIKE $1
+                       OR u.citizen_id ILIKE $1
+                       OR p.hn::text ILIKE $1
+                       OR d.mln ILIKE $1)
+                  AND ($2::text IS NULL OR EXISTS (
+                        SELECT 1 FROM user_roles r
+                        WHERE r.user_id = u.user_id AND r.role::text = $2))
+                  AND ($3::bool IS NULL OR (u.deactivated_at IS NOT NULL) = $3)
+            "#,
+            pattern,
+            role,
+            search.deactivated
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let items = rows
+            .into_iter()
+            .map(|row| AdminUserSummary {
+                user_id: row.user_id,
+                first_name: row.first_name,
+                last_name: row.last_name,
+                email: row.email,
+                phone: row.phone,
+                citizen_id: row.citizen_id,
+                roles: parse_roles(&row.roles),
+                deactivated: row.deactivated,
+                created_at: row.created_at,
+            })
+            .collect();
+        Ok((items, total))
+    }
+
+    async fn user_detail(&self, user_id: Uuid) -> AppResult<Option<AdminUserDetail>> {
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    u.user_id,
+                    u.first_name,
+                    u.last_name,
+                    u.email,
+                    u.email_verified_at IS NOT NULL AS "email_verified!",
+                    u.phone,
+                    u.phone_verified_at IS NOT NULL AS "phone_verified!",
+                    u.citizen_id,
+                    u.deactivated_at,
+                    u.created_at,
+                    ARRAY(
+                        SELECT r.role::text FROM user_roles r
+                        WHERE r.user_id = u.user_id ORDER BY r.role
+                    ) AS "roles!",
+                    EXISTS(
+                        SELECT 1 FROM user_totp t
+                        WHERE t.user_id = u.user_id AND t.confirmed_at IS NOT NULL
+                    ) AS "two_factor_enabled!",
+                    p.hn AS "hn?",
+                    d.mln AS "mln?",
+                    d.department AS "department?",
+                    d.position AS "position?"
+                FROM users u
+                LEFT JOIN patient_profile p ON p.user_id = u.user_id
+                LEFT JOIN doctor_profile d ON d.user_id = u.user_id
+                WHERE u.user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| AdminUserDetail {
+            user_id: row.user_id,
+            first_name: row.first_name,
+            last_name: row.last_name,
+            email: row.email,
+            email_verified: row.email_verified,
+            phone: row.phone,
+            phone_verified: row.phone_verified,
+            citizen_id: row.citizen_id,
+            roles: parse_roles(&row.roles),
+            two_factor_enabled: row.two_factor_enabled,
+            deactivated_at: row.deactivated_at,
+            created_at: row.created_at,
+            patient_profile: row.hn.map(|hn| PatientProfileInfo { hn }),
+            doctor_profile: row.mln.map(|mln| DoctorProfileInfo {
+                mln,
+                department: row.department,
+                position: row.position,
+            }),
+        }))
+    }
+
+    async fn grant_role(&self, user_id: Uuid, role: Role) -> AppResult<()> {
+        let res = sqlx::query!(
+            r#"INSERT INTO user_roles (user_id, role)
+               SELECT user_id, $2::text::role_type FROM users WHERE user_id = $1
+               ON CONFLICT DO NOTHING"#,
+            user_id,
+            role.as_str()
+        )
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() == 0 && !self.user_exists(user_id).await? {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn revoke_role(&self, user_id: Uuid, role: Role) -> AppResult<()> {
+        let res = sqlx::query!(
+            r#"DELETE FROM user_roles WHERE user_id = $1 AND role = $2::text::role_type"#,
+            user_id,
+            role.as_str()
+        )
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn set_deactivated(&self, user_id: Uuid, deactivated: bool) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            r#"UPDATE users SET deactivated_at = CASE WHEN $2 THEN COALESCE(deactivated_at, now())
+                                                      ELSE NULL END
+               WHERE user_id = $1"#,
+            user_id,
+            deactivated
+        )
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+        if deactivated {
+            sqlx::query!(
+                r#"UPDATE sessions SET revoked_at = now(), revoked_reason = 'deactivated'
+                   WHERE user_id = $1 AND revoked_at IS NULL"#,
+                user_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn active_admin_exists(&self) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                 SELECT 1 FROM user_roles r JOIN users u ON u.user_id = r.user_id
+                 WHERE r.role = 'ADMIN' AND u.deactivated_at IS NULL
+               ) AS "exists!""#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+
+    async fn create_admin(&self, input: NewAdminInput) -> AppResult<Uuid> {
+        let NewAdminInput {
+            citizen_id,
+            first_name,
+            last_name,
+            phone,
+            email,
+            password,
+        } = input;
+        let password_hash = hash_password(&password)?;
+        let mut tx = self.pool.begin().await?;
+        let user_id = sqlx::query_scalar!(
+            r#"INSERT INTO users (phone, first_name, last_name, citizen_id, password, email)
+               VALUES ($1,$2,$3,$4,$5,$6) RETURNING user_id"#,
+            phone,
+            first_name,
+            last_name,
+            citizen_id,
+            password_hash,
+            email,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::on_unique_violation(e, USER_UNIQUE_FIELDS))?;
+        sqlx::query!(
+            r#"INSERT INTO user_roles (user_id, role) VALUES ($1,'ADMIN') ON CONFLICT DO NOTHING"#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(user_id)
+    }
+}
+
+impl SqlxAuthRepo {
+    async fn user_exists(&self, user_id: Uuid) -> AppResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM users WHERE user_id = $1) AS "exists!""#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+}
+
+struct PatientMedicalRightRow {
+    mr_id: i32,
+    name: String,
+    policy_number: Option<String>,
+    valid_from: Option<Date>,
+    valid_until: Option<Date>,
+    status: String,
+    rejection_reason: Option<String>,
+    requested_at: OffsetDateTime,
+}
+
+impl From<PatientMedicalRightRow> for PatientMedicalRight {
+    fn from(row: PatientMedicalRightRow) -> Self {
+        let today = OffsetDateTime::now_utc().date();
+        let status = match MedicalRightStatus::parse(&row.status) {
+            Some(MedicalRightStatus::Approved) if row.valid_until.is_some_and(|d| d < today) => {
+                MedicalRightStatus::Expired
+            }
+            Some(status) => status,
+            None => MedicalRightStatus::Pending,
+        };
+        let active =
+            status == MedicalRightStatus::Approved && row.valid_from.is_none_or(|d| d <= today);
+        Self {
+            mr_id: row.mr_id,
+            name: row.name,
+            policy_number: row.policy_number,
+            valid_from: row.valid_from,
+            valid_until: row.valid_until,
+            status,
+            active,
+            rejection_reason: row.rejection_reason,
+            requested_at: row.requested_at,
+        }
+    }
+}
+
+/// `%q%` for ILIKE, with the pattern metacharacters in `q` escaped.
+fn like_pattern(q: &str) -> String {
+    let escaped = q
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+fn parse_roles(roles: &[String]) -> Vec<Role> {
+    roles.iter().filter_map(|r| Role::parse(r)).collect()
 }
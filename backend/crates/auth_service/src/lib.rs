@@ -2,5 +2,9 @@ pub mod app;
 pub mod domain;
 pub mod infra;
 
+pub use crate::infra::admin_http::AdminApiDoc;
+pub use crate::infra::admin_http::admin_router;
+pub use crate::infra::admin_http::bootstrap_admin;
 pub use crate::infra::http::ApiDoc;
 pub use crate::infra::http::router;
+pub use crate::infra::http::well_known_router;
@@ -1,50 +1,180 @@
+use std::{marker::PhantomData, path::Path};
+
+use anyhow::{Context, bail};
 use axum::{extract::FromRequestParts, http::request::Parts};
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+    jwk::{Jwk, JwkSet},
+};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use time::{Duration, OffsetDateTime};
+use time::{Duration, OffsetDateTime, format_description::well_known::Rfc3339};
 use uuid::Uuid;
 
 use crate::{
     config::AppConfig,
     error::{AppError, AppResult},
+    keys,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub sub: Uuid,
+    pub sid: Uuid,
+    #[serde(default)]
+    pub roles: Vec<Role>,
     pub exp: i64,
 }
 
 #[derive(Clone)]
 pub struct JwtKeys {
-    pub enc: EncodingKey,
-    pub dec: DecodingKey,
+    algorithm: Algorithm,
+    signing_kid: Option<String>,
+    enc: EncodingKey,
+    verifying: Vec<VerifyingKey>,
+}
+
+#[derive(Clone)]
+struct VerifyingKey {
+    kid: Option<String>,
+    dec: DecodingKey,
+    /// End of the rotation window for a retired key.
+    not_after: Option<OffsetDateTime>,
+    jwk: Option<Jwk>,
+}
+
+impl VerifyingKey {
+    fn is_active(&self, now: OffsetDateTime) -> bool {
+        self.not_after.is_none_or(|t| now < t)
+    }
 }
 
 impl JwtKeys {
     pub fn from_secret(secret: &str) -> Self {
         Self {
+            algorithm: Algorithm::HS256,
+            signing_kid: None,
             enc: EncodingKey::from_secret(secret.as_bytes()),
-            dec: DecodingKey::from_secret(secret.as_bytes()),
+            verifying: vec![VerifyingKey {
+                kid: None,
+                dec: DecodingKey::from_secret(secret.as_bytes()),
+                not_after: None,
+                jwk: None,
+            }],
+        }
+    }
+
+    pub fn from_config(cfg: &AppConfig) -> anyhow::Result<Self> {
+        let algorithm = match cfg.jwt_algorithm.as_str() {
+            "HS256" => {
+                let secret = cfg
+                    .jwt_secret
+                    .as_deref()
+                    .context("JWT_SECRET must be set when JWT_ALGORITHM is HS256")?;
+                return Ok(Self::from_secret(secret));
+            }
+            "RS256" => Algorithm::RS256,
+            "EdDSA" => Algorithm::EdDSA,
+            other => bail!("unsupported JWT_ALGORITHM {other}"),
+        };
+
+        let dir = Path::new(&cfg.jwt_keys_dir);
+        let signing_kid = cfg
+            .jwt_signing_kid
+            .clone()
+            .context("JWT_SIGNING_KID must be set for asymmetric JWT algorithms")?;
+        let enc = keys::load_signing_key(algorithm, &dir.join(format!("{signing_kid}.pem")))?;
+
+        let mut verifying = Vec::new();
+        let retired = cfg.jwt_verify_kids.iter().map(|spec| parse_kid_spec(spec));
+        for entry in std::iter::once(Ok((signing_kid.clone(), None))).chain(retired) {
+            let (kid, not_after) = entry?;
+            let path = dir.join(format!("{kid}.pub.pem"));
+            let (dec, jwk) = keys::load_verifying_key(algorithm, &kid, &path)?;
+            verifying.push(VerifyingKey {
+                kid: Some(kid),
+                dec,
+                not_after,
+                jwk: Some(jwk),
+            });
+        }
+
+        Ok(Self {
+            algorithm,
+            signing_kid: Some(signing_kid),
+            enc,
+            verifying,
+        })
+    }
+
+    /// Public keys currently accepted for verification, for `/.well-known/jwks.json`.
+    /// Always empty for `HS256`, whose secret must never be published.
+    pub fn jwks(&self) -> JwkSet {
+        let now = OffsetDateTime::now_utc();
+        JwkSet {
+            keys: self
+                .verifying
+                .iter()
+                .filter(|key| key.is_active(now))
+                .filter_map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+
+    fn verifying_key(&self, kid: Option<&str>) -> Option<&DecodingKey> {
+        let now = OffsetDateTime::now_utc();
+        self.verifying
+            .iter()
+            .find(|key| key.kid.as_deref() == kid && key.is_active(now))
+            .map(|key| &key.dec)
+    }
+}
+
+/// Parse a `kid` or `kid@<RFC 3339 instant>` entry of `JWT_VERIFY_KIDS`.
+fn parse_kid_spec(spec: &str) -> anyhow::Result<(String, Option<OffsetDateTime>)> {
+    match spec.split_once('@') {
+        None => Ok((spec.to_string(), None)),
+        Some((kid, until)) => {
+            let until = OffsetDateTime::parse(until, &Rfc3339)
+                .with_context(|| format!("invalid rotation deadline in JWT_VERIFY_KIDS: {spec}"))?;
+            Ok((kid.to_string(), Some(until)))
         }
     }
 }
 
-pub fn issue_jwt(user_id: Uuid, keys: &JwtKeys, ttl_minutes: i64) -> Result<String, AppError> {
+pub fn issue_jwt(
+    user_id: Uuid,
+    session_id: Uuid,
+    roles: Vec<Role>,
+    keys: &JwtKeys,
+    ttl_minutes: i64,
+) -> Result<String, AppError> {
     let exp = (OffsetDateTime::now_utc() + Duration::minutes(ttl_minutes)).unix_timestamp();
-    let claims = Claims { sub: user_id, exp };
+    let claims = Claims {
+        sub: user_id,
+        sid: session_id,
+        roles,
+        exp,
+    };
     let header = Header {
-        alg: Algorithm::HS256,
+        alg: keys.algorithm,
+        kid: keys.signing_kid.clone(),
         ..Default::default()
     };
     encode(&header, &claims, &keys.enc).map_err(|e| AppError::Other(e.into()))
 }
 
 pub fn verify_jwt(token: &str, keys: &JwtKeys) -> Result<Claims, AppError> {
-    let mut val = Validation::new(Algorithm::HS256);
+    let header = decode_header(token).map_err(|_| AppError::Unauthorized)?;
+    if header.alg != keys.algorithm {
+        return Err(AppError::Unauthorized);
+    }
+    let dec = keys
+        .verifying_key(header.kid.as_deref())
+        .ok_or(AppError::Unauthorized)?;
+    let mut val = Validation::new(keys.algorithm);
     val.validate_exp = true;
-    decode::<Claims>(token, &keys.dec, &val)
+    decode::<Claims>(token, dec, &val)
         .map(|td| td.claims)
         .map_err(|_| AppError::Unauthorized)
 }
@@ -52,9 +182,17 @@ pub fn verify_jwt(token: &str, keys: &JwtKeys) -> Result<Claims, AppError> {
 #[derive(Clone, Debug)]
 pub struct AuthUser {
     pub user_id: Uuid,
+    pub session_id: Uuid,
     pub claims: Claims,
 }
 
+impl AuthUser {
+    /// Role membership as recorded in the access token.
+    pub fn has_role(&self, role: Role) -> bool {
+        self.claims.roles.contains(&role)
+    }
+}
+
 impl<S> FromRequestParts<S> for AuthUser
 where
     S: Send + Sync + 'static,
@@ -75,18 +213,28 @@ where
         })?;
 
         let claims = verify_jwt(token, keys)?;
+
+        let pool = parts.extensions.get::<PgPool>().ok_or_else(|| {
+            AppError::Other(anyhow::anyhow!("missing PgPool in request extensions"))
+        })?;
+        if !session_is_active(pool, claims.sid, claims.sub).await? {
+            return Err(AppError::Unauthorized);
+        }
+
         Ok(AuthUser {
             user_id: claims.sub,
+            session_id: claims.sid,
             claims,
         })
     }
 }
 
 pub fn jwt_keys_from_config(cfg: &AppConfig) -> JwtKeys {
-    JwtKeys::from_secret(&cfg.jwt_secret)
+    JwtKeys::from_config(cfg).expect("invalid JWT key configuration")
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Role {
     Patient,
     Doctor,
@@ -101,6 +249,32 @@ impl Role {
             Role::Admin => "ADMIN",
         }
     }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "PATIENT" => Some(Role::Patient),
+            "DOCTOR" => Some(Role::Doctor),
+            "ADMIN" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// All roles currently granted to `user_id`, as stored in `user_roles`.
+pub async fn user_roles(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<Role>> {
+    let roles = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT role::text
+        FROM user_roles
+        WHERE user_id = $1
+        ORDER BY role
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(roles.iter().filter_map(|r| Role::parse(r)).collect())
 }
 
 pub async fn user_has_role(pool: &PgPool, user_id: Uuid, role: Role) -> AppResult<bool> {
@@ -129,3 +303,140 @@ pub async fn ensure_user_role(pool: &PgPool, user_id: Uuid, role: Role) -> AppRe
         Err(AppError::Forbidden)
     }
 }
+
+/// Whether the session an access token was issued for is still live (not logged
+/// out or revoked through refresh-token reuse detection) and its user has not
+/// been deactivated.
+pub async fn session_is_active(pool: &PgPool, session_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+    let active = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS(
+            SELECT 1
+            FROM sessions s
+            JOIN users u ON u.user_id = s.user_id
+            WHERE s.session_id = $1
+              AND s.user_id = $2
+              AND s.revoked_at IS NULL
+              AND u.deactivated_at IS NULL
+        )
+        "#,
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(active)
+}
+
+/// Type-level role used by [`RequireRole`] and [`RequireAnyRole`].
+pub trait RoleMarker: Send + Sync + 'static {
+    const ROLE: Role;
+}
+
+pub struct Patient;
+pub struct Doctor;
+pub struct Admin;
+
+impl RoleMarker for Patient {
+    const ROLE: Role = Role::Patient;
+}
+impl RoleMarker for Doctor {
+    const ROLE: Role = Role::Doctor;
+}
+impl RoleMarker for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+/// A tuple of [`RoleMarker`]s, any of which satisfies [`RequireAnyRole`].
+pub trait RoleSet: Send + Sync + 'static {
+    const ROLES: &'static [Role];
+}
+
+impl<A: RoleMarker> RoleSet for (A,) {
+    const ROLES: &'static [Role] = &[A::ROLE];
+}
+impl<A: RoleMarker, B: RoleMarker> RoleSet for (A, B) {
+    const ROLES: &'static [Role] = &[A::ROLE, B::ROLE];
+}
+impl<A: RoleMarker, B: RoleMarker, C: RoleMarker> RoleSet for (A, B, C) {
+    const ROLES: &'static [Role] = &[A::ROLE, B::ROLE, C::ROLE];
+}
+
+/// How a role guard decides membership.
+pub trait RoleCheck: Send + Sync + 'static {
+    const STRICT: bool;
+}
+
+/// Trust the roles embedded in the access token (no database round trip).
+pub struct FromToken;
+/// Additionally re-check `user_roles`, so a revoked role takes effect before
+/// the access token expires. Use for sensitive routes.
+pub struct Strict;
+
+impl RoleCheck for FromToken {
+    const STRICT: bool = false;
+}
+impl RoleCheck for Strict {
+    const STRICT: bool = true;
+}
+
+/// Extracts an [`AuthUser`] that holds role `R`, e.g. `RequireRole<Doctor>` or
+/// `RequireRole<Admin, Strict>`. Rejects with `403` otherwise.
+pub struct RequireRole<R, M = FromToken>(pub AuthUser, pub PhantomData<fn() -> (R, M)>);
+
+/// Extracts an [`AuthUser`] that holds at least one of the roles in `T`, e.g.
+/// `RequireAnyRole<(Doctor, Admin)>`. Rejects with `403` otherwise.
+pub struct RequireAnyRole<T, M = FromToken>(pub AuthUser, pub PhantomData<fn() -> (T, M)>);
+
+impl<S, R, M> FromRequestParts<S> for RequireRole<R, M>
+where
+    S: Send + Sync + 'static,
+    R: RoleMarker,
+    M: RoleCheck,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        authorize_roles::<M>(parts, &user, &[R::ROLE]).await?;
+        Ok(Self(user, PhantomData))
+    }
+}
+
+impl<S, T, M> FromRequestParts<S> for RequireAnyRole<T, M>
+where
+    S: Send + Sync + 'static,
+    T: RoleSet,
+    M: RoleCheck,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        authorize_roles::<M>(parts, &user, T::ROLES).await?;
+        Ok(Self(user, PhantomData))
+    }
+}
+
+async fn authorize_roles<M: RoleCheck>(
+    parts: &Parts,
+    user: &AuthUser,
+    allowed: &[Role],
+) -> AppResult<()> {
+    let mut granted = allowed.iter().copied().filter(|role| user.has_role(*role));
+    if !M::STRICT {
+        return granted.next().map(|_| ()).ok_or(AppError::Forbidden);
+    }
+
+    let pool = parts
+        .extensions
+        .get::<PgPool>()
+        .ok_or_else(|| AppError::Other(anyhow::anyhow!("missing PgPool in request extensions")))?;
+    for role in granted {
+        if user_has_role(pool, user.user_id, role).await? {
+            return Ok(());
+        }
+    }
+    Err(AppError::Forbidden)
+}
@@ -1,13 +1,115 @@
+use crate::{auth::Role, notify::Channel, password::PasswordPolicy};
 use dotenvy::dotenv;
 use std::env;
 
 #[derive(Clone)]
 pub struct AppConfig {
     pub db_url: String,
-    pub jwt_secret: String,
+    /// Shared secret for `HS256`; unused with asymmetric algorithms.
+    pub jwt_secret: Option<String>,
+    /// `HS256`, `RS256` or `EdDSA`.
+    pub jwt_algorithm: String,
+    /// Directory holding `<kid>.pem` (private) and `<kid>.pub.pem` (public) files.
+    pub jwt_keys_dir: String,
+    /// Key id used to sign new tokens.
+    pub jwt_signing_kid: Option<String>,
+    /// Further key ids still accepted for verification, each optionally
+    /// suffixed with `@<RFC 3339 instant>` after which it is no longer trusted.
+    pub jwt_verify_kids: Vec<String>,
     pub bind_addr: String,
     pub env: String,
     pub geoapify_api_key: Option<String>,
+    pub access_token_ttl_minutes: i64,
+    pub refresh_token_ttl_days: i64,
+    pub password_policy: PasswordPolicy,
+    pub login_throttle: LoginThrottleConfig,
+    /// Take the client address from `X-Forwarded-For` (set when running
+    /// behind a reverse proxy) instead of the socket peer.
+    pub trust_proxy_headers: bool,
+    /// Notification backend: `log` or `file`.
+    pub notifier: String,
+    /// Output file for the `file` notifier.
+    pub notifier_file: String,
+    pub password_reset_ttl_minutes: i64,
+    /// Frontend page that accepts a reset token as `?token=`; when unset the
+    /// bare token is sent.
+    pub password_reset_url: Option<String>,
+    /// Roles withheld from access tokens until the user has confirmed TOTP.
+    pub totp_required_roles: Vec<Role>,
+    /// Issuer shown in authenticator apps.
+    pub totp_issuer: String,
+    /// Lifetime of the challenge token between the two login steps.
+    pub login_challenge_ttl_minutes: i64,
+    /// Lifetime of email/phone verification codes.
+    pub verification_code_ttl_minutes: i64,
+    /// Offset of the clinic's local time, in which timeslots are defined.
+    pub clinic_utc_offset_minutes: i32,
+    pub booking_rules: BookingRules,
+    /// How often expired waitlist offers are passed on.
+    pub waitlist_sweep_seconds: u64,
+    /// Public origin of the API, e.g. `https://api.example.com`, used to
+    /// build calendar subscription links; when unset the links are relative.
+    pub public_api_url: Option<String>,
+    pub reminders: ReminderConfig,
+}
+
+/// When and how patients are reminded of accepted appointments.
+#[derive(Clone, Debug)]
+pub struct ReminderConfig {
+    /// Minutes before the visit at which a reminder is due, e.g. 1440 and 120.
+    pub offsets_minutes: Vec<i32>,
+    pub channels: Vec<Channel>,
+    /// How often due reminders are queued and sent.
+    pub sweep_seconds: u64,
+    /// Attempts per reminder before it is marked FAILED.
+    pub max_attempts: i32,
+    /// Delay before the first retry; doubles with every further attempt.
+    pub retry_base_seconds: i64,
+}
+
+/// Limits on when patients may book, move and cancel appointments.
+#[derive(Clone, Debug)]
+pub struct BookingRules {
+    /// Minimum time between booking and the start of the slot.
+    pub min_lead_minutes: i64,
+    /// How many days ahead a slot may be booked.
+    pub max_horizon_days: i64,
+    /// ACCEPTED appointments cannot be cancelled or moved this close to
+    /// their start.
+    pub cancellation_cutoff_hours: i64,
+    /// Upcoming PENDING appointments a patient may hold at once.
+    pub max_pending_per_patient: i64,
+    /// How long a freed place is held for the next waitlisted patient.
+    pub waitlist_hold_minutes: i64,
+}
+
+impl Default for BookingRules {
+    fn default() -> Self {
+        Self {
+            min_lead_minutes: 60,
+            max_horizon_days: 90,
+            cancellation_cutoff_hours: 24,
+            max_pending_per_patient: 3,
+            waitlist_hold_minutes: 30,
+        }
+    }
+}
+
+/// Limits applied to failed logins, both per account and per client IP.
+#[derive(Clone, Debug)]
+pub struct LoginThrottleConfig {
+    /// Failures older than this no longer count towards any limit.
+    pub failure_window_minutes: i64,
+    /// Consecutive account failures tolerated before delays kick in.
+    pub free_attempts: i32,
+    /// Upper bound for the doubling delay between attempts.
+    pub max_delay_seconds: i64,
+    /// Account failures that trigger a temporary lockout.
+    pub lockout_threshold: i32,
+    pub lockout_minutes: i64,
+    /// Failures from one IP, across all accounts, before it is blocked.
+    pub ip_threshold: i32,
+    pub ip_block_minutes: i64,
 }
 
 impl AppConfig {
@@ -15,10 +117,136 @@ impl AppConfig {
         let _ = dotenv();
         Self {
             db_url: env::var("DATABASE_URL").expect("DATABASE_URL"),
-            jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET"),
+            jwt_secret: env::var("JWT_SECRET").ok(),
+            jwt_algorithm: env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".into()),
+            jwt_keys_dir: env::var("JWT_KEYS_DIR").unwrap_or_else(|_| "keys".into()),
+            jwt_signing_kid: env::var("JWT_SIGNING_KID").ok(),
+            jwt_verify_kids: env::var("JWT_VERIFY_KIDS")
+                .map(|v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|kid| !kid.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
             bind_addr: env::var("BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".into()),
             env: env::var("APP_ENV").unwrap_or_else(|_| "dev".into()),
             geoapify_api_key: env::var("GEOAPIFY_API_KEY").ok(),
+            access_token_ttl_minutes: env_or("ACCESS_TOKEN_TTL_MINUTES", 15),
+            refresh_token_ttl_days: env_or("REFRESH_TOKEN_TTL_DAYS", 30),
+            password_policy: password_policy_from_env(),
+            login_throttle: LoginThrottleConfig {
+                failure_window_minutes: env_or("LOGIN_FAILURE_WINDOW_MINUTES", 15),
+                free_attempts: env_or("LOGIN_FREE_ATTEMPTS", 3),
+                max_delay_seconds: env_or("LOGIN_MAX_DELAY_SECONDS", 60),
+                lockout_threshold: env_or("LOGIN_LOCKOUT_THRESHOLD", 10),
+                lockout_minutes: env_or("LOGIN_LOCKOUT_MINUTES", 15),
+                ip_threshold: env_or("LOGIN_IP_THRESHOLD", 50),
+                ip_block_minutes: env_or("LOGIN_IP_BLOCK_MINUTES", 15),
+            },
+            trust_proxy_headers: env_or("TRUST_PROXY_HEADERS", false),
+            notifier: env::var("NOTIFIER").unwrap_or_else(|_| "log".into()),
+            notifier_file: env::var("NOTIFIER_FILE")
+                .unwrap_or_else(|_| "notifications.jsonl".into()),
+            password_reset_ttl_minutes: env_or("PASSWORD_RESET_TTL_MINUTES", 30),
+            password_reset_url: env::var("PASSWORD_RESET_URL").ok(),
+            totp_required_roles: env::var("TOTP_REQUIRED_ROLES")
+                .map(|v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|role| !role.is_empty())
+                        .map(|role| {
+                            Role::parse(&role.to_ascii_uppercase()).unwrap_or_else(|| {
+                                panic!("unknown role `{role}` in TOTP_REQUIRED_ROLES")
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            totp_issuer: env::var("TOTP_ISSUER").unwrap_or_else(|_| "Therapeia".into()),
+            login_challenge_ttl_minutes: env_or("LOGIN_CHALLENGE_TTL_MINUTES", 5),
+            verification_code_ttl_minutes: env_or("VERIFICATION_CODE_TTL_MINUTES", 10),
+            clinic_utc_offset_minutes: env_or("CLINIC_UTC_OFFSET_MINUTES", 0),
+            booking_rules: booking_rules_from_env(),
+            waitlist_sweep_seconds: env_or("WAITLIST_SWEEP_SECONDS", 60),
+            public_api_url: env::var("PUBLIC_API_URL")
+                .ok()
+                .map(|url| url.trim_end_matches('/').to_owned())
+                .filter(|url| !url.is_empty()),
+            reminders: reminder_config_from_env(),
         }
     }
 }
+
+fn password_policy_from_env() -> PasswordPolicy {
+    let defaults = PasswordPolicy::default();
+    PasswordPolicy {
+        min_length: env_or("PASSWORD_MIN_LENGTH", defaults.min_length),
+        max_length: env_or("PASSWORD_MAX_LENGTH", defaults.max_length),
+        require_lowercase: env_or("PASSWORD_REQUIRE_LOWERCASE", defaults.require_lowercase),
+        require_uppercase: env_or("PASSWORD_REQUIRE_UPPERCASE", defaults.require_uppercase),
+        require_digit: env_or("PASSWORD_REQUIRE_DIGIT", defaults.require_digit),
+        require_symbol: env_or("PASSWORD_REQUIRE_SYMBOL", defaults.require_symbol),
+    }
+}
+
+fn booking_rules_from_env() -> BookingRules {
+    let defaults = BookingRules::default();
+    BookingRules {
+        min_lead_minutes: env_or("BOOKING_MIN_LEAD_MINUTES", defaults.min_lead_minutes),
+        max_horizon_days: env_or("BOOKING_MAX_HORIZON_DAYS", defaults.max_horizon_days),
+        cancellation_cutoff_hours: env_or(
+            "BOOKING_CANCELLATION_CUTOFF_HOURS",
+            defaults.cancellation_cutoff_hours,
+        ),
+        max_pending_per_patient: env_or(
+            "BOOKING_MAX_PENDING_PER_PATIENT",
+            defaults.max_pending_per_patient,
+        ),
+        waitlist_hold_minutes: env_or("WAITLIST_HOLD_MINUTES", defaults.waitlist_hold_minutes),
+    }
+}
+
+fn reminder_config_from_env() -> ReminderConfig {
+    let list = |key: &str, default: &str| -> Vec<String> {
+        env::var(key)
+            .unwrap_or_else(|_| default.into())
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    ReminderConfig {
+        offsets_minutes: list("REMINDER_OFFSETS_MINUTES", "1440,120")
+            .iter()
+            .map(|offset| {
+                offset
+                    .parse()
+                    .ok()
+                    .filter(|minutes| *minutes > 0)
+                    .unwrap_or_else(|| {
+                        panic!("invalid offset `{offset}` in REMINDER_OFFSETS_MINUTES")
+                    })
+            })
+            .collect(),
+        channels: list("REMINDER_CHANNELS", "email")
+            .iter()
+            .map(|channel| {
+                Channel::parse(&channel.to_ascii_lowercase())
+                    .unwrap_or_else(|| panic!("unknown channel `{channel}` in REMINDER_CHANNELS"))
+            })
+            .collect(),
+        sweep_seconds: env_or("REMINDER_SWEEP_SECONDS", 60),
+        max_attempts: env_or("REMINDER_MAX_ATTEMPTS", 5),
+        retry_base_seconds: env_or("REMINDER_RETRY_BASE_SECONDS", 60),
+    }
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
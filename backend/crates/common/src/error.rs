@@ -1,5 +1,6 @@
 use axum::{
-    http::StatusCode,
+    Json,
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
     response::{IntoResponse, Response},
 };
 use thiserror::Error;
@@ -14,8 +15,22 @@ pub enum AppError {
     Forbidden,
     #[error("not found")]
     NotFound,
-    #[error("conflict")]
-    Conflict,
+    #[error("{0}")]
+    Conflict(String),
+    /// A business rule refused the request. Rendered as JSON
+    /// `{"code": ..., "message": ...}` so clients can branch on `code`.
+    #[error("{message}")]
+    Rule {
+        status: StatusCode,
+        code: &'static str,
+        message: String,
+    },
+    /// Rate limited; the client may retry after `retry_after` seconds.
+    #[error("too many requests")]
+    TooManyRequests { retry_after: u64 },
+    /// The account is temporarily locked for `retry_after` seconds.
+    #[error("account locked")]
+    Locked { retry_after: u64 },
     #[error(transparent)]
     Db(#[from] sqlx::Error),
     #[error(transparent)]
@@ -29,11 +44,54 @@ impl IntoResponse for AppError {
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
             AppError::Forbidden => StatusCode::FORBIDDEN,
             AppError::NotFound => StatusCode::NOT_FOUND,
-            AppError::Conflict => StatusCode::CONFLICT,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Rule {
+                status,
+                code,
+                ref message,
+            } => {
+                let body = serde_json::json!({ "code": code, "message": message });
+                return (status, Json(body)).into_response();
+            }
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Locked { .. } => StatusCode::LOCKED,
             AppError::Db(_) | AppError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
-        (code, self.to_string()).into_response()
+        let retry_after = match self {
+            AppError::TooManyRequests { retry_after } | AppError::Locked { retry_after } => {
+                Some(retry_after)
+            }
+            _ => None,
+        };
+        let mut response = (code, self.to_string()).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
 
+impl AppError {
+    /// Map a unique-constraint violation to a `Conflict` naming the offending
+    /// field. `fields` pairs Postgres constraint names with the field names
+    /// clients know; any other error is passed through unchanged.
+    pub fn on_unique_violation(err: sqlx::Error, fields: &[(&str, &str)]) -> Self {
+        if let sqlx::Error::Database(db) = &err
+            && db.is_unique_violation()
+            && let Some(constraint) = db.constraint()
+            && let Some((_, field)) = fields.iter().find(|(name, _)| *name == constraint)
+        {
+            return AppError::Conflict(format!("{field} is already in use"));
+        }
+        AppError::Db(err)
+    }
+}
+
+/// Whether `err` is a database error raised by the named constraint.
+pub fn violates_constraint(err: &sqlx::Error, constraint: &str) -> bool {
+    matches!(err, sqlx::Error::Database(db) if db.constraint() == Some(constraint))
+}
+
 pub type AppResult<T> = Result<T, AppError>;
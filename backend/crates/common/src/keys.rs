@@ -0,0 +1,102 @@
+//! Loading of asymmetric JWT keys from PEM files and their JWK representation.
+
+use anyhow::{Context, anyhow, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+};
+use simple_asn1::{ASN1Block, from_der};
+use std::{fs, path::Path};
+
+/// Load the private key used to sign tokens.
+pub(crate) fn load_signing_key(algorithm: Algorithm, path: &Path) -> anyhow::Result<EncodingKey> {
+    let pem = read_key_file(path)?;
+    let key = match algorithm {
+        Algorithm::RS256 => EncodingKey::from_rsa_pem(&pem),
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(&pem),
+        other => bail!("unsupported asymmetric JWT algorithm {other:?}"),
+    };
+    key.with_context(|| format!("invalid private key {}", path.display()))
+}
+
+/// Load a public key for verification, together with the JWK published for it.
+pub(crate) fn load_verifying_key(
+    algorithm: Algorithm,
+    kid: &str,
+    path: &Path,
+) -> anyhow::Result<(DecodingKey, Jwk)> {
+    let pem_bytes = read_key_file(path)?;
+    let invalid = || format!("invalid public key {}", path.display());
+    let pem = pem::parse(&pem_bytes).with_context(invalid)?;
+    let subject_public_key = spki_subject_public_key(pem.contents()).with_context(invalid)?;
+
+    let (dec, params, key_algorithm) = match algorithm {
+        Algorithm::RS256 => {
+            let (n, e) = rsa_components(&subject_public_key).with_context(invalid)?;
+            let params = AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(&n),
+                e: URL_SAFE_NO_PAD.encode(&e),
+            });
+            (
+                DecodingKey::from_rsa_raw_components(&n, &e),
+                params,
+                KeyAlgorithm::RS256,
+            )
+        }
+        Algorithm::EdDSA => {
+            let x = URL_SAFE_NO_PAD.encode(&subject_public_key);
+            let dec = DecodingKey::from_ed_components(&x).with_context(invalid)?;
+            let params = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x,
+            });
+            (dec, params, KeyAlgorithm::EdDSA)
+        }
+        other => bail!("unsupported asymmetric JWT algorithm {other:?}"),
+    };
+
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: params,
+    };
+    Ok((dec, jwk))
+}
+
+fn read_key_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("failed to read key file {}", path.display()))
+}
+
+/// Extract the `subjectPublicKey` bit string of a DER `SubjectPublicKeyInfo`.
+fn spki_subject_public_key(der: &[u8]) -> anyhow::Result<Vec<u8>> {
+    match from_der(der)?.as_slice() {
+        [ASN1Block::Sequence(_, fields)] => match fields.as_slice() {
+            [ASN1Block::Sequence(..), ASN1Block::BitString(_, _, key)] => Ok(key.clone()),
+            _ => Err(anyhow!("not a SubjectPublicKeyInfo")),
+        },
+        _ => Err(anyhow!("not a SubjectPublicKeyInfo")),
+    }
+}
+
+/// Big-endian modulus and exponent of a PKCS#1 `RSAPublicKey`.
+fn rsa_components(der: &[u8]) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+    match from_der(der)?.as_slice() {
+        [ASN1Block::Sequence(_, fields)] => match fields.as_slice() {
+            [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] => {
+                Ok((n.to_bytes_be().1, e.to_bytes_be().1))
+            }
+            _ => Err(anyhow!("not an RSA public key")),
+        },
+        _ => Err(anyhow!("not an RSA public key")),
+    }
+}
@@ -1,4 +1,8 @@
 pub mod auth;
 pub mod config;
 pub mod error;
+mod keys;
+pub mod notify;
 pub mod password;
+pub mod token;
+pub mod totp;
@@ -0,0 +1,149 @@
+use crate::{config::AppConfig, error::AppError};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use time::OffsetDateTime;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+/// How a notification reaches the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Email,
+    Sms,
+    /// Push to the user's devices; `to` is the user ID.
+    Push,
+}
+
+impl Channel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Sms => "sms",
+            Self::Push => "push",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "email" => Some(Self::Email),
+            "sms" => Some(Self::Sms),
+            "push" => Some(Self::Push),
+            _ => None,
+        }
+    }
+}
+
+/// A message for one recipient on one channel.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub channel: Channel,
+    /// Email address, phone number or user ID, depending on `channel`.
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers notifications to users. Implementations must not block for long;
+/// callers treat delivery as best effort.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send(&self, notification: &Notification) -> Result<(), AppError>;
+}
+
+/// Writes every notification to the tracing log. Development only: message
+/// bodies may contain secrets such as reset tokens.
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), AppError> {
+        tracing::info!(
+            channel = ?notification.channel,
+            to = %notification.to,
+            subject = %notification.subject,
+            body = %notification.body,
+            "notification"
+        );
+        Ok(())
+    }
+}
+
+/// Keeps notifications in memory; for tests that construct it and inspect
+/// what was sent. Not selectable through `NOTIFIER`, since nothing else could
+/// read what it keeps.
+#[derive(Default)]
+pub struct MemoryNotifier {
+    sent: Mutex<Vec<Notification>>,
+}
+
+impl MemoryNotifier {
+    /// Everything sent so far, oldest first.
+    pub fn sent(&self) -> Vec<Notification> {
+        self.sent.lock().expect("notifier lock poisoned").clone()
+    }
+}
+
+#[async_trait]
+impl Notifier for MemoryNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), AppError> {
+        self.sent
+            .lock()
+            .expect("notifier lock poisoned")
+            .push(notification.clone());
+        Ok(())
+    }
+}
+
+/// Appends every notification as a JSON line to a file, so local setups and
+/// tests can pick up the delivered tokens.
+pub struct FileNotifier {
+    path: PathBuf,
+}
+
+impl FileNotifier {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[derive(Serialize)]
+struct FileRecord<'a> {
+    #[serde(with = "time::serde::rfc3339")]
+    sent_at: OffsetDateTime,
+    #[serde(flatten)]
+    notification: &'a Notification,
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), AppError> {
+        let record = FileRecord {
+            sent_at: OffsetDateTime::now_utc(),
+            notification,
+        };
+        let mut line = serde_json::to_vec(&record).map_err(anyhow::Error::from)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| AppError::Other(e.into()))?;
+        file.write_all(&line)
+            .await
+            .map_err(|e| AppError::Other(e.into()))
+    }
+}
+
+/// Build the notifier selected by `NOTIFIER` (`log` or `file`).
+pub fn notifier_from_config(cfg: &AppConfig) -> Arc<dyn Notifier> {
+    match cfg.notifier.as_str() {
+        "log" => Arc::new(LogNotifier),
+        "file" => Arc::new(FileNotifier::new(&cfg.notifier_file)),
+        other => panic!("unsupported NOTIFIER `{other}`; expected `log` or `file`"),
+    }
+}
@@ -1,18 +1,128 @@
 use crate::error::AppError;
-use bcrypt::{BcryptError, DEFAULT_COST, hash, verify};
+use argon2::{
+    Algorithm, Argon2, Params, Version,
+    password_hash::{
+        self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng,
+    },
+};
 
-/// Hash a plaintext password using bcrypt.
+/// Argon2id cost used for new hashes (OWASP baseline: 19 MiB, 2 passes, 1 lane).
+/// Stored hashes with different parameters are upgraded on the next login.
+const ARGON2_M_COST: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+
+fn argon2() -> Argon2<'static> {
+    let params = Params::new(ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST, None)
+        .expect("valid argon2 parameters");
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+fn hash_error(err: password_hash::Error) -> AppError {
+    AppError::Other(anyhow::anyhow!("password hash: {err}"))
+}
+
+fn is_bcrypt(hashed: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| hashed.starts_with(prefix))
+}
+
+/// Hash a plaintext password using argon2id.
 pub fn hash_password(plaintext: &str) -> Result<String, AppError> {
-    hash(plaintext, DEFAULT_COST).map_err(|e| AppError::Other(e.into()))
+    let salt = SaltString::generate(&mut OsRng);
+    argon2()
+        .hash_password(plaintext.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(hash_error)
 }
 
-/// Verify a plaintext password against a bcrypt hash.
+/// Verify a plaintext password against an argon2 or (legacy) bcrypt hash.
 pub fn verify_password(plaintext: &str, hashed: &str) -> Result<bool, AppError> {
-    match verify(plaintext, hashed) {
-        Ok(result) => Ok(result),
-        Err(BcryptError::InvalidHash(_)) | Err(BcryptError::InvalidCost(_)) => {
-            Ok(plaintext == hashed)
+    if is_bcrypt(hashed) {
+        return bcrypt::verify(plaintext, hashed).map_err(|e| AppError::Other(e.into()));
+    }
+    let parsed = PasswordHash::new(hashed).map_err(hash_error)?;
+    match argon2().verify_password(plaintext.as_bytes(), &parsed) {
+        Ok(()) => Ok(true),
+        Err(password_hash::Error::Password) => Ok(false),
+        Err(err) => Err(hash_error(err)),
+    }
+}
+
+/// Whether `hashed` was produced by anything other than the current argon2id
+/// parameters and should be replaced after a successful verification.
+pub fn needs_rehash(hashed: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(hashed) else {
+        return true;
+    };
+    if parsed.algorithm != Algorithm::Argon2id.ident() {
+        return true;
+    }
+    let Ok(params) = Params::try_from(&parsed) else {
+        return true;
+    };
+    parsed.version != Some(Version::V0x13.into())
+        || params.m_cost() != ARGON2_M_COST
+        || params.t_cost() != ARGON2_T_COST
+        || params.p_cost() != ARGON2_P_COST
+}
+
+/// Strength requirements for newly chosen passwords.
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: false,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Check `password` against the policy, listing every unmet rule in the
+    /// `BadRequest` message.
+    pub fn validate(&self, password: &str) -> Result<(), AppError> {
+        let length = password.chars().count();
+        let mut problems = Vec::new();
+        if length < self.min_length {
+            problems.push(format!("be at least {} characters long", self.min_length));
+        }
+        if length > self.max_length {
+            problems.push(format!("be at most {} characters long", self.max_length));
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            problems.push("contain a lowercase letter".into());
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            problems.push("contain an uppercase letter".into());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            problems.push("contain a digit".into());
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            problems.push("contain a symbol".into());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::BadRequest(format!(
+                "password must {}",
+                problems.join(", ")
+            )))
         }
-        Err(err) => Err(AppError::Other(err.into())),
     }
 }
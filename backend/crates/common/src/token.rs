@@ -0,0 +1,22 @@
+use rand::{Rng, RngCore};
+use sha2::{Digest, Sha256};
+
+const OPAQUE_TOKEN_BYTES: usize = 32;
+
+/// Generate a random, URL-safe opaque token suitable for refresh/reset tokens.
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; OPAQUE_TOKEN_BYTES];
+    rand::rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Generate a numeric one-time code of `digits` digits, e.g. `"042917"`.
+pub fn generate_numeric_code(digits: u32) -> String {
+    let n = rand::rng().random_range(0..10u64.pow(digits));
+    format!("{n:0width$}", width = digits as usize)
+}
+
+/// Hash an opaque token for storage; only the hash is ever persisted.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
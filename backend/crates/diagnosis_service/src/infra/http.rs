@@ -10,7 +10,7 @@ use axum::{
     routing::{get, patch},
 };
 use common::{
-    auth::{AuthUser, JwtKeys, Role, ensure_user_role},
+    auth::{AuthUser, Doctor, RequireRole, Strict, jwt_keys_from_config},
     config::AppConfig,
     error::{AppError, AppResult},
 };
@@ -21,13 +21,12 @@ use uuid::Uuid;
 #[derive(Clone)]
 pub struct Ctx {
     svc: DiagnosesService<SqlxDiagnosesRepo>,
-    pool: PgPool,
 }
 
 impl Ctx {
     pub fn new(pool: PgPool) -> Self {
-        let svc = DiagnosesService::new(SqlxDiagnosesRepo::new(pool.clone()));
-        Self { svc, pool }
+        let svc = DiagnosesService::new(SqlxDiagnosesRepo::new(pool));
+        Self { svc }
     }
 }
 
@@ -43,11 +42,10 @@ impl Ctx {
     security(("bearerAuth" = []))
 )]
 async fn history_by_patient(
-    AuthUser { user_id, .. }: AuthUser,
+    _: RequireRole<Doctor>,
     State(ctx): State<Ctx>,
     Path(patient_id): Path<Uuid>,
 ) -> AppResult<Json<Vec<DiagnosesResp>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
     let rows = ctx.svc.history_by_patient(patient_id).await?;
     if rows.is_empty() {
         return Err(AppError::NotFound);
@@ -67,11 +65,10 @@ async fn history_by_patient(
     security(("bearerAuth" = []))
 )]
 async fn patinet_info(
-    AuthUser { user_id, .. }: AuthUser,
+    _: RequireRole<Doctor>,
     State(ctx): State<Ctx>,
     Path(patient_id): Path<Uuid>,
 ) -> AppResult<Json<Option<PatientInfoResp>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
     let rows = ctx.svc.patinet_info(patient_id).await?;
     if rows.is_none() {
         return Err(AppError::NotFound);
@@ -91,13 +88,11 @@ async fn patinet_info(
     security(("bearerAuth" = []))
 )]
 async fn create(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor, Strict>,
     State(ctx): State<Ctx>,
     Path(patient_id): Path<Uuid>,
     Json(req): Json<DiagnosesReq>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-
     ctx.svc.create(req, patient_id, user_id).await?;
 
     Ok(StatusCode::CREATED)
@@ -116,22 +111,20 @@ async fn create(
     security(("bearerAuth" = []))
 )]
 async fn update(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Doctor, Strict>,
     State(ctx): State<Ctx>,
     Path(diagnosis_id): Path<i32>,
     Json(req): Json<UpdateDiagnosesReq>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-
     ctx.svc.update(req, diagnosis_id, user_id).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
 pub fn router(pool: PgPool) -> Router {
-    let ctx = Ctx::new(pool);
+    let ctx = Ctx::new(pool.clone());
     let cfg = AppConfig::from_env();
-    let jwt_keys = JwtKeys::from_secret(&cfg.jwt_secret);
+    let jwt_keys = jwt_keys_from_config(&cfg);
     Router::new()
         .route(
             "/diagnoses/by-patient/{patient_id}",
@@ -141,6 +134,7 @@ pub fn router(pool: PgPool) -> Router {
         .route("/diagnoses/by-patient/{patient_id}/info", get(patinet_info))
         .with_state(ctx)
         .layer(Extension(jwt_keys))
+        .layer(Extension(pool))
 }
 
 #[derive(OpenApi, Default)]
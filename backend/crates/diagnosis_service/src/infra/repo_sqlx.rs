@@ -1,9 +1,6 @@
 use super::super::app::DiagnosesRepo;
 use super::super::domain::*;
-use common::{
-    auth::{Role, ensure_user_role},
-    error::{AppError, AppResult},
-};
+use common::error::{AppError, AppResult};
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -52,8 +49,6 @@ impl DiagnosesRepo for SqlxDiagnosesRepo {
         diagnosis_id: i32,
         doctor_id: Uuid,
     ) -> AppResult<()> {
-        ensure_user_role(&self.pool, doctor_id, Role::Doctor).await?;
-
         let existing = sqlx::query!(
             r#"SELECT doctor_id FROM diagnoses WHERE diagnosis_id = $1"#,
             diagnosis_id
@@ -87,8 +82,6 @@ impl DiagnosesRepo for SqlxDiagnosesRepo {
         patient_id: Uuid,
         doctor_id: Uuid,
     ) -> AppResult<()> {
-        ensure_user_role(&self.pool, doctor_id, Role::Doctor).await?;
-
         let appointment = sqlx::query!(
             r#"
                 SELECT a.patient_id, ts.doctor_id
@@ -120,7 +113,7 @@ impl DiagnosesRepo for SqlxDiagnosesRepo {
         .execute(&self.pool)
         .await?;
         if rows.rows_affected() == 0 {
-            return Err(AppError::Conflict);
+            return Err(AppError::Conflict("diagnosis was not recorded".into()));
         }
         Ok(())
     }
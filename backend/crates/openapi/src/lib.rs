@@ -11,6 +11,7 @@ use utoipa_swagger_ui::{Config, SwaggerUi};
     nest(
         (path = "/appointments", api = appointment_service::ApiDoc),
         (path = "/users", api = auth_service::ApiDoc),
+        (path = "/admin", api = auth_service::AdminApiDoc),
         (path = "/diagnoses", api = diagnosis_service::ApiDoc),
         (path = "/prescriptions", api = prescription_service::ApiDoc),
         (path = "/orders", api = order_service::ApiDoc),
@@ -1,6 +1,9 @@
-use crate::domain::{CreateOrderItemReq, CreateOrderReq, OrderDetail};
+use crate::domain::{
+    CoverageRule, CreateOrderItemReq, CreateOrderReq, ItemCoverage, OrderDetail, PricedItem,
+};
 use common::error::{AppError, AppResult};
 use db::PgTx;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 #[expect(async_fn_in_trait)]
@@ -12,11 +15,26 @@ pub trait OrderRepo: Send + Sync {
         patient_id: Uuid,
         req: &CreateOrderReq,
     ) -> AppResult<i32>;
+    /// Coverage rules of the patient's medical rights that are approved and
+    /// valid today.
+    async fn coverage_rules(
+        &self,
+        tx: &mut PgTx<'_>,
+        patient_id: Uuid,
+    ) -> AppResult<Vec<CoverageRule>>;
+    /// Price the lines from the medicine catalogue; `400` for an unknown
+    /// medicine.
+    async fn price_items(
+        &self,
+        tx: &mut PgTx<'_>,
+        items: &[CreateOrderItemReq],
+    ) -> AppResult<Vec<PricedItem>>;
     async fn insert_order_item(
         &self,
         tx: &mut PgTx<'_>,
         order_id: i32,
-        item: &CreateOrderItemReq,
+        item: &PricedItem,
+        coverage: ItemCoverage,
     ) -> AppResult<()>;
     async fn confirm_order(
         &self,
@@ -46,9 +64,14 @@ impl<R: OrderRepo> OrderService<R> {
         patient_id: Uuid,
         req: &CreateOrderReq,
     ) -> AppResult<i32> {
+        let items = self.repo.price_items(tx, &req.items).await?;
         let order_id = self.repo.create_order(tx, patient_id, req).await?;
-        for item in &req.items {
-            self.repo.insert_order_item(tx, order_id, item).await?;
+        let rules = self.repo.coverage_rules(tx, patient_id).await?;
+        let coverage = apply_coverage(&items, &rules);
+        for (item, coverage) in items.iter().zip(coverage) {
+            self.repo
+                .insert_order_item(tx, order_id, item, coverage)
+                .await?;
         }
         Ok(order_id)
     }
@@ -66,3 +89,148 @@ impl<R: OrderRepo> OrderService<R> {
         }
     }
 }
+
+/// Split each line between the patient's medical rights and the patient.
+/// Every line is paid by at most one right: whichever covers the most of it,
+/// after what that right has already paid towards earlier lines of the order
+/// is deducted from its cap.
+pub fn apply_coverage(items: &[PricedItem], rules: &[CoverageRule]) -> Vec<ItemCoverage> {
+    let mut remaining: HashMap<i32, f64> = rules
+        .iter()
+        .filter_map(|rule| rule.order_cap.map(|cap| (rule.mr_id, cap)))
+        .collect();
+    items
+        .iter()
+        .map(|item| {
+            let line_total = round_cents(round_cents(item.unit_price) * f64::from(item.amount));
+            let best = rules
+                .iter()
+                .filter(|rule| rule.covered_medicines.contains(&item.medicine_id))
+                .map(|rule| {
+                    let share = line_total * (100.0 - rule.copay_percent) / 100.0;
+                    let limit = remaining.get(&rule.mr_id).copied().unwrap_or(f64::INFINITY);
+                    (rule.mr_id, round_cents(share.min(limit)))
+                })
+                .filter(|&(_, covered)| covered > 0.0)
+                .fold(None, |best: Option<(i32, f64)>, candidate| match best {
+                    Some(current) if current.1 >= candidate.1 => Some(current),
+                    _ => Some(candidate),
+                });
+            let Some((mr_id, covered_amount)) = best else {
+                return ItemCoverage::default();
+            };
+            if let Some(left) = remaining.get_mut(&mr_id) {
+                *left = round_cents(*left - covered_amount);
+            }
+            ItemCoverage {
+                mr_id: Some(mr_id),
+                covered_amount,
+            }
+        })
+        .collect()
+}
+
+pub fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(medicine_id: i32, amount: i32, unit_price: f64) -> PricedItem {
+        PricedItem {
+            medicine_id,
+            amount,
+            unit_price,
+        }
+    }
+
+    fn rule(
+        mr_id: i32,
+        copay_percent: f64,
+        order_cap: Option<f64>,
+        covered: &[i32],
+    ) -> CoverageRule {
+        CoverageRule {
+            mr_id,
+            copay_percent,
+            order_cap,
+            covered_medicines: covered.to_vec(),
+        }
+    }
+
+    fn covered(mr_id: i32, covered_amount: f64) -> ItemCoverage {
+        ItemCoverage {
+            mr_id: Some(mr_id),
+            covered_amount,
+        }
+    }
+
+    #[test]
+    fn round_cents_rounds_half_away_from_zero() {
+        assert_eq!(round_cents(1.005_000_1), 1.01);
+        assert_eq!(round_cents(2.344), 2.34);
+        assert_eq!(round_cents(0.0), 0.0);
+    }
+
+    #[test]
+    fn no_rules_leaves_every_line_to_the_patient() {
+        let coverage = apply_coverage(&[item(1, 2, 10.0)], &[]);
+        assert_eq!(coverage, vec![ItemCoverage::default()]);
+    }
+
+    #[test]
+    fn uncovered_medicine_is_not_paid() {
+        let coverage = apply_coverage(&[item(2, 1, 10.0)], &[rule(1, 0.0, None, &[1])]);
+        assert_eq!(coverage, vec![ItemCoverage::default()]);
+    }
+
+    #[test]
+    fn copay_is_deducted_and_rounded() {
+        let coverage = apply_coverage(&[item(1, 3, 3.33)], &[rule(1, 30.0, None, &[1])]);
+        // 9.99 * 0.7 = 6.993
+        assert_eq!(coverage, vec![covered(1, 6.99)]);
+    }
+
+    #[test]
+    fn full_copay_covers_nothing() {
+        let coverage = apply_coverage(&[item(1, 1, 10.0)], &[rule(1, 100.0, None, &[1])]);
+        assert_eq!(coverage, vec![ItemCoverage::default()]);
+    }
+
+    #[test]
+    fn cap_is_shared_across_the_lines_of_an_order() {
+        let coverage = apply_coverage(
+            &[item(1, 1, 80.0), item(2, 1, 80.0), item(1, 1, 10.0)],
+            &[rule(1, 0.0, Some(100.0), &[1, 2])],
+        );
+        assert_eq!(
+            coverage,
+            vec![covered(1, 80.0), covered(1, 20.0), ItemCoverage::default()]
+        );
+    }
+
+    #[test]
+    fn the_right_paying_most_wins_each_line() {
+        let rules = [
+            rule(1, 50.0, None, &[1, 2]),
+            rule(2, 10.0, Some(30.0), &[1]),
+        ];
+        let coverage = apply_coverage(&[item(1, 1, 100.0), item(1, 1, 100.0)], &rules);
+        // Right 2 pays 30 (its cap) < 50, so right 1 wins both lines.
+        assert_eq!(coverage, vec![covered(1, 50.0), covered(1, 50.0)]);
+
+        let coverage = apply_coverage(&[item(1, 1, 20.0), item(1, 1, 20.0)], &rules);
+        // Right 2 pays 18 of the first line; only 12 of its cap is left for
+        // the second, so right 1 (10) loses there too.
+        assert_eq!(coverage, vec![covered(2, 18.0), covered(2, 12.0)]);
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_right() {
+        let rules = [rule(1, 20.0, None, &[1]), rule(2, 20.0, None, &[1])];
+        let coverage = apply_coverage(&[item(1, 1, 50.0)], &rules);
+        assert_eq!(coverage, vec![covered(1, 40.0)]);
+    }
+}
@@ -38,6 +38,17 @@ pub struct OrderItemSummary {
     pub medicine_name: String,
     #[schema(nullable = true)]
     pub img_link: Option<String>,
+    /// `price * amount`.
+    pub line_total: f64,
+    /// Part of `line_total` paid by the medical right.
+    pub covered_amount: f64,
+    /// Part of `line_total` paid by the patient.
+    pub patient_amount: f64,
+    /// Medical right that covered this line, if any.
+    #[schema(nullable = true)]
+    pub mr_id: Option<i32>,
+    #[schema(nullable = true)]
+    pub medical_right: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -50,16 +61,20 @@ pub struct OrderDetail {
     #[schema(nullable = true)]
     pub payment_platform: Option<String>,
     pub tot_price: f64,
+    /// Sum of `covered_amount` over the items.
+    pub covered_total: f64,
+    /// Sum of `patient_amount` over the items; what the patient pays.
+    pub patient_total: f64,
     pub items: Vec<OrderItemSummary>,
 }
 
+/// One line of a new order. The price is always the catalogue price of the
+/// medicine; a `price` sent by the client is ignored.
 #[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct CreateOrderItemReq {
     pub medicine_id: i32,
     #[schema(example = 1)]
     pub amount: i32,
-    #[schema(example = 120.0)]
-    pub price: f64,
 }
 
 #[derive(Debug, Clone, Deserialize, ToSchema)]
@@ -76,3 +91,29 @@ pub struct CreateOrderReq {
 pub struct CreateOrderResp {
     pub order_id: i32,
 }
+
+/// Coverage terms of a medical right the patient currently holds.
+#[derive(Debug, Clone)]
+pub struct CoverageRule {
+    pub mr_id: i32,
+    /// Share of a covered line the patient still pays, 0 to 100.
+    pub copay_percent: f64,
+    /// Most this right pays towards one order.
+    pub order_cap: Option<f64>,
+    pub covered_medicines: Vec<i32>,
+}
+
+/// An order line priced from the medicine catalogue.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PricedItem {
+    pub medicine_id: i32,
+    pub amount: i32,
+    pub unit_price: f64,
+}
+
+/// How one order line is paid for.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ItemCoverage {
+    pub mr_id: Option<i32>,
+    pub covered_amount: f64,
+}
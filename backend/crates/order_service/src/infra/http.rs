@@ -10,7 +10,7 @@ use axum::{
     routing::{get, post},
 };
 use common::{
-    auth::{AuthUser, JwtKeys, Role, ensure_user_role},
+    auth::{AuthUser, Patient, RequireRole, jwt_keys_from_config},
     config::AppConfig,
     error::{AppError, AppResult},
 };
@@ -46,10 +46,9 @@ impl Ctx {
     security(("bearerAuth" = []))
 )]
 async fn list_orders(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<Vec<OrderDetail>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     let orders = ctx.svc.list_orders(user_id).await?;
     Ok(Json(orders))
 }
@@ -66,11 +65,10 @@ async fn list_orders(
     security(("bearerAuth" = []))
 )]
 async fn create_order(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Json(req): Json<CreateOrderReq>,
 ) -> AppResult<(StatusCode, Json<CreateOrderResp>)> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     for item in &req.items {
         if item.amount <= 0 {
             return Err(AppError::BadRequest("amount must be positive".into()));
@@ -94,11 +92,10 @@ async fn create_order(
     security(("bearerAuth" = []))
 )]
 async fn confirm_order(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Path(order_id): Path<i32>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     let mut tx = ctx.begin_tx().await?;
     ctx.svc.confirm_order(&mut tx, user_id, order_id).await?;
     tx.commit().await?;
@@ -108,13 +105,14 @@ async fn confirm_order(
 pub fn router(pool: PgPool) -> Router {
     let ctx = Ctx::new(pool.clone());
     let cfg = AppConfig::from_env();
-    let jwt_keys = JwtKeys::from_secret(&cfg.jwt_secret);
+    let jwt_keys = jwt_keys_from_config(&cfg);
     Router::new()
         .route("/orders", get(list_orders))
         .route("/orders", post(create_order))
         .route("/orders/{order_id}/confirm", post(confirm_order))
         .with_state(ctx)
         .layer(Extension(jwt_keys))
+        .layer(Extension(pool))
 }
 
 #[derive(OpenApi, Default)]
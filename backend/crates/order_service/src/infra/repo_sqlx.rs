@@ -1,8 +1,11 @@
 use crate::{
-    app::OrderRepo,
-    domain::{CreateOrderItemReq, CreateOrderReq, OrderDetail, OrderItemSummary, OrderStatus},
+    app::{OrderRepo, round_cents},
+    domain::{
+        CoverageRule, CreateOrderItemReq, CreateOrderReq, ItemCoverage, OrderDetail,
+        OrderItemSummary, OrderStatus, PricedItem,
+    },
 };
-use common::error::AppResult;
+use common::error::{AppError, AppResult};
 use db::PgTx;
 use sqlx::PgPool;
 use std::collections::HashMap;
@@ -51,11 +54,15 @@ impl OrderRepo for SqlxOrderRepo {
                     m.medicine_name,
                     oi.quantity,
                     oi.unit_price::float8 AS "unit_price!",
-                    m.image_url AS "image_url?"
+                    m.image_url AS "image_url?",
+                    oi.covered_amount::float8 AS "covered_amount!",
+                    oi.mr_id,
+                    mr.name AS "medical_right?"
                 FROM order_items oi
                 JOIN medicines m ON m.medicine_id = oi.medicine_id
+                LEFT JOIN medical_rights mr ON mr.mr_id = oi.mr_id
                 WHERE oi.order_id = ANY($1)
-                ORDER BY oi.order_id
+                ORDER BY oi.order_id, oi.order_item_id
                 "#,
                 &order_ids
             )
@@ -63,6 +70,7 @@ impl OrderRepo for SqlxOrderRepo {
             .await?;
 
             for item in items {
+                let line_total = round_cents(item.unit_price * f64::from(item.quantity));
                 items_map
                     .entry(item.order_id)
                     .or_default()
@@ -72,6 +80,11 @@ impl OrderRepo for SqlxOrderRepo {
                         price: item.unit_price,
                         medicine_name: item.medicine_name,
                         img_link: item.image_url,
+                        line_total,
+                        covered_amount: item.covered_amount,
+                        patient_amount: round_cents(line_total - item.covered_amount),
+                        mr_id: item.mr_id,
+                        medical_right: item.medical_right,
                     });
             }
         }
@@ -84,6 +97,8 @@ impl OrderRepo for SqlxOrderRepo {
                     .iter()
                     .map(|item| item.price * item.amount as f64)
                     .sum();
+                let covered_total = round_cents(items.iter().map(|item| item.covered_amount).sum());
+                let patient_total = round_cents(items.iter().map(|item| item.patient_amount).sum());
                 OrderDetail {
                     order_id: order.order_id,
                     status_code: order.status.code(),
@@ -91,6 +106,8 @@ impl OrderRepo for SqlxOrderRepo {
                     shipping_platform: order.shipping_platform,
                     payment_platform: order.payment_platform,
                     tot_price,
+                    covered_total,
+                    patient_total,
                     items,
                 }
             })
@@ -120,21 +137,89 @@ impl OrderRepo for SqlxOrderRepo {
         Ok(rec.order_id)
     }
 
+    async fn coverage_rules(
+        &self,
+        tx: &mut PgTx<'_>,
+        patient_id: Uuid,
+    ) -> AppResult<Vec<CoverageRule>> {
+        let rules = sqlx::query_as!(
+            CoverageRule,
+            r#"
+            SELECT
+                mr.mr_id,
+                mr.copay_percent::float8 AS "copay_percent!",
+                mr.order_cap::float8 AS order_cap,
+                ARRAY(
+                    SELECT mrm.medicine_id FROM medical_right_medicines mrm
+                    WHERE mrm.mr_id = mr.mr_id
+                ) AS "covered_medicines!"
+            FROM user_mr um
+            JOIN medical_rights mr ON mr.mr_id = um.mr_id
+            WHERE um.patient_id = $1
+              AND um.status = 'APPROVED'
+              AND (um.valid_from IS NULL OR um.valid_from <= current_date)
+              AND (um.valid_until IS NULL OR um.valid_until >= current_date)
+            ORDER BY mr.mr_id
+            "#,
+            patient_id
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        Ok(rules)
+    }
+
+    async fn price_items(
+        &self,
+        tx: &mut PgTx<'_>,
+        items: &[CreateOrderItemReq],
+    ) -> AppResult<Vec<PricedItem>> {
+        let ids: Vec<i32> = items.iter().map(|item| item.medicine_id).collect();
+        let prices: HashMap<i32, f64> = sqlx::query!(
+            r#"
+            SELECT medicine_id, unit_price::float8 AS "unit_price!"
+            FROM medicines
+            WHERE medicine_id = ANY($1)
+            "#,
+            &ids
+        )
+        .fetch_all(&mut **tx)
+        .await?
+        .into_iter()
+        .map(|row| (row.medicine_id, row.unit_price))
+        .collect();
+        items
+            .iter()
+            .map(|item| {
+                let unit_price = prices.get(&item.medicine_id).copied().ok_or_else(|| {
+                    AppError::BadRequest(format!("unknown medicine {}", item.medicine_id))
+                })?;
+                Ok(PricedItem {
+                    medicine_id: item.medicine_id,
+                    amount: item.amount,
+                    unit_price,
+                })
+            })
+            .collect()
+    }
+
     async fn insert_order_item(
         &self,
         tx: &mut PgTx<'_>,
         order_id: i32,
-        item: &CreateOrderItemReq,
+        item: &PricedItem,
+        coverage: ItemCoverage,
     ) -> AppResult<()> {
         sqlx::query!(
             r#"
-            INSERT INTO order_items (order_id, medicine_id, quantity, unit_price)
-            VALUES ($1, $2, $3, $4::float8)
+            INSERT INTO order_items (order_id, medicine_id, quantity, unit_price, covered_amount, mr_id)
+            VALUES ($1, $2, $3, $4::float8, $5::float8, $6)
             "#,
             order_id,
             item.medicine_id,
             item.amount,
-            item.price
+            item.unit_price,
+            coverage.covered_amount,
+            coverage.mr_id
         )
         .execute(&mut **tx)
         .await?;
@@ -180,4 +265,7 @@ struct OrderItemRow {
     quantity: i32,
     unit_price: f64,
     image_url: Option<String>,
+    covered_amount: f64,
+    mr_id: Option<i32>,
+    medical_right: Option<String>,
 }
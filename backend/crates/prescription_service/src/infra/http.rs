@@ -13,7 +13,7 @@ use axum::{
     routing::{get, patch},
 };
 use common::{
-    auth::{AuthUser, JwtKeys, Role, ensure_user_role},
+    auth::{AuthUser, Doctor, RequireRole, Strict, jwt_keys_from_config},
     config::AppConfig,
     error::{AppError, AppResult},
 };
@@ -24,13 +24,12 @@ use uuid::Uuid;
 #[derive(Clone)]
 pub struct Ctx {
     svc: PrescriptionService<SqlxPrescriptionRepo>,
-    pool: PgPool,
 }
 
 impl Ctx {
     pub fn new(pool: PgPool) -> Self {
-        let svc = PrescriptionService::new(SqlxPrescriptionRepo::new(pool.clone()));
-        Self { svc, pool }
+        let svc = PrescriptionService::new(SqlxPrescriptionRepo::new(pool));
+        Self { svc }
     }
 }
 
@@ -46,12 +45,10 @@ impl Ctx {
     security(("bearerAuth" = []))
 )]
 async fn get_by_user_id(
-    AuthUser { user_id, .. }: AuthUser,
+    _: RequireRole<Doctor>,
     State(ctx): State<Ctx>,
     Path(patient_id): Path<Uuid>,
 ) -> AppResult<Json<Vec<Prescription>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-
     let rows = ctx.svc.prescription_by_patient(patient_id).await?;
     if rows.is_empty() {
         return Err(AppError::NotFound);
@@ -89,12 +86,10 @@ async fn get_by_user(
     security(("bearerAuth" = []))
 )]
 async fn get_medicine_info(
-    AuthUser { user_id, .. }: AuthUser,
+    _: RequireRole<Doctor>,
     State(ctx): State<Ctx>,
     Path(medicine_id): Path<i32>,
 ) -> AppResult<Json<MedicineInfo>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-
     let Some((medicine_id, medicine_name, img_link)) = ctx.svc.medicine_info(medicine_id).await?
     else {
         return Err(AppError::NotFound);
@@ -115,12 +110,10 @@ async fn get_medicine_info(
     security(("bearerAuth" = []))
 )]
 async fn create_prescription(
-    AuthUser { user_id, .. }: AuthUser,
+    _: RequireRole<Doctor, Strict>,
     State(ctx): State<Ctx>,
     Json(req): Json<CreatePrescriptionReq>,
 ) -> AppResult<(StatusCode, Json<PrescriptionIdResp>)> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-
     let id = ctx.svc.create_prescription(req.into()).await?;
     Ok((
         StatusCode::CREATED,
@@ -140,13 +133,11 @@ async fn create_prescription(
     security(("bearerAuth" = []))
 )]
 async fn update_prescription(
-    AuthUser { user_id, .. }: AuthUser,
+    _: RequireRole<Doctor, Strict>,
     State(ctx): State<Ctx>,
     Path(prescription_id): Path<i32>,
     Json(req): Json<UpdatePrescriptionReq>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-
     let input = UpdatePrescriptionInput::from_request(prescription_id, req);
     ctx.svc.update_prescription(input).await?;
     Ok(StatusCode::NO_CONTENT)
@@ -161,12 +152,10 @@ async fn update_prescription(
     security(("bearerAuth" = []))
 )]
 async fn delete_prescription(
-    AuthUser { user_id, .. }: AuthUser,
+    _: RequireRole<Doctor, Strict>,
     State(ctx): State<Ctx>,
     Path(prescription_id): Path<i32>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-
     ctx.svc.delete_prescription(prescription_id).await?;
     Ok(StatusCode::NO_CONTENT)
 }
@@ -182,12 +171,10 @@ async fn delete_prescription(
     security(("bearerAuth" = []))
 )]
 async fn search_medicines(
-    AuthUser { user_id, .. }: AuthUser,
+    _: RequireRole<Doctor>,
     State(ctx): State<Ctx>,
     Path(input): Path<String>,
 ) -> AppResult<Json<Vec<MedicineSearchItem>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Doctor).await?;
-
     let rows = ctx.svc.search_medicines(&input).await?;
     Ok(Json(
         rows.into_iter()
@@ -224,9 +211,9 @@ impl utoipa::Modify for SecurityAddon {
 }
 
 pub fn router(pool: PgPool) -> Router {
-    let ctx = Ctx::new(pool);
+    let ctx = Ctx::new(pool.clone());
     let cfg = AppConfig::from_env();
-    let jwt_keys = JwtKeys::from_secret(&cfg.jwt_secret);
+    let jwt_keys = jwt_keys_from_config(&cfg);
 
     Router::new()
         // Collection: list & create
@@ -244,4 +231,5 @@ pub fn router(pool: PgPool) -> Router {
         .route("/prescriptions/search/{input}", get(search_medicines))
         .with_state(ctx)
         .layer(Extension(jwt_keys))
+        .layer(Extension(pool))
 }
@@ -16,7 +16,7 @@ use axum::{
     routing::get,
 };
 use common::{
-    auth::{AuthUser, JwtKeys, Role, ensure_user_role},
+    auth::{AuthUser, Patient, RequireRole, jwt_keys_from_config},
     config::AppConfig,
     error::{AppError, AppResult},
 };
@@ -26,19 +26,14 @@ use utoipa::OpenApi;
 
 #[derive(Clone)]
 pub struct Ctx {
-    pool: PgPool,
     svc: ShippingService<SqlxShippingRepo>,
     map_provider: Option<MapProvider>,
 }
 
 impl Ctx {
     fn new(pool: PgPool, map_provider: Option<MapProvider>) -> Self {
-        let svc = ShippingService::new(SqlxShippingRepo::new(pool.clone()));
-        Self {
-            pool,
-            svc,
-            map_provider,
-        }
+        let svc = ShippingService::new(SqlxShippingRepo::new(pool));
+        Self { svc, map_provider }
     }
 }
 
@@ -58,10 +53,9 @@ struct OrdersQuery {
     security(("bearerAuth" = []))
 )]
 async fn get_address(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
 ) -> AppResult<Json<ShippingAddressResp>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     match ctx.svc.address(user_id).await? {
         Some(addr) => Ok(Json(addr)),
         None => Err(AppError::NotFound),
@@ -80,11 +74,10 @@ async fn get_address(
     security(("bearerAuth" = []))
 )]
 async fn create_address(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Json(req): Json<ShippingAddressReq>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     ctx.svc.upsert_address(user_id, &req).await?;
     Ok(StatusCode::CREATED)
 }
@@ -101,11 +94,10 @@ async fn create_address(
     security(("bearerAuth" = []))
 )]
 async fn update_address(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Json(req): Json<ShippingAddressReq>,
 ) -> AppResult<StatusCode> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     ctx.svc.update_address(user_id, &req).await?;
     Ok(StatusCode::NO_CONTENT)
 }
@@ -123,11 +115,10 @@ async fn update_address(
     security(("bearerAuth" = []))
 )]
 async fn list_orders(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Query(query): Query<OrdersQuery>,
 ) -> AppResult<Json<Vec<ShippingOrderSummary>>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     let filter = match query.status {
         None | Some(0) => None,
         Some(1) => Some(OrderStatus::PENDING),
@@ -152,11 +143,10 @@ async fn list_orders(
     security(("bearerAuth" = []))
 )]
 async fn order_status(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Path(order_id): Path<i32>,
 ) -> AppResult<Json<ShippingStatusTimeline>> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     let timeline = ctx.svc.order_timeline(user_id, order_id).await?;
     Ok(Json(timeline))
 }
@@ -173,11 +163,10 @@ async fn order_status(
     security(("bearerAuth" = []))
 )]
 async fn order_map(
-    AuthUser { user_id, .. }: AuthUser,
+    RequireRole(AuthUser { user_id, .. }, _): RequireRole<Patient>,
     State(ctx): State<Ctx>,
     Path(order_id): Path<i32>,
 ) -> AppResult<Response> {
-    ensure_user_role(&ctx.pool, user_id, Role::Patient).await?;
     let points = ctx.svc.map_points(user_id, order_id).await?;
 
     let image_bytes = if let Some(provider) = ctx.map_provider.as_ref() {
@@ -223,7 +212,7 @@ pub fn router(pool: PgPool) -> Router {
     let cfg = AppConfig::from_env();
     let map_provider = cfg.geoapify_api_key.clone().map(MapProvider::new);
     let ctx = Ctx::new(pool.clone(), map_provider);
-    let jwt_keys = JwtKeys::from_secret(&cfg.jwt_secret);
+    let jwt_keys = jwt_keys_from_config(&cfg);
     Router::new()
         .route(
             "/shipping/address",
@@ -234,6 +223,7 @@ pub fn router(pool: PgPool) -> Router {
         .route("/shipping/orders/{order_id}/map", get(order_map))
         .with_state(ctx)
         .layer(Extension(jwt_keys))
+        .layer(Extension(pool))
 }
 
 #[derive(OpenApi, Default)]
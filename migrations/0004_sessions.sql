@@ -0,0 +1,25 @@
+-- Login sessions. One row per device login; every refresh token issued for the
+-- session belongs to the same family, so revoking the session kills them all.
+CREATE TABLE IF NOT EXISTS sessions (
+  session_id     uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id        uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  created_at     timestamptz NOT NULL DEFAULT now(),
+  last_used_at   timestamptz NOT NULL DEFAULT now(),
+  revoked_at     timestamptz,
+  revoked_reason varchar
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions(user_id);
+
+-- Opaque refresh tokens (SHA-256 hashed). A token is single use: refreshing
+-- marks it used and issues a successor; presenting a used token again means it
+-- leaked, and the whole session is revoked.
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+  token_hash  text PRIMARY KEY,
+  session_id  uuid NOT NULL REFERENCES sessions(session_id) ON DELETE CASCADE,
+  issued_at   timestamptz NOT NULL DEFAULT now(),
+  expires_at  timestamptz NOT NULL,
+  used_at     timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_session ON refresh_tokens(session_id);
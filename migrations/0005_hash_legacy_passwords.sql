@@ -0,0 +1,8 @@
+SET search_path = public, pg_temp;
+
+-- Hash any password still stored as plaintext so login no longer needs a
+-- plaintext fallback. pgcrypto only offers bcrypt; these rows are upgraded to
+-- argon2id on the user's next successful login.
+UPDATE users
+SET password = crypt(password, gen_salt('bf', 12))
+WHERE password !~ '^\$(2[abxy]|argon2(id|i|d))\$';
@@ -0,0 +1,17 @@
+-- Failed login bookkeeping, shared by all API instances. Failures older than
+-- the configured window are forgotten on the next failure; a successful login
+-- clears the account row.
+CREATE TABLE IF NOT EXISTS account_login_failures (
+  user_id        uuid PRIMARY KEY REFERENCES users(user_id) ON DELETE CASCADE,
+  failed_count   int NOT NULL DEFAULT 0,
+  last_failed_at timestamptz NOT NULL DEFAULT now(),
+  locked_until   timestamptz
+);
+
+-- Same counters per client IP, across every account it tried.
+CREATE TABLE IF NOT EXISTS ip_login_failures (
+  ip_addr        text PRIMARY KEY,
+  failed_count   int NOT NULL DEFAULT 0,
+  last_failed_at timestamptz NOT NULL DEFAULT now(),
+  locked_until   timestamptz
+);
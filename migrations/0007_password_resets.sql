@@ -0,0 +1,11 @@
+-- Single-use password reset tokens (SHA-256 hashed). Requesting a new token
+-- discards the user's earlier unused ones.
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+  token_hash  text PRIMARY KEY,
+  user_id     uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  created_at  timestamptz NOT NULL DEFAULT now(),
+  expires_at  timestamptz NOT NULL,
+  used_at     timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS idx_password_reset_tokens_user ON password_reset_tokens(user_id);
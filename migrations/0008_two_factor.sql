@@ -0,0 +1,30 @@
+-- TOTP enrollment. A row without confirmed_at is a pending enrollment that
+-- does not affect login yet. last_used_step stops a code being replayed.
+CREATE TABLE IF NOT EXISTS user_totp (
+  user_id        uuid PRIMARY KEY REFERENCES users(user_id) ON DELETE CASCADE,
+  secret         text NOT NULL,            -- base32 shared secret
+  confirmed_at   timestamptz,
+  last_used_step bigint,
+  created_at     timestamptz NOT NULL DEFAULT now()
+);
+
+-- Single-use recovery codes (SHA-256 hashed), replaced on every confirmation.
+CREATE TABLE IF NOT EXISTS totp_recovery_codes (
+  user_id    uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  code_hash  text NOT NULL,
+  used_at    timestamptz,
+  PRIMARY KEY (user_id, code_hash)
+);
+
+-- Challenge handed out by the first login step of a 2FA account, redeemed
+-- together with a TOTP or recovery code. Opaque and SHA-256 hashed.
+CREATE TABLE IF NOT EXISTS login_challenges (
+  challenge_hash text PRIMARY KEY,
+  user_id        uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  created_at     timestamptz NOT NULL DEFAULT now(),
+  expires_at     timestamptz NOT NULL,
+  attempts       int NOT NULL DEFAULT 0,
+  used_at        timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_challenges_user ON login_challenges(user_id);
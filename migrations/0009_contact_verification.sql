@@ -0,0 +1,16 @@
+-- Verification status of the contact details; cleared whenever the value changes.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified_at timestamptz;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS phone_verified_at timestamptz;
+
+-- Outstanding one-time codes, at most one per user and channel. `target` is the
+-- address the code was sent to; confirming only succeeds while it is unchanged.
+CREATE TABLE IF NOT EXISTS contact_verifications (
+  user_id     uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  channel     varchar NOT NULL CHECK (channel IN ('email','phone')),
+  target      varchar NOT NULL,
+  code_hash   text NOT NULL,
+  created_at  timestamptz NOT NULL DEFAULT now(),
+  expires_at  timestamptz NOT NULL,
+  attempts    int NOT NULL DEFAULT 0,
+  PRIMARY KEY (user_id, channel)
+);
@@ -0,0 +1,2 @@
+-- Deactivated accounts cannot log in and their sessions are rejected.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS deactivated_at timestamptz;
@@ -0,0 +1,18 @@
+-- Patient medical-right assignments carry the member's policy number, a
+-- validity window and an approval status. Rows that existed before this
+-- migration were assigned by staff and count as approved and open-ended.
+ALTER TABLE user_mr ADD COLUMN IF NOT EXISTS policy_number varchar;
+ALTER TABLE user_mr ADD COLUMN IF NOT EXISTS valid_from date;
+ALTER TABLE user_mr ADD COLUMN IF NOT EXISTS valid_until date;
+ALTER TABLE user_mr ADD COLUMN IF NOT EXISTS status varchar NOT NULL DEFAULT 'APPROVED'
+  CHECK (status IN ('PENDING','APPROVED','REJECTED'));
+ALTER TABLE user_mr ADD COLUMN IF NOT EXISTS requested_at timestamptz NOT NULL DEFAULT now();
+ALTER TABLE user_mr ADD COLUMN IF NOT EXISTS decided_at timestamptz;
+ALTER TABLE user_mr ADD COLUMN IF NOT EXISTS decided_by uuid REFERENCES users(user_id) ON DELETE SET NULL;
+ALTER TABLE user_mr ADD COLUMN IF NOT EXISTS rejection_reason text;
+
+ALTER TABLE user_mr DROP CONSTRAINT IF EXISTS user_mr_validity_check;
+ALTER TABLE user_mr ADD CONSTRAINT user_mr_validity_check
+  CHECK (valid_until IS NULL OR valid_from IS NULL OR valid_until >= valid_from);
+
+CREATE INDEX IF NOT EXISTS idx_user_mr_pending ON user_mr(requested_at) WHERE status = 'PENDING';
@@ -0,0 +1,20 @@
+-- Coverage rules per medical right: the share of a covered medicine's price the
+-- patient still pays, an optional cap on the amount covered per order, and
+-- the medicines the right covers. Rights without covered medicines pay nothing.
+ALTER TABLE medical_rights ADD COLUMN IF NOT EXISTS copay_percent numeric(5,2) NOT NULL DEFAULT 0
+  CHECK (copay_percent BETWEEN 0 AND 100);
+ALTER TABLE medical_rights ADD COLUMN IF NOT EXISTS order_cap numeric(10,2)
+  CHECK (order_cap >= 0);
+
+CREATE TABLE IF NOT EXISTS medical_right_medicines (
+  mr_id       int NOT NULL REFERENCES medical_rights(mr_id) ON DELETE CASCADE,
+  medicine_id int NOT NULL REFERENCES medicines(medicine_id) ON DELETE CASCADE,
+  PRIMARY KEY (mr_id, medicine_id)
+);
+
+-- Coverage is fixed when the order is placed so later rule changes do not
+-- alter existing orders. `mr_id` is the right that paid for the line, if any.
+ALTER TABLE order_items ADD COLUMN IF NOT EXISTS covered_amount numeric(10,2) NOT NULL DEFAULT 0
+  CHECK (covered_amount >= 0);
+ALTER TABLE order_items ADD COLUMN IF NOT EXISTS mr_id int
+  REFERENCES medical_rights(mr_id) ON DELETE SET NULL;
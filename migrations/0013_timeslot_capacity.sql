@@ -0,0 +1,4 @@
+-- How many patients a timeslot takes on a single date. Bookings lock the
+-- timeslot row and count PENDING/ACCEPTED appointments before inserting.
+ALTER TABLE time_slots ADD COLUMN IF NOT EXISTS capacity int NOT NULL DEFAULT 1
+  CHECK (capacity > 0);
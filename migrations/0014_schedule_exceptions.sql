@@ -0,0 +1,55 @@
+-- One-off timeslots are offered on `valid_on` only; recurring timeslots have
+-- no `valid_on`. Recurring timeslots still clash with each other by weekday,
+-- one-off timeslots only with other one-off timeslots on the same date.
+-- A one-off timeslot may replace a cancelled occurrence of a recurring one,
+-- so that clash is checked when the one-off timeslot is added.
+ALTER TABLE time_slots ADD COLUMN IF NOT EXISTS valid_on date;
+
+ALTER TABLE time_slots DROP CONSTRAINT IF EXISTS time_slots_valid_on_day_ck;
+ALTER TABLE time_slots ADD CONSTRAINT time_slots_valid_on_day_ck
+  CHECK (valid_on IS NULL OR day_of_weeks = extract(dow FROM valid_on)::int);
+
+ALTER TABLE time_slots DROP CONSTRAINT IF EXISTS time_slots_no_overlap;
+ALTER TABLE time_slots
+  ADD CONSTRAINT time_slots_no_overlap
+  EXCLUDE USING gist (
+    doctor_id WITH =,
+    day_of_weeks WITH =,
+    (COALESCE(valid_on, 'infinity'::date)) WITH =,
+    int4range(start_minute, end_minute) WITH &&
+  );
+
+-- Clinic-wide closures; nothing can be booked on these dates.
+CREATE TABLE IF NOT EXISTS clinic_holidays (
+  holiday_date  date PRIMARY KEY,
+  name          varchar NOT NULL,
+  created_at    timestamptz NOT NULL DEFAULT now()
+);
+
+-- Per-doctor exceptions to the weekly schedule: whole-day LEAVE over a date
+-- range, or a single CANCELLED occurrence of one timeslot.
+CREATE TABLE IF NOT EXISTS schedule_exceptions (
+  exception_id  int GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  doctor_id     uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  kind          varchar NOT NULL CHECK (kind IN ('LEAVE','CANCELLED')),
+  start_date    date NOT NULL,
+  end_date      date NOT NULL,
+  timeslot_id   int REFERENCES time_slots(timeslot_id) ON DELETE CASCADE,
+  reason        text,
+  created_at    timestamptz NOT NULL DEFAULT now(),
+  CONSTRAINT schedule_exceptions_range_ck CHECK (start_date <= end_date),
+  CONSTRAINT schedule_exceptions_kind_ck CHECK (
+    (kind = 'LEAVE' AND timeslot_id IS NULL)
+    OR (kind = 'CANCELLED' AND timeslot_id IS NOT NULL AND start_date = end_date)
+  ),
+  CONSTRAINT schedule_exceptions_no_overlapping_leave
+    EXCLUDE USING gist (
+      doctor_id WITH =,
+      daterange(start_date, end_date, '[]') WITH &&
+    ) WHERE (kind = 'LEAVE')
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS uniq_cancelled_occurrence
+  ON schedule_exceptions(timeslot_id, start_date) WHERE kind = 'CANCELLED';
+CREATE INDEX IF NOT EXISTS idx_schedule_exceptions_doctor
+  ON schedule_exceptions(doctor_id, start_date);
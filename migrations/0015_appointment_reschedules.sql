@@ -0,0 +1,24 @@
+-- Per-doctor booking rules. Doctors without a row use the defaults.
+CREATE TABLE IF NOT EXISTS doctor_booking_policies (
+  doctor_id                    uuid PRIMARY KEY REFERENCES users(user_id) ON DELETE CASCADE,
+  -- Whether an ACCEPTED appointment stays ACCEPTED when the patient moves it,
+  -- rather than going back to PENDING.
+  keep_accepted_on_reschedule  boolean NOT NULL DEFAULT false,
+  updated_at                   timestamptz NOT NULL DEFAULT now()
+);
+
+-- Every slot an appointment was moved away from, oldest first.
+CREATE TABLE IF NOT EXISTS appointment_reschedules (
+  reschedule_id     int GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  appointment_id    int  NOT NULL REFERENCES appointments(appointment_id) ON DELETE CASCADE,
+  from_timeslot_id  int  NOT NULL REFERENCES time_slots(timeslot_id) ON DELETE RESTRICT,
+  from_date         date NOT NULL,
+  from_status       appointment_status NOT NULL,
+  to_timeslot_id    int  NOT NULL REFERENCES time_slots(timeslot_id) ON DELETE RESTRICT,
+  to_date           date NOT NULL,
+  rescheduled_by    uuid REFERENCES users(user_id) ON DELETE SET NULL,
+  rescheduled_at    timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_appointment_reschedules_appointment
+  ON appointment_reschedules(appointment_id, reschedule_id);
@@ -0,0 +1,27 @@
+-- Visit outcome states. Legal moves between states are enforced by
+-- `AppointmentStatus::can_transition_to` in the appointment service.
+ALTER TYPE appointment_status ADD VALUE IF NOT EXISTS 'CHECKED_IN';
+ALTER TYPE appointment_status ADD VALUE IF NOT EXISTS 'COMPLETED';
+ALTER TYPE appointment_status ADD VALUE IF NOT EXISTS 'NO_SHOW';
+
+-- Every status an appointment has been in, with who moved it there.
+CREATE TABLE IF NOT EXISTS appointment_transitions (
+  transition_id   int GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  appointment_id  int NOT NULL REFERENCES appointments(appointment_id) ON DELETE CASCADE,
+  from_status     appointment_status,
+  to_status       appointment_status NOT NULL,
+  actor_id        uuid REFERENCES users(user_id) ON DELETE SET NULL,
+  note            text,
+  created_at      timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_appointment_transitions_appointment
+  ON appointment_transitions(appointment_id, transition_id);
+
+-- Existing appointments start their log at their current status.
+INSERT INTO appointment_transitions (appointment_id, from_status, to_status, created_at)
+SELECT a.appointment_id, NULL, a.status, a.created_at
+FROM appointments a
+WHERE NOT EXISTS (
+  SELECT 1 FROM appointment_transitions t WHERE t.appointment_id = a.appointment_id
+);
@@ -0,0 +1,30 @@
+-- Patients waiting for a place with a doctor on a date that is fully booked.
+-- When a place frees up the oldest WAITING entry is OFFERED that place; the
+-- offer holds it until `offer_expires_at`, then it is CONFIRMED into an
+-- appointment or EXPIRED and passed on to the next patient.
+CREATE TABLE IF NOT EXISTS waitlist_entries (
+  entry_id             int GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  patient_id           uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  doctor_id            uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  date                 date NOT NULL,
+  status               varchar NOT NULL DEFAULT 'WAITING'
+                       CHECK (status IN ('WAITING','OFFERED','CONFIRMED','EXPIRED','LEFT')),
+  offered_timeslot_id  int REFERENCES time_slots(timeslot_id) ON DELETE SET NULL,
+  offer_expires_at     timestamptz,
+  appointment_id       int REFERENCES appointments(appointment_id) ON DELETE SET NULL,
+  created_at           timestamptz NOT NULL DEFAULT now(),
+  updated_at           timestamptz NOT NULL DEFAULT now(),
+  CONSTRAINT waitlist_offer_ck CHECK (
+    status <> 'OFFERED' OR (offered_timeslot_id IS NOT NULL AND offer_expires_at IS NOT NULL)
+  )
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS uniq_waitlist_active_entry
+  ON waitlist_entries(patient_id, doctor_id, date)
+  WHERE status IN ('WAITING','OFFERED');
+CREATE INDEX IF NOT EXISTS idx_waitlist_queue
+  ON waitlist_entries(doctor_id, date, created_at)
+  WHERE status = 'WAITING';
+CREATE INDEX IF NOT EXISTS idx_waitlist_offers
+  ON waitlist_entries(offered_timeslot_id, date)
+  WHERE status = 'OFFERED';
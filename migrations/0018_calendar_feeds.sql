@@ -0,0 +1,9 @@
+-- Secret per-user calendar subscription links. Calendar apps cannot send a
+-- bearer header, so the token in the URL is the credential; only its hash is
+-- stored. Creating a new link replaces the old one.
+CREATE TABLE IF NOT EXISTS calendar_feeds (
+  user_id       uuid PRIMARY KEY REFERENCES users(user_id) ON DELETE CASCADE,
+  token_hash    text NOT NULL UNIQUE,
+  created_at    timestamptz NOT NULL DEFAULT now(),
+  last_used_at  timestamptz
+);
@@ -0,0 +1,25 @@
+-- One row per reminder to send: appointment, visit start, offset and
+-- channel. The unique key makes queueing idempotent; a rescheduled visit has
+-- a new start and so gets its own reminders.
+CREATE TABLE IF NOT EXISTS reminder_deliveries (
+  delivery_id      bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  appointment_id   int NOT NULL REFERENCES appointments(appointment_id) ON DELETE CASCADE,
+  visit_start      timestamptz NOT NULL,
+  offset_minutes   int NOT NULL,
+  channel          varchar NOT NULL CHECK (channel IN ('email','sms','push')),
+  recipient        varchar NOT NULL,
+  -- PENDING until SENT; FAILED after the last attempt; SKIPPED when the
+  -- appointment was no longer ACCEPTED at that time or had moved.
+  status           varchar NOT NULL DEFAULT 'PENDING'
+                   CHECK (status IN ('PENDING','SENT','FAILED','SKIPPED')),
+  attempts         int NOT NULL DEFAULT 0,
+  last_error       text,
+  next_attempt_at  timestamptz NOT NULL DEFAULT now(),
+  sent_at          timestamptz,
+  created_at       timestamptz NOT NULL DEFAULT now(),
+  CONSTRAINT uniq_reminder_delivery UNIQUE (appointment_id, visit_start, offset_minutes, channel)
+);
+
+CREATE INDEX IF NOT EXISTS idx_reminder_deliveries_due
+  ON reminder_deliveries(next_attempt_at)
+  WHERE status = 'PENDING';
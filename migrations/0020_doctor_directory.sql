@@ -0,0 +1,4 @@
+-- Name search in the doctor directory. Queries must use the same
+-- expression for the index to apply.
+CREATE INDEX IF NOT EXISTS idx_users_name_search
+  ON users USING gin (to_tsvector('simple', first_name || ' ' || last_name));
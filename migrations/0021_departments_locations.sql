@@ -0,0 +1,65 @@
+-- Catalogue of departments and clinic locations. Doctor profiles and
+-- timeslots reference them; their free-text `department` and `place_name`
+-- columns are kept as copies of the catalogue name for existing readers.
+CREATE TABLE IF NOT EXISTS departments (
+  department_id  int GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  name           varchar NOT NULL,
+  created_at     timestamptz NOT NULL DEFAULT now(),
+  updated_at     timestamptz NOT NULL DEFAULT now()
+);
+CREATE UNIQUE INDEX IF NOT EXISTS uniq_department_name ON departments (lower(name));
+
+CREATE TABLE IF NOT EXISTS locations (
+  location_id  int GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  name         varchar NOT NULL,
+  building     varchar,
+  floor        varchar,
+  room         varchar,
+  latitude     double precision CHECK (latitude BETWEEN -90 AND 90),
+  longitude    double precision CHECK (longitude BETWEEN -180 AND 180),
+  created_at   timestamptz NOT NULL DEFAULT now(),
+  updated_at   timestamptz NOT NULL DEFAULT now(),
+  CONSTRAINT locations_coordinates_ck CHECK ((latitude IS NULL) = (longitude IS NULL))
+);
+CREATE UNIQUE INDEX IF NOT EXISTS uniq_location_name ON locations (lower(name));
+
+-- Spellings that differ only in case or surrounding blanks become one
+-- entry, named after the most common spelling.
+INSERT INTO departments (name)
+SELECT mode() WITHIN GROUP (ORDER BY btrim(department))
+FROM doctor_profile
+WHERE btrim(department) <> ''
+GROUP BY lower(btrim(department))
+ON CONFLICT ((lower(name))) DO NOTHING;
+
+ALTER TABLE doctor_profile ADD COLUMN IF NOT EXISTS department_id int;
+ALTER TABLE doctor_profile DROP CONSTRAINT IF EXISTS doctor_profile_department_fk;
+ALTER TABLE doctor_profile ADD CONSTRAINT doctor_profile_department_fk
+  FOREIGN KEY (department_id) REFERENCES departments(department_id) ON DELETE RESTRICT;
+CREATE INDEX IF NOT EXISTS idx_doctor_profile_department ON doctor_profile(department_id);
+
+UPDATE doctor_profile dp
+SET department_id = d.department_id,
+    department = d.name
+FROM departments d
+WHERE lower(btrim(dp.department)) = lower(d.name);
+UPDATE doctor_profile SET department = NULL WHERE department_id IS NULL;
+
+INSERT INTO locations (name)
+SELECT mode() WITHIN GROUP (ORDER BY btrim(place_name))
+FROM time_slots
+GROUP BY lower(btrim(place_name))
+ON CONFLICT ((lower(name))) DO NOTHING;
+
+ALTER TABLE time_slots ADD COLUMN IF NOT EXISTS location_id int;
+ALTER TABLE time_slots DROP CONSTRAINT IF EXISTS time_slots_location_fk;
+ALTER TABLE time_slots ADD CONSTRAINT time_slots_location_fk
+  FOREIGN KEY (location_id) REFERENCES locations(location_id) ON DELETE RESTRICT;
+CREATE INDEX IF NOT EXISTS idx_time_slots_location ON time_slots(location_id);
+
+UPDATE time_slots ts
+SET location_id = l.location_id,
+    place_name = l.name
+FROM locations l
+WHERE lower(btrim(ts.place_name)) = lower(l.name);
+ALTER TABLE time_slots ALTER COLUMN location_id SET NOT NULL;
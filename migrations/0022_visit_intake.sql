@@ -0,0 +1,30 @@
+-- Visit reason given by the patient when booking, and the intake
+-- questionnaire each department asks before a visit.
+ALTER TABLE appointments ADD COLUMN IF NOT EXISTS chief_complaint text;
+ALTER TABLE appointments ADD COLUMN IF NOT EXISTS patient_notes text;
+
+CREATE TABLE IF NOT EXISTS intake_questions (
+  question_id    int GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  department_id  int NOT NULL REFERENCES departments(department_id) ON DELETE CASCADE,
+  position       int NOT NULL,
+  prompt         text NOT NULL,
+  kind           varchar NOT NULL CHECK (kind IN ('TEXT','YES_NO','CHOICE')),
+  options        text[] NOT NULL DEFAULT '{}',
+  required       boolean NOT NULL DEFAULT false,
+  created_at     timestamptz NOT NULL DEFAULT now(),
+  CONSTRAINT intake_questions_options_ck
+    CHECK ((kind = 'CHOICE') = (cardinality(options) > 0))
+);
+CREATE INDEX IF NOT EXISTS idx_intake_questions_department
+  ON intake_questions(department_id, position);
+
+-- Answers keep a copy of the question wording, so they stay readable after
+-- the questionnaire is edited.
+CREATE TABLE IF NOT EXISTS appointment_intake_answers (
+  appointment_id  int NOT NULL REFERENCES appointments(appointment_id) ON DELETE CASCADE,
+  position        int NOT NULL,
+  question_id     int REFERENCES intake_questions(question_id) ON DELETE SET NULL,
+  prompt          text NOT NULL,
+  answer          text NOT NULL,
+  PRIMARY KEY (appointment_id, position)
+);
@@ -0,0 +1,18 @@
+-- Day-of-visit queue. Checking in gives the appointment the next number in
+-- its doctor's queue for the day; the doctor then calls patients in order.
+ALTER TABLE appointments ADD COLUMN IF NOT EXISTS queue_number int;
+ALTER TABLE appointments ADD COLUMN IF NOT EXISTS checked_in_at timestamptz;
+ALTER TABLE appointments ADD COLUMN IF NOT EXISTS called_at timestamptz;
+
+-- The last number handed out per doctor and day. Taking a number is a
+-- single upsert, so concurrent check-ins never share one.
+CREATE TABLE IF NOT EXISTS visit_queue_counters (
+  doctor_id    uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  date         date NOT NULL,
+  last_number  int NOT NULL,
+  PRIMARY KEY (doctor_id, date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_appointments_queue
+  ON appointments(date, queue_number)
+  WHERE queue_number IS NOT NULL;
@@ -0,0 +1,59 @@
+-- When a timeslot can be booked, in one place for every query that needs
+-- it. Both functions are plain SQL so the planner inlines them and pushes
+-- the caller's filters down.
+
+-- Places taken in a timeslot on a date: appointments other than REJECTED
+-- and CANCELED ones, plus waitlist holds that have not lapsed.
+CREATE OR REPLACE FUNCTION timeslot_places_taken(p_timeslot_id int, p_date date)
+RETURNS bigint LANGUAGE sql STABLE AS $$
+  SELECT
+      (SELECT COUNT(*)
+       FROM appointments a
+       WHERE a.timeslot_id = p_timeslot_id
+         AND a.date = p_date
+         AND a.status NOT IN ('REJECTED','CANCELED'))
+    + (SELECT COUNT(*)
+       FROM waitlist_entries w
+       WHERE w.offered_timeslot_id = p_timeslot_id
+         AND w.date = p_date
+         AND w.status = 'OFFERED'
+         AND w.offer_expires_at > now())
+$$;
+
+-- Every occurrence of a timeslot held between two dates, with its free
+-- places: the weekday matches, one-off timeslots fall on their date, the
+-- clinic is open, the doctor is not on leave and the occurrence is not
+-- cancelled.
+CREATE OR REPLACE FUNCTION timeslot_openings(p_from date, p_to date)
+RETURNS TABLE (
+  doctor_id   uuid,
+  timeslot_id int,
+  date        date,
+  place_name  varchar,
+  start_time  time,
+  end_time    time,
+  capacity    int,
+  free        bigint
+) LANGUAGE sql STABLE AS $$
+  SELECT ts.doctor_id, ts.timeslot_id, d.day::date, ts.place_name,
+         ts.start_time, ts.end_time, ts.capacity,
+         GREATEST(ts.capacity - timeslot_places_taken(ts.timeslot_id, d.day::date), 0)
+  FROM generate_series(p_from, p_to, interval '1 day') AS d(day)
+  JOIN time_slots ts
+    ON ts.day_of_weeks = extract(dow FROM d.day)::int
+   AND (ts.valid_on IS NULL OR ts.valid_on = d.day::date)
+  WHERE NOT EXISTS (
+          SELECT 1 FROM clinic_holidays h WHERE h.holiday_date = d.day::date
+        )
+    AND NOT EXISTS (
+          SELECT 1
+          FROM schedule_exceptions se
+          WHERE se.doctor_id = ts.doctor_id
+            AND (
+                  (se.kind = 'LEAVE' AND d.day::date BETWEEN se.start_date AND se.end_date)
+               OR (se.kind = 'CANCELLED'
+                   AND se.timeslot_id = ts.timeslot_id
+                   AND se.start_date = d.day::date)
+            )
+        )
+$$;
@@ -0,0 +1,7 @@
+-- Cancelled and rejected appointments stay on record, so a patient may hold
+-- only one live booking per timeslot and date, not one row ever.
+ALTER TABLE appointments DROP CONSTRAINT IF EXISTS uniq_patient_timeslot_date;
+
+CREATE UNIQUE INDEX IF NOT EXISTS uniq_patient_timeslot_date_active
+  ON appointments(patient_id, timeslot_id, date)
+  WHERE status NOT IN ('REJECTED','CANCELED');
@@ -0,0 +1,24 @@
+-- Messages telling a waitlisted patient that a place is held for them.
+-- They are queued in the transaction that makes the offer, so an offer that
+-- is rolled back is never announced, and sent by the jobs loop after commit.
+CREATE TABLE IF NOT EXISTS waitlist_offer_notices (
+  notice_id        bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  entry_id         int NOT NULL REFERENCES waitlist_entries(entry_id) ON DELETE CASCADE,
+  channel          varchar NOT NULL CHECK (channel IN ('email','sms','push')),
+  recipient        varchar NOT NULL,
+  subject          varchar NOT NULL,
+  body             text NOT NULL,
+  -- PENDING until SENT; FAILED after the last attempt; SKIPPED when the
+  -- hold had already ended by the time it came to be sent.
+  status           varchar NOT NULL DEFAULT 'PENDING'
+                   CHECK (status IN ('PENDING','SENT','FAILED','SKIPPED')),
+  attempts         int NOT NULL DEFAULT 0,
+  last_error       text,
+  next_attempt_at  timestamptz NOT NULL DEFAULT now(),
+  sent_at          timestamptz,
+  created_at       timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_waitlist_offer_notices_due
+  ON waitlist_offer_notices(next_attempt_at)
+  WHERE status = 'PENDING';